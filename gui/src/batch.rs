@@ -0,0 +1,423 @@
+// 標準庫導入
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// 第三方庫導入
+use log::{error, info};
+use serde::Serialize;
+use tokio::time::{interval, MissedTickBehavior};
+
+// 本地模組導入
+use crate::matching::{BestMatch, MatchScore, SongFields};
+use crate::osu::{
+    beatmapset_id_from_file_name, is_beatmap_downloaded, read_local_beatmapset, Beatmapset,
+    LocalBeatmapset,
+};
+use crate::search::{CrossSearchEngine, SearchError};
+use crate::spotify::{track_id_from_track, Track};
+
+// osu! API 建議每分鐘不超過 60 次請求，批次比對時每首曲目間隔一秒
+const DEFAULT_REQUEST_INTERVAL: Duration = Duration::from_millis(1000);
+// 「全部加入下載」只採用信心分數達到這個值的匹配，避免把不相關的譜面也下載下來
+pub const MIN_QUEUE_CONFIDENCE: f32 = 0.6;
+// 反向比對時信心分數低於這個值的曲目需要使用者確認後才加入播放清單
+pub const MIN_AUTO_ADD_CONFIDENCE: f32 = 0.75;
+// 本機譜面反向比對後建立的 Spotify 播放清單名稱
+pub const LOCAL_LIBRARY_PLAYLIST_NAME: &str = "My osu! songs";
+
+// 單首曲目的批次比對結果
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchMatchEntry {
+    pub track: Track,
+    pub beatmapset: Option<Beatmapset>,
+    pub best_match: Option<BestMatch>,
+    pub downloaded: bool,
+    // 這首曲目搜尋失敗時的錯誤訊息
+    pub error: Option<String>,
+}
+
+impl BatchMatchEntry {
+    pub fn confidence(&self) -> Option<f32> {
+        self.best_match.map(|best| best.score.confidence)
+    }
+
+    // 有匹配、分數夠高且尚未下載的才需要加入下載
+    pub fn should_queue(&self) -> bool {
+        !self.downloaded
+            && self
+                .confidence()
+                .is_some_and(|confidence| confidence >= MIN_QUEUE_CONFIDENCE)
+    }
+}
+
+// 整份播放清單的比對報告
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchMatchReport {
+    pub entries: Vec<BatchMatchEntry>,
+    // 使用者中途取消時為 true，entries 只包含已處理的曲目
+    pub cancelled: bool,
+}
+
+impl BatchMatchReport {
+    pub fn matched_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.beatmapset.is_some())
+            .count()
+    }
+
+    pub fn downloaded_count(&self) -> usize {
+        self.entries.iter().filter(|e| e.downloaded).count()
+    }
+
+    // 可一次加入下載隊列的譜面集 ID，同一個譜面集只出現一次
+    pub fn queueable_beatmapset_ids(&self) -> Vec<i32> {
+        queueable_beatmapset_ids(&self.entries)
+    }
+}
+
+// 由比對結果挑出要加入下載的譜面集 ID，介面在比對進行中也會用到
+pub fn queueable_beatmapset_ids(entries: &[BatchMatchEntry]) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter(|e| e.should_queue())
+        .filter_map(|e| e.best_match.map(|best| best.beatmapset_id))
+        .filter(|id| seen.insert(*id))
+        .collect()
+}
+
+// 信心分數夠高的譜面集 ID（不論是否已下載），用來建立 osu! 收藏
+pub fn confident_beatmapset_ids(entries: &[BatchMatchEntry]) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter(|e| {
+            e.confidence()
+                .is_some_and(|confidence| confidence >= MIN_QUEUE_CONFIDENCE)
+        })
+        .filter_map(|e| e.best_match.map(|best| best.beatmapset_id))
+        .filter(|id| seen.insert(*id))
+        .collect()
+}
+
+// 背景逐首比對播放清單或 Liked Songs 的工作
+pub struct BatchMatchJob {
+    engine: CrossSearchEngine,
+    download_directory: PathBuf,
+    request_interval: Duration,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl BatchMatchJob {
+    pub fn new(engine: CrossSearchEngine, download_directory: PathBuf) -> Self {
+        Self {
+            engine,
+            download_directory,
+            request_interval: DEFAULT_REQUEST_INTERVAL,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // 設定兩次 osu! 搜尋之間的最短間隔
+    pub fn with_request_interval(mut self, request_interval: Duration) -> Self {
+        self.request_interval = request_interval;
+        self
+    }
+
+    // 共用同一個取消旗標，讓介面可以在工作進行中將其中止
+    pub fn with_cancel_flag(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    // 逐首搜尋並回報進度，每完成一首就呼叫一次 on_entry
+    pub async fn run<F>(
+        &self,
+        tracks: Vec<Track>,
+        mut on_entry: F,
+    ) -> Result<BatchMatchReport, SearchError>
+    where
+        F: FnMut(&BatchMatchEntry),
+    {
+        let osu_token = self.engine.osu_token().await?;
+        let total = tracks.len();
+        info!("開始批次比對 {} 首曲目", total);
+
+        let mut ticker = interval(self.request_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut report = BatchMatchReport::default();
+        for (index, track) in tracks.into_iter().enumerate() {
+            if self.cancelled.load(Ordering::SeqCst) {
+                info!("批次比對已取消，完成 {}/{} 首", index, total);
+                report.cancelled = true;
+                break;
+            }
+            ticker.tick().await;
+
+            // 單首失敗不影響整份清單，記錄錯誤後繼續
+            let entry = match self
+                .engine
+                .best_beatmapset_for_track(&osu_token, &track)
+                .await
+            {
+                Ok(Some((beatmapset, best_match))) => BatchMatchEntry {
+                    downloaded: is_beatmap_downloaded(&self.download_directory, beatmapset.id),
+                    beatmapset: Some(beatmapset),
+                    best_match: Some(best_match),
+                    track,
+                    error: None,
+                },
+                Ok(None) => BatchMatchEntry {
+                    track,
+                    beatmapset: None,
+                    best_match: None,
+                    downloaded: false,
+                    error: None,
+                },
+                Err(e) => {
+                    error!("批次比對 \"{}\" 失敗: {:?}", track.name, e);
+                    BatchMatchEntry {
+                        track,
+                        beatmapset: None,
+                        best_match: None,
+                        downloaded: false,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+
+            on_entry(&entry);
+            report.entries.push(entry);
+        }
+
+        info!(
+            "批次比對結束：{} 首中找到 {} 首，已下載 {} 首",
+            report.entries.len(),
+            report.matched_count(),
+            report.downloaded_count()
+        );
+        Ok(report)
+    }
+}
+
+// 單一本機譜面反向比對到 Spotify 的結果
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalSongMatch {
+    // 下載資料夾中的檔案或資料夾名稱
+    pub file_name: String,
+    pub beatmapset: LocalBeatmapset,
+    pub track: Option<Track>,
+    pub score: Option<MatchScore>,
+    // 是否要加入播放清單，高信心的匹配預設勾選，其餘等使用者確認
+    pub approved: bool,
+    pub error: Option<String>,
+}
+
+impl LocalSongMatch {
+    pub fn confidence(&self) -> Option<f32> {
+        self.score.map(|score| score.confidence)
+    }
+
+    // 找到曲目但信心分數不足，需要使用者確認
+    pub fn needs_review(&self) -> bool {
+        self.track.is_some()
+            && self
+                .confidence()
+                .is_some_and(|confidence| confidence < MIN_AUTO_ADD_CONFIDENCE)
+    }
+
+    pub fn spotify_track_id(&self) -> Option<String> {
+        self.track.as_ref().and_then(track_id_from_track)
+    }
+}
+
+// 由使用者確認過的比對結果取出要加入播放清單的曲目 ID，同一首只出現一次
+pub fn approved_track_ids(matches: &[LocalSongMatch]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    matches
+        .iter()
+        .filter(|m| m.approved)
+        .filter_map(LocalSongMatch::spotify_track_id)
+        .filter(|id| seen.insert(id.clone()))
+        .collect()
+}
+
+// 背景逐一把下載資料夾中的譜面反查 Spotify 曲目的工作
+pub struct LocalLibraryMatchJob {
+    engine: CrossSearchEngine,
+    download_directory: PathBuf,
+    request_interval: Duration,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl LocalLibraryMatchJob {
+    pub fn new(engine: CrossSearchEngine, download_directory: PathBuf) -> Self {
+        Self {
+            engine,
+            download_directory,
+            request_interval: DEFAULT_REQUEST_INTERVAL,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // 設定兩次搜尋之間的最短間隔
+    pub fn with_request_interval(mut self, request_interval: Duration) -> Self {
+        self.request_interval = request_interval;
+        self
+    }
+
+    // 共用同一個取消旗標，讓介面可以在工作進行中將其中止
+    pub fn with_cancel_flag(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    // file_names 為 get_downloaded_beatmaps 回傳的名稱，每完成一個就呼叫一次 on_entry
+    pub async fn run<F>(
+        &self,
+        file_names: Vec<String>,
+        mut on_entry: F,
+    ) -> Result<Vec<LocalSongMatch>, SearchError>
+    where
+        F: FnMut(&LocalSongMatch),
+    {
+        let spotify_token = self.engine.spotify_token().await?;
+        // osu! token 只有在需要向 API 查詢譜面資訊時才取得
+        let mut osu_token: Option<String> = None;
+        let total = file_names.len();
+        info!("開始反向比對 {} 個本機譜面", total);
+
+        let mut ticker = interval(self.request_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut matches = Vec::new();
+        for (index, file_name) in file_names.into_iter().enumerate() {
+            if self.cancelled.load(Ordering::SeqCst) {
+                info!("反向比對已取消，完成 {}/{} 個", index, total);
+                break;
+            }
+            ticker.tick().await;
+
+            let entry = match self.read_metadata(&file_name, &mut osu_token).await {
+                Ok(beatmapset) => {
+                    self.match_beatmapset(&spotify_token, file_name, beatmapset)
+                        .await
+                }
+                Err(e) => {
+                    error!("無法取得 \"{}\" 的譜面資訊: {:?}", file_name, e);
+                    LocalSongMatch {
+                        file_name,
+                        beatmapset: LocalBeatmapset::default(),
+                        track: None,
+                        score: None,
+                        approved: false,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+
+            on_entry(&entry);
+            matches.push(entry);
+        }
+
+        info!(
+            "反向比對結束：{} 個中找到 {} 首，{} 首待確認",
+            matches.len(),
+            matches.iter().filter(|m| m.track.is_some()).count(),
+            matches.iter().filter(|m| m.needs_review()).count()
+        );
+        Ok(matches)
+    }
+
+    // 優先讀取資料夾或 .osz 中的 .osu 檔，讀不到時改用檔名中的 ID 向 API 查詢
+    async fn read_metadata(
+        &self,
+        file_name: &str,
+        osu_token: &mut Option<String>,
+    ) -> Result<LocalBeatmapset, SearchError> {
+        match read_local_beatmapset(&self.download_directory.join(file_name)) {
+            Ok(beatmapset) => return Ok(beatmapset),
+            Err(e) => info!("無法讀取 \"{}\"，改向 API 查詢: {}", file_name, e),
+        }
+
+        let beatmapset_id = beatmapset_id_from_file_name(file_name)
+            .ok_or_else(|| SearchError::Osu(format!("無法從檔名取得譜面 ID: {}", file_name)))?;
+        let token = match osu_token {
+            Some(token) => token.clone(),
+            None => {
+                let token = self.engine.osu_token().await?;
+                *osu_token = Some(token.clone());
+                token
+            }
+        };
+        let beatmapset = self.engine.beatmapset_by_id(&token, beatmapset_id).await?;
+        Ok(local_from_api(&beatmapset, file_name))
+    }
+
+    async fn match_beatmapset(
+        &self,
+        spotify_token: &str,
+        file_name: String,
+        beatmapset: LocalBeatmapset,
+    ) -> LocalSongMatch {
+        let result = self
+            .engine
+            .best_track_for_song(spotify_token, &SongFields::from(&beatmapset))
+            .await;
+
+        match result {
+            Ok(Some((track, score))) => LocalSongMatch {
+                approved: score.confidence >= MIN_AUTO_ADD_CONFIDENCE,
+                file_name,
+                beatmapset,
+                track: Some(track),
+                score: Some(score),
+                error: None,
+            },
+            Ok(None) => LocalSongMatch {
+                file_name,
+                beatmapset,
+                track: None,
+                score: None,
+                approved: false,
+                error: None,
+            },
+            Err(e) => {
+                error!("反向比對 \"{}\" 失敗: {:?}", file_name, e);
+                LocalSongMatch {
+                    file_name,
+                    beatmapset,
+                    track: None,
+                    score: None,
+                    approved: false,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+}
+
+// API 回傳的譜面集只有歌曲資訊，沒有音訊與背景等本機檔案資訊
+fn local_from_api(beatmapset: &Beatmapset, file_name: &str) -> LocalBeatmapset {
+    LocalBeatmapset {
+        id: Some(beatmapset.id),
+        artist: beatmapset.artist.clone(),
+        artist_unicode: beatmapset.artist_unicode.clone(),
+        title: beatmapset.title.clone(),
+        title_unicode: beatmapset.title_unicode.clone(),
+        creator: beatmapset.creator.clone(),
+        length_secs: beatmapset
+            .beatmaps
+            .iter()
+            .map(|b| b.total_length)
+            .max()
+            .unwrap_or(0),
+        file_name: file_name.to_string(),
+        ..Default::default()
+    }
+}
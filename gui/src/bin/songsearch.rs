@@ -0,0 +1,219 @@
+// 標準庫導入
+use std::collections::HashMap;
+use std::process::ExitCode;
+
+// 第三方庫導入
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use log::{error, LevelFilter};
+use reqwest::Client;
+use simplelog::{ColorChoice, TermLogger, TerminalMode};
+
+// 本地模組導入
+use lib::search::{CrossSearchEngine, CrossSearchResult, QueryKind};
+use lib::spotify::{SpotifySearchType, Track};
+
+// 無介面版本的 Spotify ↔ osu! 交叉搜尋
+#[derive(Parser)]
+#[command(
+    name = "songsearch",
+    version,
+    about = "Spotify 與 osu! 交叉搜尋（命令列版本）"
+)]
+struct Cli {
+    /// 輸出格式
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    format: OutputFormat,
+
+    /// 關鍵字搜尋類型，專輯、藝人、播放清單會展開成曲目逐首搜尋 osu!
+    #[arg(long = "type", value_enum, default_value_t = SearchType::Track, global = true)]
+    search_type: SearchType,
+
+    /// 每個平台最多輸出的結果數
+    #[arg(long, default_value_t = 10, global = true)]
+    limit: u32,
+
+    /// 開啟除錯模式（輸出詳細日誌）
+    #[arg(long, global = true)]
+    debug: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 以關鍵字、Spotify 曲目網址或 osu! 譜面集網址進行交叉搜尋
+    Search { query: String },
+    /// 由 osu! 譜面集網址找出 Spotify 上的同一首歌
+    OsuToSpotify { url: String },
+    /// 由 Spotify 曲目、專輯、藝人或播放清單網址找出 osu! 上的譜面集
+    SpotifyToOsu { url: String },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SearchType {
+    Track,
+    Album,
+    Artist,
+    Playlist,
+}
+
+impl From<SearchType> for SpotifySearchType {
+    fn from(search_type: SearchType) -> Self {
+        match search_type {
+            SearchType::Track => SpotifySearchType::Track,
+            SearchType::Album => SpotifySearchType::Album,
+            SearchType::Artist => SpotifySearchType::Artist,
+            SearchType::Playlist => SpotifySearchType::Playlist,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // 日誌輸出到 stderr，避免污染 stdout 上的表格或 JSON
+    let log_level = if cli.debug {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Warn
+    };
+    if let Err(e) = TermLogger::init(
+        log_level,
+        simplelog::Config::default(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    ) {
+        eprintln!("Failed to initialize logger: {:?}", e);
+    }
+
+    let engine = CrossSearchEngine::new(Client::new(), cli.debug)
+        .with_spotify_limit(cli.limit)
+        .with_spotify_search_type(cli.search_type.into());
+    let result = run(&engine, &cli.command, cli.limit as usize).await;
+
+    match result {
+        Ok(output) => match print_output(&output, cli.format) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("錯誤: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        Err(e) => {
+            error!("搜尋失敗: {:?}", e);
+            eprintln!("錯誤: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(
+    engine: &CrossSearchEngine,
+    command: &Command,
+    limit: usize,
+) -> Result<CrossSearchResult> {
+    // 子命令限定輸入類型，避免把關鍵字誤當成網址送出
+    let query = match command {
+        Command::Search { query } => query,
+        Command::OsuToSpotify { url } => {
+            if !matches!(
+                CrossSearchEngine::classify_query(url)?,
+                QueryKind::OsuBeatmapset { .. }
+            ) {
+                return Err(anyhow!("不是有效的 osu! 譜面集網址: {}", url));
+            }
+            url
+        }
+        Command::SpotifyToOsu { url } => {
+            if !matches!(
+                CrossSearchEngine::classify_query(url)?,
+                QueryKind::SpotifyTrack { .. }
+                    | QueryKind::SpotifyAlbum { .. }
+                    | QueryKind::SpotifyArtist { .. }
+                    | QueryKind::SpotifyPlaylist { .. }
+            ) {
+                return Err(anyhow!("不是有效的 Spotify 網址: {}", url));
+            }
+            url
+        }
+    };
+
+    let mut result = engine.search(query).await?;
+    result.osu_beatmapsets.truncate(limit);
+    Ok(result)
+}
+
+fn print_output(output: &CrossSearchResult, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(output)?);
+        }
+        OutputFormat::Table => {
+            println!("查詢類型: {}", output.kind);
+            println!();
+            println!("Spotify（{} 筆）", output.spotify_tracks.len());
+            for (index, track) in output.spotify_tracks.iter().enumerate() {
+                println!(
+                    "{:>3}. {} - {} | {} | {}",
+                    index + 1,
+                    join_artists(track),
+                    track.name,
+                    track.album.name,
+                    spotify_url(&track.external_urls)
+                );
+            }
+
+            println!();
+            println!("osu!（{} 筆）", output.osu_beatmapsets.len());
+            for (index, beatmapset) in output.osu_beatmapsets.iter().enumerate() {
+                let best_match = output
+                    .best_match
+                    .iter()
+                    .chain(
+                        output
+                            .track_matches
+                            .iter()
+                            .filter_map(|m| m.best_match.as_ref()),
+                    )
+                    .find(|best| best.beatmapset_id == beatmapset.id)
+                    .map(|best| format!(" | 最佳匹配 {:.0}%", best.score.confidence * 100.0))
+                    .unwrap_or_default();
+                println!(
+                    "{:>3}. {} - {} | by {} | https://osu.ppy.sh/beatmapsets/{}{}",
+                    index + 1,
+                    beatmapset.artist,
+                    beatmapset.title,
+                    beatmapset.creator,
+                    beatmapset.id,
+                    best_match
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn join_artists(track: &Track) -> String {
+    track
+        .artists
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn spotify_url(external_urls: &HashMap<String, String>) -> &str {
+    external_urls
+        .get("spotify")
+        .map(String::as_str)
+        .unwrap_or("-")
+}
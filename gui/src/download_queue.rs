@@ -0,0 +1,439 @@
+// 標準庫導入
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 第三方庫導入
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// 本地模組導入
+use crate::get_app_data_path;
+use crate::mirror::DownloadVariant;
+
+// 佇列檔存放在應用數據目錄下
+const QUEUE_FILE_NAME: &str = "download_queue.json";
+// 失敗後自動重試的次數，超過就停在失敗狀態等使用者處理
+pub const MAX_AUTO_RETRIES: u32 = 2;
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("IO 錯誤: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON 解析錯誤: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueueItemState {
+    Queued,
+    Downloading,
+    Paused,
+    Failed,
+    Completed,
+}
+
+impl QueueItemState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            QueueItemState::Queued => "等待中",
+            QueueItemState::Downloading => "下載中",
+            QueueItemState::Paused => "已暫停",
+            QueueItemState::Failed => "失敗",
+            QueueItemState::Completed => "已完成",
+        }
+    }
+
+    // 還沒結束、之後會被下載的項目
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self,
+            QueueItemState::Queued | QueueItemState::Downloading | QueueItemState::Paused
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub beatmapset_id: i32,
+    // 顯示用的名稱，例如 "Artist - Title"
+    pub title: String,
+    // 要求的版本，完成後改為實際下載到的版本
+    #[serde(default)]
+    pub variant: DownloadVariant,
+    pub state: QueueItemState,
+    pub retries: u32,
+    pub last_error: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+// 寫入磁碟的下載佇列，依順序下載
+pub struct DownloadQueue {
+    items: Vec<QueueItem>,
+    debug_mode: bool,
+}
+
+impl DownloadQueue {
+    fn queue_path() -> PathBuf {
+        get_app_data_path().join(QUEUE_FILE_NAME)
+    }
+
+    // 讀取上次的佇列，上次關閉時還在下載的項目重新排隊（.part 檔可續傳）
+    pub fn load(debug_mode: bool) -> Self {
+        Self::load_from(&Self::queue_path(), debug_mode)
+    }
+
+    fn load_from(path: &Path, debug_mode: bool) -> Self {
+        let mut items: Vec<QueueItem> = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                // 先把壞掉的佇列檔改名保留，之後儲存時才不會蓋掉使用者原本的佇列
+                let backup_path = path.with_extension("json.bak");
+                match fs::rename(path, &backup_path) {
+                    Ok(()) => error!(
+                        "無法解析下載佇列，已備份至 {:?} 並重新建立: {:?}",
+                        backup_path, e
+                    ),
+                    Err(rename_error) => {
+                        error!("無法解析下載佇列: {:?}，備份失敗: {:?}", e, rename_error)
+                    }
+                }
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                error!("無法讀取下載佇列: {:?}", e);
+                Vec::new()
+            }
+        };
+        for item in items.iter_mut() {
+            if item.state == QueueItemState::Downloading {
+                item.state = QueueItemState::Queued;
+            }
+        }
+        let pending = items.iter().filter(|item| item.state.is_pending()).count();
+        if pending > 0 {
+            info!("已載入下載佇列，{} 個項目待下載", pending);
+        }
+        Self { items, debug_mode }
+    }
+
+    pub fn save(&self) -> Result<(), QueueError> {
+        fs::create_dir_all(get_app_data_path())?;
+        self.save_to(&Self::queue_path())
+    }
+
+    // 先寫到暫存檔再取代，寫到一半當機時原本的佇列檔仍然完整
+    fn save_to(&self, path: &Path) -> Result<(), QueueError> {
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string(&self.items)?)?;
+        fs::rename(&temp_path, path)?;
+        if self.debug_mode {
+            debug!("已儲存下載佇列，共 {} 個項目", self.items.len());
+        }
+        Ok(())
+    }
+
+    pub fn items(&self) -> &[QueueItem] {
+        &self.items
+    }
+
+    pub fn get(&self, beatmapset_id: i32) -> Option<&QueueItem> {
+        self.items
+            .iter()
+            .find(|item| item.beatmapset_id == beatmapset_id)
+    }
+
+    fn get_mut(&mut self, beatmapset_id: i32) -> Option<&mut QueueItem> {
+        self.items
+            .iter_mut()
+            .find(|item| item.beatmapset_id == beatmapset_id)
+    }
+
+    // 加入佇列尾端，已在佇列中且尚未結束時回傳 false
+    pub fn enqueue(&mut self, beatmapset_id: i32, title: String, variant: DownloadVariant) -> bool {
+        if let Some(item) = self.get_mut(beatmapset_id) {
+            if item.state.is_pending() {
+                return false;
+            }
+            // 已完成或失敗的項目重新排到最後
+            self.items
+                .retain(|item| item.beatmapset_id != beatmapset_id);
+        }
+        self.items.push(QueueItem {
+            beatmapset_id,
+            title,
+            variant,
+            state: QueueItemState::Queued,
+            retries: 0,
+            last_error: None,
+            added_at: Utc::now(),
+        });
+        true
+    }
+
+    // 取出下一個要下載的項目並標記為下載中
+    pub fn start_next(&mut self) -> Option<(i32, DownloadVariant)> {
+        let item = self
+            .items
+            .iter_mut()
+            .find(|item| item.state == QueueItemState::Queued)?;
+        item.state = QueueItemState::Downloading;
+        Some((item.beatmapset_id, item.variant))
+    }
+
+    pub fn mark_completed(&mut self, beatmapset_id: i32, variant: DownloadVariant) {
+        if let Some(item) = self.get_mut(beatmapset_id) {
+            item.state = QueueItemState::Completed;
+            item.variant = variant;
+            item.last_error = None;
+        }
+    }
+
+    // 記錄失敗，次數未超過上限時排回佇列自動重試；回傳是否會重試
+    pub fn mark_failed(&mut self, beatmapset_id: i32, error: String) -> bool {
+        let Some(item) = self.get_mut(beatmapset_id) else {
+            return false;
+        };
+        // 下載途中被暫停或取消時不算失敗
+        if item.state != QueueItemState::Downloading {
+            return false;
+        }
+        item.last_error = Some(error);
+        if item.retries < MAX_AUTO_RETRIES {
+            item.retries += 1;
+            item.state = QueueItemState::Queued;
+            true
+        } else {
+            item.state = QueueItemState::Failed;
+            false
+        }
+    }
+
+    pub fn pause(&mut self, beatmapset_id: i32) -> bool {
+        match self.get_mut(beatmapset_id) {
+            Some(item)
+                if matches!(
+                    item.state,
+                    QueueItemState::Queued | QueueItemState::Downloading
+                ) =>
+            {
+                item.state = QueueItemState::Paused;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn resume(&mut self, beatmapset_id: i32) -> bool {
+        match self.get_mut(beatmapset_id) {
+            Some(item) if item.state == QueueItemState::Paused => {
+                item.state = QueueItemState::Queued;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // 手動重試會重設重試次數
+    pub fn retry(&mut self, beatmapset_id: i32) -> bool {
+        match self.get_mut(beatmapset_id) {
+            Some(item) if item.state == QueueItemState::Failed => {
+                item.state = QueueItemState::Queued;
+                item.retries = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn cancel(&mut self, beatmapset_id: i32) -> Option<QueueItem> {
+        let index = self
+            .items
+            .iter()
+            .position(|item| item.beatmapset_id == beatmapset_id)?;
+        Some(self.items.remove(index))
+    }
+
+    // 往前（負數）或往後移動，改變下載順序
+    pub fn move_by(&mut self, beatmapset_id: i32, offset: isize) {
+        let Some(index) = self
+            .items
+            .iter()
+            .position(|item| item.beatmapset_id == beatmapset_id)
+        else {
+            return;
+        };
+        let target = index
+            .saturating_add_signed(offset)
+            .min(self.items.len() - 1);
+        let item = self.items.remove(index);
+        self.items.insert(target, item);
+    }
+
+    pub fn clear_completed(&mut self) {
+        self.items
+            .retain(|item| item.state != QueueItemState::Completed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn queue_with(ids: &[i32]) -> DownloadQueue {
+        let mut queue = DownloadQueue {
+            items: Vec::new(),
+            debug_mode: false,
+        };
+        for id in ids {
+            queue.enqueue(*id, format!("Beatmapset {}", id), DownloadVariant::Full);
+        }
+        queue
+    }
+
+    #[test]
+    fn save_and_load_requeues_interrupted_downloads() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(QUEUE_FILE_NAME);
+        let mut queue = queue_with(&[1, 2]);
+        queue.start_next();
+        queue.save_to(&path).unwrap();
+
+        let loaded = DownloadQueue::load_from(&path, false);
+
+        assert!(!path.with_extension("json.tmp").exists());
+        assert_eq!(loaded.items().len(), 2);
+        assert_eq!(loaded.get(1).unwrap().state, QueueItemState::Queued);
+        assert_eq!(loaded.get(2).unwrap().state, QueueItemState::Queued);
+    }
+
+    #[test]
+    fn corrupt_queue_file_is_backed_up_before_starting_empty() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(QUEUE_FILE_NAME);
+        fs::write(&path, "[{\"beatmapset_id\": 1,").unwrap();
+
+        let queue = DownloadQueue::load_from(&path, false);
+        queue.save_to(&path).unwrap();
+
+        assert!(queue.items().is_empty());
+        assert_eq!(
+            fs::read_to_string(path.with_extension("json.bak")).unwrap(),
+            "[{\"beatmapset_id\": 1,"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+    }
+
+    #[test]
+    fn missing_queue_file_starts_empty() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(QUEUE_FILE_NAME);
+
+        let queue = DownloadQueue::load_from(&path, false);
+
+        assert!(queue.items().is_empty());
+        assert!(!path.with_extension("json.bak").exists());
+    }
+
+    fn ids(queue: &DownloadQueue) -> Vec<i32> {
+        queue
+            .items()
+            .iter()
+            .map(|item| item.beatmapset_id)
+            .collect()
+    }
+
+    #[test]
+    fn enqueue_skips_pending_items_and_requeues_finished_ones() {
+        let mut queue = queue_with(&[1, 2]);
+
+        assert!(!queue.enqueue(1, "again".to_string(), DownloadVariant::Full));
+        queue.start_next();
+        queue.mark_completed(1, DownloadVariant::NoVideo);
+        assert!(queue.enqueue(1, "again".to_string(), DownloadVariant::Full));
+
+        assert_eq!(ids(&queue), [2, 1]);
+        assert_eq!(queue.get(1).unwrap().state, QueueItemState::Queued);
+    }
+
+    #[test]
+    fn mark_failed_retries_until_limit() {
+        let mut queue = queue_with(&[1]);
+
+        for attempt in 0..MAX_AUTO_RETRIES {
+            assert_eq!(queue.start_next(), Some((1, DownloadVariant::Full)));
+            assert!(queue.mark_failed(1, format!("error {}", attempt)));
+            assert_eq!(queue.get(1).unwrap().retries, attempt + 1);
+        }
+        queue.start_next();
+        assert!(!queue.mark_failed(1, "last error".to_string()));
+
+        let item = queue.get(1).unwrap();
+        assert_eq!(item.state, QueueItemState::Failed);
+        assert_eq!(item.last_error.as_deref(), Some("last error"));
+        assert_eq!(queue.start_next(), None);
+
+        // 手動重試重新計算次數
+        assert!(queue.retry(1));
+        assert_eq!(queue.get(1).unwrap().retries, 0);
+        assert_eq!(queue.get(1).unwrap().state, QueueItemState::Queued);
+    }
+
+    #[test]
+    fn mark_failed_ignores_paused_or_cancelled_downloads() {
+        let mut queue = queue_with(&[1, 2]);
+        queue.start_next();
+        queue.pause(1);
+
+        assert!(!queue.mark_failed(1, "interrupted".to_string()));
+        assert_eq!(queue.get(1).unwrap().state, QueueItemState::Paused);
+        assert!(queue.get(1).unwrap().last_error.is_none());
+        assert!(!queue.mark_failed(3, "unknown".to_string()));
+    }
+
+    #[test]
+    fn pause_resume_and_cancel() {
+        let mut queue = queue_with(&[1, 2, 3]);
+
+        assert!(queue.pause(1));
+        assert!(!queue.pause(1));
+        assert_eq!(queue.start_next(), Some((2, DownloadVariant::Full)));
+        assert!(queue.pause(2));
+        assert!(queue.resume(1));
+        assert!(!queue.resume(3));
+        assert_eq!(queue.start_next(), Some((1, DownloadVariant::Full)));
+
+        let cancelled = queue.cancel(3).unwrap();
+        assert_eq!(cancelled.beatmapset_id, 3);
+        assert!(queue.cancel(3).is_none());
+        assert_eq!(ids(&queue), [1, 2]);
+    }
+
+    #[test]
+    fn move_by_clamps_to_queue_bounds() {
+        let mut queue = queue_with(&[1, 2, 3, 4]);
+
+        queue.move_by(3, -1);
+        assert_eq!(ids(&queue), [1, 3, 2, 4]);
+        queue.move_by(3, -10);
+        assert_eq!(ids(&queue), [3, 1, 2, 4]);
+        queue.move_by(1, 10);
+        assert_eq!(ids(&queue), [3, 2, 4, 1]);
+        queue.move_by(5, 1);
+        assert_eq!(ids(&queue), [3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn clear_completed_keeps_pending_and_failed_items() {
+        let mut queue = queue_with(&[1, 2, 3]);
+        queue.start_next();
+        queue.mark_completed(1, DownloadVariant::Full);
+        queue.get_mut(2).unwrap().state = QueueItemState::Failed;
+
+        queue.clear_completed();
+
+        assert_eq!(ids(&queue), [2, 3]);
+    }
+}
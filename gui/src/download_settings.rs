@@ -0,0 +1,216 @@
+// 標準庫導入
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// 第三方庫導入
+use chrono::{DateTime, Local, Timelike};
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex as TokioMutex;
+
+// 本地模組導入
+use crate::get_app_data_path;
+use crate::mirror::DownloadVariant;
+
+// 設定檔存放在應用數據目錄下
+const SETTINGS_FILE_NAME: &str = "download_settings.json";
+pub const MAX_CONCURRENT_DOWNLOADS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadSettings {
+    // 同時下載的數量
+    pub max_concurrent: usize,
+    // 所有下載合計的頻寬上限（KB/s），None 表示不限制
+    pub bandwidth_limit_kbps: Option<u64>,
+    // 單一譜面（包含換鏡像站重試）的下載時間上限
+    pub timeout_secs: u64,
+    // 只在這段時間（本地時間的小時，開始含、結束不含）開始新的下載，可跨午夜
+    pub active_hours: Option<(u32, u32)>,
+    // 沒有另外指定時下載的版本
+    pub default_variant: DownloadVariant,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 3,
+            bandwidth_limit_kbps: None,
+            timeout_secs: 300,
+            active_hours: None,
+            default_variant: DownloadVariant::Full,
+        }
+    }
+}
+
+impl DownloadSettings {
+    fn settings_path() -> PathBuf {
+        get_app_data_path().join(SETTINGS_FILE_NAME)
+    }
+
+    pub fn load() -> Self {
+        let Ok(content) = fs::read_to_string(Self::settings_path()) else {
+            return Self::default();
+        };
+        let mut settings: Self = serde_json::from_str(&content).unwrap_or_else(|e| {
+            error!("無法解析下載設定，改用預設值: {:?}", e);
+            Self::default()
+        });
+        settings.max_concurrent = settings.max_concurrent.clamp(1, MAX_CONCURRENT_DOWNLOADS);
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        fs::create_dir_all(get_app_data_path())?;
+        fs::write(Self::settings_path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn bandwidth_limit_bytes(&self) -> u64 {
+        self.bandwidth_limit_kbps.unwrap_or(0) * 1024
+    }
+
+    // 現在是否可以開始新的下載
+    pub fn is_within_active_hours(&self, now: DateTime<Local>) -> bool {
+        let Some((start, end)) = self.active_hours else {
+            return true;
+        };
+        let hour = now.hour();
+        if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+// 所有下載共用的頻寬限制（token bucket），上限為 0 時不限制
+pub struct BandwidthLimiter {
+    bytes_per_sec: AtomicU64,
+    // 上次補充的時間與目前可用的位元組數
+    bucket: TokioMutex<(Instant, f64)>,
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: AtomicU64::new(bytes_per_sec),
+            bucket: TokioMutex::new((Instant::now(), bytes_per_sec as f64)),
+        }
+    }
+
+    pub fn set_limit(&self, bytes_per_sec: u64) {
+        self.bytes_per_sec.store(bytes_per_sec, Ordering::SeqCst);
+    }
+
+    // 用掉 bytes 的額度，不夠時等到補足為止
+    pub async fn consume(&self, bytes: usize) {
+        let limit = self.bytes_per_sec.load(Ordering::SeqCst);
+        if limit == 0 {
+            return;
+        }
+        let limit = limit as f64;
+
+        let mut bucket = self.bucket.lock().await;
+        let (last_refill, available) = &mut *bucket;
+        // 最多累積一秒的額度，避免閒置後突然暴衝
+        *available = (*available + last_refill.elapsed().as_secs_f64() * limit).min(limit);
+        *last_refill = Instant::now();
+        *available -= bytes as f64;
+        if *available < 0.0 {
+            // 持有鎖等待，其他下載也會跟著排隊，合計速度才不會超過上限
+            tokio::time::sleep(Duration::from_secs_f64(-*available / limit)).await;
+            *available = 0.0;
+            *last_refill = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at_hour(hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 1, hour, 30, 0).unwrap()
+    }
+
+    fn with_active_hours(active_hours: Option<(u32, u32)>) -> DownloadSettings {
+        DownloadSettings {
+            active_hours,
+            ..DownloadSettings::default()
+        }
+    }
+
+    #[test]
+    fn active_hours_include_start_and_exclude_end() {
+        let settings = with_active_hours(Some((9, 17)));
+
+        assert!(!settings.is_within_active_hours(at_hour(8)));
+        assert!(settings.is_within_active_hours(at_hour(9)));
+        assert!(settings.is_within_active_hours(at_hour(16)));
+        assert!(!settings.is_within_active_hours(at_hour(17)));
+    }
+
+    #[test]
+    fn active_hours_can_wrap_past_midnight() {
+        let settings = with_active_hours(Some((22, 6)));
+
+        assert!(settings.is_within_active_hours(at_hour(23)));
+        assert!(settings.is_within_active_hours(at_hour(0)));
+        assert!(settings.is_within_active_hours(at_hour(5)));
+        assert!(!settings.is_within_active_hours(at_hour(6)));
+        assert!(!settings.is_within_active_hours(at_hour(12)));
+    }
+
+    #[test]
+    fn no_active_hours_allows_any_time() {
+        let settings = with_active_hours(None);
+
+        assert!((0..24).all(|hour| settings.is_within_active_hours(at_hour(hour))));
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: DownloadSettings =
+            serde_json::from_str(r#"{"bandwidth_limit_kbps": 512}"#).unwrap();
+
+        assert_eq!(settings.bandwidth_limit_bytes(), 512 * 1024);
+        assert_eq!(
+            settings.max_concurrent,
+            DownloadSettings::default().max_concurrent
+        );
+        assert_eq!(settings.timeout(), Duration::from_secs(300));
+        assert_eq!(DownloadSettings::default().bandwidth_limit_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn bandwidth_limiter_waits_when_bucket_is_empty() {
+        let limiter = BandwidthLimiter::new(10_000);
+
+        // 一開始有一秒的額度，用完後再要 2000 位元組需要等約 0.2 秒
+        let started = Instant::now();
+        limiter.consume(10_000).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        limiter.consume(2_000).await;
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn bandwidth_limiter_without_limit_never_waits() {
+        let limiter = BandwidthLimiter::new(1_000);
+        limiter.set_limit(0);
+
+        let started = Instant::now();
+        for _ in 0..10 {
+            limiter.consume(1_000_000).await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+}
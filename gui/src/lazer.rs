@@ -0,0 +1,178 @@
+// 標準庫導入
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// 第三方庫導入
+use log::{debug, error, info};
+use thiserror::Error;
+
+// 本地模組導入
+use crate::osu::{beatmapset_id_from_file_name, parse_osu_file};
+
+// lazer 的資料庫檔，用來確認資料夾真的是 lazer 的資料目錄
+const REALM_FILE_NAME: &str = "client.realm";
+// 讀取檔案開頭判斷是不是 .osu 檔
+const OSU_HEADER: &[u8] = b"osu file format";
+
+#[derive(Error, Debug)]
+pub enum LazerError {
+    #[error("不是 osu!lazer 的資料夾: {0}")]
+    NotLazerDirectory(PathBuf),
+    #[error("IO 錯誤: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+// lazer 預設的資料目錄：Windows 為 %APPDATA%\osu，其他平台在使用者資料目錄下
+pub fn default_data_directory() -> Option<PathBuf> {
+    let path = dirs::data_dir()?.join("osu");
+    is_lazer_data_directory(&path).then_some(path)
+}
+
+pub fn is_lazer_data_directory(path: &Path) -> bool {
+    path.join(REALM_FILE_NAME).is_file() && path.join("files").is_dir()
+}
+
+// 一次掃描的結果；file_ids 只記錄 .osu 檔的雜湊，內容不會變，下次掃描可直接沿用
+#[derive(Debug, Default, Clone)]
+pub struct LazerScan {
+    pub beatmapset_ids: HashSet<i32>,
+    pub file_ids: HashMap<String, i32>,
+}
+
+// lazer 把所有檔案以雜湊存在 files/ 下（例如 files/a/ab/abcdef...），沒有譜面集資料夾；
+// 無法直接讀 realm，改從其中的 .osu 檔取得 BeatmapSetID
+pub fn scan_file_store(
+    data_directory: &Path,
+    known: &HashMap<String, i32>,
+    debug_mode: bool,
+) -> Result<LazerScan, LazerError> {
+    if !is_lazer_data_directory(data_directory) {
+        return Err(LazerError::NotLazerDirectory(data_directory.to_path_buf()));
+    }
+
+    let mut scan = LazerScan::default();
+    let mut parsed = 0;
+    let mut pending = vec![data_directory.join("files")];
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory)?.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let Ok(hash) = entry.file_name().into_string() else {
+                continue;
+            };
+            let id = match known.get(&hash) {
+                Some(id) => *id,
+                None => {
+                    parsed += 1;
+                    // 其他檔案（音訊、背景等）只讀開頭就能排除，不必記錄
+                    match read_beatmapset_id(&path) {
+                        Some(id) => id,
+                        None => continue,
+                    }
+                }
+            };
+            scan.beatmapset_ids.insert(id);
+            scan.file_ids.insert(hash, id);
+        }
+    }
+
+    if debug_mode {
+        debug!(
+            "lazer 檔案庫共 {} 個 .osu 檔，讀取 {} 個新檔案",
+            scan.file_ids.len(),
+            parsed
+        );
+    }
+    info!("osu!lazer 中有 {} 個譜面集", scan.beatmapset_ids.len());
+    Ok(scan)
+}
+
+// 不是 .osu 檔或沒有 BeatmapSetID 時回傳 None
+fn read_beatmapset_id(path: &Path) -> Option<i32> {
+    let mut file = File::open(path).ok()?;
+    let mut header = [0u8; 32];
+    let read = file.read(&mut header).ok()?;
+    let header = header[..read]
+        .strip_prefix(b"\xef\xbb\xbf".as_slice())
+        .unwrap_or(&header[..read]);
+    if !header.trim_ascii_start().starts_with(OSU_HEADER) {
+        return None;
+    }
+    // 不是 UTF-8 的 .osu 也要能讀出 BeatmapSetID
+    let content = fs::read(path).ok()?;
+    parse_osu_file(&String::from_utf8_lossy(&content)).ok()?.id
+}
+
+// 匯出的譜面集清單：每行一個 ID、以 ID 開頭的檔名或譜面集網址，# 開頭為註解
+pub fn read_exported_list(path: &Path) -> Result<HashSet<i32>, LazerError> {
+    let content = fs::read_to_string(path)?;
+    let ids: HashSet<i32> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line.split_once("beatmapsets/") {
+            Some((_, rest)) => beatmapset_id_from_file_name(
+                rest.split(|c: char| !c.is_ascii_digit())
+                    .next()
+                    .unwrap_or_default(),
+            ),
+            None => beatmapset_id_from_file_name(line),
+        })
+        .collect();
+    info!("從 {:?} 讀取 {} 個譜面集", path, ids.len());
+    Ok(ids)
+}
+
+// 依設定讀取 lazer 資料目錄與匯出清單，兩者都有時合併；讀取失敗只記錄錯誤
+pub fn scan_library(
+    data_directory: Option<&Path>,
+    exported_list: Option<&Path>,
+    known: &HashMap<String, i32>,
+    debug_mode: bool,
+) -> LazerScan {
+    let scanned = data_directory.map(|directory| scan_file_store(directory, known, debug_mode));
+    let mut scan = match scanned {
+        Some(Ok(scan)) => scan,
+        other => {
+            if let Some(Err(e)) = other {
+                error!("無法讀取 osu!lazer 檔案庫: {}", e);
+            }
+            LazerScan {
+                beatmapset_ids: HashSet::new(),
+                file_ids: known.clone(),
+            }
+        }
+    };
+    if let Some(path) = exported_list {
+        match read_exported_list(path) {
+            Ok(ids) => scan.beatmapset_ids.extend(ids),
+            Err(e) => error!("無法讀取譜面集清單 {:?}: {}", path, e),
+        }
+    }
+    scan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn read_beatmapset_id_accepts_non_utf8_osu_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ab12cd");
+        // 檔案庫中的檔名是雜湊，Title 為 Latin-1 的 "Café"
+        fs::write(
+            &path,
+            b"\xef\xbb\xbfosu file format v7\n\n[Metadata]\nTitle:Caf\xe9\nBeatmapSetID:321\n",
+        )
+        .unwrap();
+
+        assert_eq!(read_beatmapset_id(&path), Some(321));
+    }
+}
@@ -1,8 +1,25 @@
+// 本地模組
+pub mod batch;
+pub mod download_queue;
+pub mod download_settings;
+pub mod lazer;
+pub mod library;
+pub mod matching;
+pub mod mirror;
+pub mod normalize;
+pub mod osu;
+pub mod osu_client;
+pub mod osu_db;
+pub mod search;
+pub mod spotify;
+pub mod watcher;
+
 // 標準庫導入
 use std::fs::File;
 use std::fs;
 use std::io::{self, Read};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::path::PathBuf;
 use std::collections::HashMap;
@@ -15,18 +32,22 @@ use dirs;
 use dirs::home_dir;
 use reqwest::Client;
 use lazy_static::lazy_static;
+use parking_lot::Mutex as ParkingLotMutex;
 use log::{debug, error, LevelFilter};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 
+// 本地模組導入
+use crate::spotify::AuthStatus;
+
 // 靜態變量
 lazy_static! {
     static ref LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct ServiceConfig {
     pub client_id: String,
     pub client_secret: String,
@@ -36,6 +57,13 @@ pub struct ServiceConfig {
 pub struct Config {
     pub spotify: ServiceConfig,
     pub osu: ServiceConfig,
+    // 譜面下載鏡像站，依序嘗試，例如 ["nerinyan", "catboy", "official"]
+    #[serde(default = "default_mirrors")]
+    pub mirrors: Vec<String>,
+}
+
+fn default_mirrors() -> Vec<String> {
+    mirror::DEFAULT_MIRRORS.map(String::from).to_vec()
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LoginInfo {
@@ -70,6 +98,142 @@ pub enum ConfigError {
     Other(String),
 }
 
+// 定義 AuthPlatform 列舉，用於標識不同的授權平台
+#[derive(Eq, PartialEq, Hash, Debug, Clone)]
+pub enum AuthPlatform {
+    Spotify,
+    Osu,
+}
+// 定義 DownloadStatus 列舉，用於標識不同的下載狀態
+#[derive(Clone, Debug, PartialEq)]
+pub enum DownloadStatus {
+    NotStarted,
+    Waiting,
+    // total 在伺服器沒給 Content-Length 時未知
+    Downloading {
+        received: u64,
+        total: Option<u64>,
+        bytes_per_sec: u64,
+    },
+    Completed,
+    // 下載或驗證失敗，附上原因
+    Failed(String),
+}
+
+impl DownloadStatus {
+    // 剛開始下載、還沒有進度
+    pub fn started() -> Self {
+        DownloadStatus::Downloading {
+            received: 0,
+            total: None,
+            bytes_per_sec: 0,
+        }
+    }
+
+    pub fn is_downloading(&self) -> bool {
+        matches!(self, DownloadStatus::Downloading { .. })
+    }
+
+    // 下載進度 0.0 ~ 1.0，總大小未知時為 None
+    pub fn progress(&self) -> Option<f32> {
+        match *self {
+            DownloadStatus::Downloading {
+                received,
+                total: Some(total),
+                ..
+            } if total > 0 => Some((received as f64 / total as f64).min(1.0) as f32),
+            _ => None,
+        }
+    }
+
+    // 依目前速度估計的剩餘時間
+    pub fn eta(&self) -> Option<std::time::Duration> {
+        match *self {
+            DownloadStatus::Downloading {
+                received,
+                total: Some(total),
+                bytes_per_sec,
+            } if bytes_per_sec > 0 => Some(std::time::Duration::from_secs(
+                total.saturating_sub(received) / bytes_per_sec,
+            )),
+            _ => None,
+        }
+    }
+}
+// 定義 AuthManager 結構，儲存授權狀態和錯誤記錄
+pub struct AuthManager {
+    status: ParkingLotMutex<HashMap<AuthPlatform, AuthStatus>>,
+    error_logged: AtomicBool,
+}
+
+impl AuthManager {
+    pub fn new() -> Self {
+        let mut status = HashMap::new();
+        status.insert(AuthPlatform::Spotify, AuthStatus::NotStarted);
+        status.insert(AuthPlatform::Osu, AuthStatus::NotStarted);
+        Self {
+            status: ParkingLotMutex::new(status),
+            error_logged: AtomicBool::new(false),
+        }
+    }
+
+    pub fn reset(&self, platform: &AuthPlatform) {
+        self.status
+            .lock()
+            .insert(platform.clone(), AuthStatus::NotStarted);
+        self.error_logged.store(false, Ordering::Relaxed);
+    }
+
+    pub fn update_status(&self, platform: &AuthPlatform, new_status: AuthStatus) {
+        let mut status = self.status.lock();
+        let old_status = status
+            .get(platform)
+            .cloned()
+            .unwrap_or(AuthStatus::NotStarted);
+        status.entry(platform.clone()).or_insert(new_status.clone());
+
+        if let AuthStatus::Failed(ref error) = new_status {
+            if !matches!(old_status, AuthStatus::Failed(_)) {
+                error!("{:?} 授權失敗: {}", platform, error);
+            }
+        }
+    }
+
+    // 一律覆寫目前的狀態，osu! 授權流程逐步更新進度時使用
+    pub fn set_status(&self, platform: &AuthPlatform, new_status: AuthStatus) {
+        let old_status = self
+            .status
+            .lock()
+            .insert(platform.clone(), new_status.clone())
+            .unwrap_or(AuthStatus::NotStarted);
+
+        if let AuthStatus::Failed(ref error) = new_status {
+            if !matches!(old_status, AuthStatus::Failed(_)) {
+                error!("{:?} 授權失敗: {}", platform, error);
+            }
+        }
+    }
+
+    pub fn get_status(&self, platform: &AuthPlatform) -> AuthStatus {
+        self.status
+            .lock()
+            .get(platform)
+            .cloned()
+            .unwrap_or(AuthStatus::NotStarted)
+    }
+
+    pub fn get_all_statuses(&self) -> HashMap<AuthPlatform, AuthStatus> {
+        self.status.lock().clone()
+    }
+}
+
+impl Default for AuthManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
 pub fn read_config(debug_mode: bool) -> Result<Config, ConfigError> {
     if debug_mode {
         debug!("開始讀取配置文件");
@@ -195,6 +359,20 @@ fn check_osu_config(config_value: &Value) -> Result<(), Vec<String>> {
     }
 }
 
+// 以 KB/MB 顯示檔案大小
+pub fn format_bytes(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    let bytes = bytes as f64;
+    if bytes >= MB {
+        format!("{:.1} MB", bytes / MB)
+    } else if bytes >= KB {
+        format!("{:.0} KB", bytes / KB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
 //設置日誌級別
 pub fn set_log_level(debug_mode: bool) {
     let log_level = if debug_mode {
@@ -238,6 +416,21 @@ pub fn read_login_info() -> Result<HashMap<String, LoginInfo>, ConfigError> {
     }
 }
 
+// 只更新單一平台的登入信息，保留其他平台的
+pub fn save_platform_login_info(platform: &str, login_info: LoginInfo) -> Result<(), ConfigError> {
+    let mut login_infos = read_login_info()?;
+    login_infos.insert(platform.to_string(), login_info);
+    save_login_info(&login_infos)
+}
+
+pub fn remove_platform_login_info(platform: &str) -> Result<(), ConfigError> {
+    let mut login_infos = read_login_info()?;
+    if login_infos.remove(platform).is_some() {
+        save_login_info(&login_infos)?;
+    }
+    Ok(())
+}
+
 pub fn is_token_valid(login_info: &LoginInfo) -> bool {
     Utc::now() < login_info.expiry_time
 }
@@ -251,7 +444,15 @@ pub async fn check_and_refresh_token(client: &Client, config: &Config, platform:
                 Ok(login_info.clone())
             } else {
                 // 令牌已過期,嘗試刷新
-                let new_token = refresh_spotify_token(client, &config.spotify, &login_info.refresh_token).await?;
+                let new_token = match platform {
+                    "osu" => {
+                        refresh_osu_token(client, &config.osu, &login_info.refresh_token).await?
+                    }
+                    _ => {
+                        refresh_spotify_token(client, &config.spotify, &login_info.refresh_token)
+                            .await?
+                    }
+                };
                 
                 let new_login_info = LoginInfo {
                     platform: platform.to_string(),
@@ -305,6 +506,41 @@ async fn refresh_spotify_token(
     }
 }
 
+// osu! 的刷新令牌用過即失效，回應中會附上新的
+async fn refresh_osu_token(
+    client: &Client,
+    config: &ServiceConfig,
+    refresh_token: &str,
+) -> Result<RefreshTokenResponse, ConfigError> {
+    let params = [
+        ("client_id", config.client_id.as_str()),
+        ("client_secret", config.client_secret.as_str()),
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("scope", osu::OSU_USER_SCOPE),
+    ];
+
+    let response = client
+        .post(osu::OSU_TOKEN_URL)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| ConfigError::Other(format!("刷新令牌請求失敗: {}", e)))?;
+
+    if response.status().is_success() {
+        response
+            .json()
+            .await
+            .map_err(|e| ConfigError::Other(format!("解析刷新令牌響應失敗: {}", e)))
+    } else {
+        let error_text = response
+            .text()
+            .await
+            .map_err(|e| ConfigError::Other(format!("讀取錯誤響應失敗: {}", e)))?;
+        Err(ConfigError::Other(format!("刷新令牌失敗: {}", error_text)))
+    }
+}
+
 pub fn load_download_directory() -> Option<PathBuf> {
     // 首先嘗試讀取保存的下載目錄
     let saved_path = get_app_data_path().join("download_directory.txt");
@@ -418,4 +654,4 @@ pub fn open_url_default_browser(url: &str) -> io::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,647 @@
+// 標準庫導入
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// 第三方庫導入
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// 本地模組導入
+use crate::get_app_data_path;
+use crate::lazer::LazerScan;
+use crate::osu::{
+    beatmapset_id_of_entry, read_local_beatmapset, Beatmapset, LocalBeatmapset, OsuMode,
+};
+use crate::watcher::DirectoryEvent;
+
+// 索引檔存放在應用數據目錄下
+const LIBRARY_FILE_NAME: &str = "local_library.json";
+
+#[derive(Error, Debug)]
+pub enum LibraryError {
+    #[error("IO 錯誤: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON 解析錯誤: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+// 索引中的一筆資料，記錄修改時間以判斷是否需要重新解析
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LibraryEntry {
+    beatmapset: LocalBeatmapset,
+    modified: SystemTime,
+}
+
+// 寫入磁碟的索引內容
+#[derive(Serialize, Deserialize)]
+struct LibraryFile {
+    download_directory: PathBuf,
+    entries: HashMap<String, LibraryEntry>,
+    // osu!lazer 中 .osu 檔的雜湊對應的譜面集 ID，重新掃描時不必再解析
+    #[serde(default)]
+    lazer_osu_files: HashMap<String, i32>,
+}
+
+// 一次掃描後索引的變化
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LibraryChanges {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl LibraryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+// 本機譜面查詢條件，所有條件皆需符合
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LibraryQuery {
+    // 比對藝人、曲名（含原文）、作者與檔名
+    pub text: String,
+    pub id: Option<i32>,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub creator: Option<String>,
+    pub mode: Option<OsuMode>,
+    // 星級只有從 API 得知的難度才有，沒有星級的難度不符合星級條件
+    pub min_stars: Option<StarBound>,
+    pub max_stars: Option<StarBound>,
+}
+
+// 星級範圍的一端，"stars>5" 不含 5、"stars>=5" 含 5
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StarBound {
+    pub stars: f32,
+    pub inclusive: bool,
+}
+
+impl StarBound {
+    fn parse(value: &str, inclusive: bool) -> Option<Self> {
+        value.parse().ok().map(|stars| Self { stars, inclusive })
+    }
+
+    fn allows_at_least(&self, stars: f32) -> bool {
+        if self.inclusive {
+            stars >= self.stars
+        } else {
+            stars > self.stars
+        }
+    }
+
+    fn allows_at_most(&self, stars: f32) -> bool {
+        if self.inclusive {
+            stars <= self.stars
+        } else {
+            stars < self.stars
+        }
+    }
+}
+
+impl LibraryQuery {
+    // 解析類似 osu! 客戶端的搜尋語法，例如 "artist=camellia stars>5 mode=mania 關鍵字"
+    pub fn parse(input: &str) -> Self {
+        let mut query = LibraryQuery::default();
+        let mut text = Vec::new();
+
+        for token in input.split_whitespace() {
+            let lower = token.to_lowercase();
+            if let Some(value) = lower.strip_prefix("id=") {
+                query.id = value.parse().ok();
+            } else if let Some(value) = lower.strip_prefix("artist=") {
+                query.artist = Some(value.to_string());
+            } else if let Some(value) = lower.strip_prefix("title=") {
+                query.title = Some(value.to_string());
+            } else if let Some(value) = lower.strip_prefix("creator=") {
+                query.creator = Some(value.to_string());
+            } else if let Some(value) = lower.strip_prefix("mode=") {
+                query.mode = match value {
+                    "osu" | "std" | "0" => Some(OsuMode::Osu),
+                    "taiko" | "1" => Some(OsuMode::Taiko),
+                    "catch" | "fruits" | "ctb" | "2" => Some(OsuMode::Catch),
+                    "mania" | "3" => Some(OsuMode::Mania),
+                    _ => None,
+                };
+            } else if let Some(value) = lower.strip_prefix("stars>=") {
+                query.min_stars = StarBound::parse(value, true);
+            } else if let Some(value) = lower.strip_prefix("stars>") {
+                query.min_stars = StarBound::parse(value, false);
+            } else if let Some(value) = lower.strip_prefix("stars<=") {
+                query.max_stars = StarBound::parse(value, true);
+            } else if let Some(value) = lower.strip_prefix("stars<") {
+                query.max_stars = StarBound::parse(value, false);
+            } else {
+                text.push(lower);
+            }
+        }
+
+        query.text = text.join(" ");
+        query
+    }
+
+    fn matches(&self, file_name: &str, beatmapset: &LocalBeatmapset) -> bool {
+        if self.id.is_some() && beatmapset.id != self.id {
+            return false;
+        }
+        if let Some(artist) = &self.artist {
+            if !contains_lowercase(&beatmapset.artist, artist)
+                && !contains_lowercase(&beatmapset.artist_unicode, artist)
+            {
+                return false;
+            }
+        }
+        if let Some(title) = &self.title {
+            if !contains_lowercase(&beatmapset.title, title)
+                && !contains_lowercase(&beatmapset.title_unicode, title)
+            {
+                return false;
+            }
+        }
+        if let Some(creator) = &self.creator {
+            if !contains_lowercase(&beatmapset.creator, creator) {
+                return false;
+            }
+        }
+
+        // 模式與星級要由同一個難度同時符合
+        if self.mode.is_some() || self.min_stars.is_some() || self.max_stars.is_some() {
+            let any_beatmap = beatmapset.beatmaps.iter().any(|beatmap| {
+                let mode_ok = self
+                    .mode
+                    .is_none_or(|mode| OsuMode::from_index(beatmap.mode) == Some(mode));
+                let stars_ok = match (self.min_stars, self.max_stars) {
+                    (None, None) => true,
+                    (min, max) => beatmap.difficulty_rating.is_some_and(|stars| {
+                        min.is_none_or(|min| min.allows_at_least(stars))
+                            && max.is_none_or(|max| max.allows_at_most(stars))
+                    }),
+                };
+                mode_ok && stars_ok
+            });
+            if !any_beatmap {
+                return false;
+            }
+        }
+
+        self.text.split_whitespace().all(|word| {
+            [
+                file_name,
+                &beatmapset.artist,
+                &beatmapset.artist_unicode,
+                &beatmapset.title,
+                &beatmapset.title_unicode,
+                &beatmapset.creator,
+            ]
+            .iter()
+            .any(|field| contains_lowercase(field, word))
+        })
+    }
+}
+
+// 下載資料夾的持久化索引，只在檔案新增、修改或刪除時重新解析
+pub struct LocalLibrary {
+    download_directory: PathBuf,
+    entries: HashMap<String, LibraryEntry>,
+    // 不在下載資料夾、但 osu!lazer 已經有的譜面集
+    lazer_ids: HashSet<i32>,
+    lazer_files: HashMap<String, i32>,
+    dirty: bool,
+    debug_mode: bool,
+}
+
+impl LocalLibrary {
+    // 讀取磁碟上的索引，下載資料夾換了的話從空索引開始
+    pub fn load(download_directory: &Path, debug_mode: bool) -> Self {
+        let mut library = Self {
+            download_directory: download_directory.to_path_buf(),
+            entries: HashMap::new(),
+            lazer_ids: HashSet::new(),
+            lazer_files: HashMap::new(),
+            dirty: false,
+            debug_mode,
+        };
+
+        let path = Self::library_path();
+        match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<LibraryFile>(&content) {
+                Ok(file) if file.download_directory == download_directory => {
+                    info!("已載入本機譜面索引，共 {} 筆", file.entries.len());
+                    library.entries = file.entries;
+                    library.lazer_files = file.lazer_osu_files;
+                }
+                Ok(_) => info!("下載資料夾已變更，重新建立本機譜面索引"),
+                Err(e) => error!("無法解析本機譜面索引: {:?}", e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("尚未建立本機譜面索引");
+            }
+            Err(e) => error!("無法讀取本機譜面索引: {:?}", e),
+        }
+
+        library
+    }
+
+    fn library_path() -> PathBuf {
+        get_app_data_path().join(LIBRARY_FILE_NAME)
+    }
+
+    pub fn download_directory(&self) -> &Path {
+        &self.download_directory
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // 掃描下載資料夾，只解析新增或修改時間有變的項目
+    pub fn refresh(&mut self) -> LibraryChanges {
+        let mut changes = LibraryChanges::default();
+        let mut seen = HashSet::new();
+
+        let entries = match fs::read_dir(&self.download_directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("無法讀取下載資料夾 {:?}: {:?}", self.download_directory, e);
+                return changes;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            // 已索引的項目之前判斷過，不必再讀取 .osu 檔
+            if !self.entries.contains_key(&file_name) && !is_beatmap_entry(&entry.path()) {
+                continue;
+            }
+            seen.insert(file_name.clone());
+
+            let modified = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            match self.entries.get(&file_name) {
+                Some(existing) if existing.modified == modified => {}
+                Some(_) => {
+                    if self.index_file(&file_name, modified) {
+                        changes.updated.push(file_name);
+                    }
+                }
+                None => {
+                    if self.index_file(&file_name, modified) {
+                        changes.added.push(file_name);
+                    }
+                }
+            }
+        }
+
+        self.entries.retain(|file_name, _| {
+            let keep = seen.contains(file_name);
+            if !keep {
+                changes.removed.push(file_name.clone());
+            }
+            keep
+        });
+
+        if !changes.is_empty() {
+            self.dirty = true;
+            info!(
+                "本機譜面索引更新：新增 {}、更新 {}、移除 {}",
+                changes.added.len(),
+                changes.updated.len(),
+                changes.removed.len()
+            );
+        }
+        changes
+    }
+
+    // 單一檔案新增或修改時更新索引，不必重新掃描整個資料夾
+    pub fn update_file(&mut self, file_name: &str) -> bool {
+        let path = self.download_directory.join(file_name);
+        if !is_beatmap_entry(&path) {
+            return false;
+        }
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let updated = self.index_file(file_name, modified);
+        self.dirty |= updated;
+        updated
+    }
+
+    // 單一檔案被刪除時從索引移除
+    pub fn remove_file(&mut self, file_name: &str) -> bool {
+        let removed = self.entries.remove(file_name).is_some();
+        self.dirty |= removed;
+        removed
+    }
+
+    fn index_file(&mut self, file_name: &str, modified: SystemTime) -> bool {
+        match read_local_beatmapset(&self.download_directory.join(file_name)) {
+            Ok(mut beatmapset) => {
+                // 保留先前從 API 得知的星級
+                if let Some(existing) = self.entries.get(file_name) {
+                    copy_difficulty_ratings(&existing.beatmapset, &mut beatmapset);
+                }
+                if self.debug_mode {
+                    debug!("索引譜面 {}: {:?}", file_name, beatmapset);
+                }
+                self.entries.insert(
+                    file_name.to_string(),
+                    LibraryEntry {
+                        beatmapset,
+                        modified,
+                    },
+                );
+                true
+            }
+            Err(e) => {
+                // 下載中的檔案可能還不完整，下次掃描時修改時間不同會再試一次
+                error!("無法解析譜面 {}: {:?}", file_name, e);
+                false
+            }
+        }
+    }
+
+    // 寫回磁碟，沒有變更時略過
+    pub fn save(&mut self) -> Result<(), LibraryError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        fs::create_dir_all(get_app_data_path())?;
+        self.save_to(&Self::library_path())
+    }
+
+    // 先寫到暫存檔再取代，寫到一半當機時原本的索引仍然完整
+    fn save_to(&mut self, path: &Path) -> Result<(), LibraryError> {
+        let file = LibraryFile {
+            download_directory: self.download_directory.clone(),
+            entries: self.entries.clone(),
+            lazer_osu_files: self.lazer_files.clone(),
+        };
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string(&file)?)?;
+        fs::rename(&temp_path, path)?;
+        self.dirty = false;
+        if self.debug_mode {
+            debug!("已儲存本機譜面索引，共 {} 筆", self.entries.len());
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, beatmapset_id: i32) -> Option<&LocalBeatmapset> {
+        self.entries
+            .values()
+            .map(|entry| &entry.beatmapset)
+            .find(|beatmapset| beatmapset.id == Some(beatmapset_id))
+    }
+
+    pub fn get_by_file_name(&self, file_name: &str) -> Option<&LocalBeatmapset> {
+        self.entries.get(file_name).map(|entry| &entry.beatmapset)
+    }
+
+    // 下載資料夾或 osu!lazer 中已有這個譜面集
+    pub fn contains(&self, beatmapset_id: i32) -> bool {
+        self.get(beatmapset_id).is_some() || self.lazer_ids.contains(&beatmapset_id)
+    }
+
+    // 只存在於 osu!lazer，下載資料夾中沒有檔案可刪除
+    pub fn is_lazer_only(&self, beatmapset_id: i32) -> bool {
+        self.get(beatmapset_id).is_none() && self.lazer_ids.contains(&beatmapset_id)
+    }
+
+    pub fn lazer_len(&self) -> usize {
+        self.lazer_ids.len()
+    }
+
+    pub fn lazer_files(&self) -> &HashMap<String, i32> {
+        &self.lazer_files
+    }
+
+    // 目前的 lazer 掃描結果，換下載資料夾重新載入索引時沿用，不必重新掃描檔案庫
+    pub fn lazer_scan(&self) -> LazerScan {
+        LazerScan {
+            beatmapset_ids: self.lazer_ids.clone(),
+            file_ids: self.lazer_files.clone(),
+        }
+    }
+
+    // 套用 lazer 的掃描結果；None 表示沒有使用 lazer，保留檔案快取以便之後切回
+    pub fn set_lazer_scan(&mut self, scan: Option<LazerScan>) {
+        match scan {
+            Some(scan) => {
+                self.dirty |= scan.file_ids != self.lazer_files;
+                self.lazer_ids = scan.beatmapset_ids;
+                self.lazer_files = scan.file_ids;
+            }
+            None => self.lazer_ids.clear(),
+        }
+    }
+
+    // 交給 lazer 匯入後 .osz 會被移走，在下次掃描前先當作 lazer 已經有了
+    pub fn record_lazer_import(&mut self, beatmapset_id: i32) {
+        self.lazer_ids.insert(beatmapset_id);
+    }
+
+    // 索引中所有已知 ID 的譜面集，包含 osu!lazer 中的
+    pub fn beatmapset_ids(&self) -> HashSet<i32> {
+        self.entries
+            .values()
+            .filter_map(|entry| entry.beatmapset.id)
+            .chain(self.lazer_ids.iter().copied())
+            .collect()
+    }
+
+    // 套用下載資料夾監看到的變化，回傳受影響的譜面集 ID
+    pub fn apply_events(&mut self, events: &[DirectoryEvent]) -> HashSet<i32> {
+        let mut affected = HashSet::new();
+        for event in events {
+            affected.extend(event.beatmapset_ids());
+            let (removed, added) = match event {
+                DirectoryEvent::Added(name) => (None, Some(name)),
+                DirectoryEvent::Removed(name) => (Some(name), None),
+                DirectoryEvent::Renamed { from, to } => (Some(from), Some(to)),
+            };
+            if let Some(name) = removed {
+                affected.extend(self.get_by_file_name(name).and_then(|b| b.id));
+                self.remove_file(name);
+            }
+            if let Some(name) = added {
+                self.update_file(name);
+                affected.extend(self.get_by_file_name(name).and_then(|b| b.id));
+            }
+        }
+        affected
+    }
+
+    // 依條件查詢，結果依修改時間由新到舊排序
+    pub fn query(&self, query: &LibraryQuery) -> Vec<&LocalBeatmapset> {
+        let mut results: Vec<&LibraryEntry> = self
+            .entries
+            .iter()
+            .filter(|(file_name, entry)| query.matches(file_name, &entry.beatmapset))
+            .map(|(_, entry)| entry)
+            .collect();
+        results.sort_by_key(|entry| Reverse(entry.modified));
+        results.into_iter().map(|entry| &entry.beatmapset).collect()
+    }
+
+    // .osu 檔沒有星級，從 API 取得譜面集時把各難度的星級記錄到索引
+    pub fn record_difficulty_ratings(&mut self, beatmapset: &Beatmapset) {
+        for entry in self.entries.values_mut() {
+            if entry.beatmapset.id != Some(beatmapset.id) {
+                continue;
+            }
+            for local in entry.beatmapset.beatmaps.iter_mut() {
+                let rating = beatmapset
+                    .beatmaps
+                    .iter()
+                    .find(|b| {
+                        Some(b.id) == local.beatmap_id
+                            || (local.beatmap_id.is_none() && b.version == local.version)
+                    })
+                    .map(|b| b.difficulty_rating);
+                if rating.is_some() && local.difficulty_rating != rating {
+                    local.difficulty_rating = rating;
+                    self.dirty = true;
+                }
+            }
+        }
+    }
+}
+
+// 與刪除譜面相同的判斷，索引中的譜面集才都能被刪除：
+// 檔名開頭是譜面集 ID，或 .osu 檔中有 BeatmapSetID 的 .osz 檔與資料夾
+fn is_beatmap_entry(path: &Path) -> bool {
+    beatmapset_id_of_entry(path).is_some()
+}
+
+fn copy_difficulty_ratings(from: &LocalBeatmapset, to: &mut LocalBeatmapset) {
+    for beatmap in to.beatmaps.iter_mut() {
+        beatmap.difficulty_rating = from
+            .beatmaps
+            .iter()
+            .find(|b| b.beatmap_id == beatmap.beatmap_id && b.version == beatmap.version)
+            .and_then(|b| b.difficulty_rating);
+    }
+}
+
+fn contains_lowercase(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osu::find_beatmap_entries;
+    use tempfile::tempdir;
+
+    const OSU_WITH_SET_ID: &str = "osu file format v14\n\n[Metadata]\nTitle:Song\nArtist:Someone\nVersion:Normal\nBeatmapSetID:555\n";
+
+    // 不經過 load，避免讀寫應用數據目錄中的索引
+    fn empty_library(download_directory: &Path) -> LocalLibrary {
+        LocalLibrary {
+            download_directory: download_directory.to_path_buf(),
+            entries: HashMap::new(),
+            lazer_ids: HashSet::new(),
+            lazer_files: HashMap::new(),
+            dirty: false,
+            debug_mode: false,
+        }
+    }
+
+    #[test]
+    fn refresh_indexes_folders_that_can_be_deleted() {
+        let dir = tempdir().unwrap();
+        let folder = dir.path().join("Someone - Song");
+        fs::create_dir(&folder).unwrap();
+        fs::write(
+            folder.join("Someone - Song (Mapper) [Normal].osu"),
+            OSU_WITH_SET_ID,
+        )
+        .unwrap();
+        // 沒有 ID 也沒有 .osu 檔的資料夾兩邊都不算
+        fs::create_dir(dir.path().join("Screenshots")).unwrap();
+
+        let mut library = empty_library(dir.path());
+        let changes = library.refresh();
+
+        assert_eq!(changes.added, ["Someone - Song"]);
+        assert!(library.contains(555));
+        assert_eq!(find_beatmap_entries(dir.path(), 555).unwrap(), [folder]);
+        assert!(!library.update_file("Screenshots"));
+    }
+
+    fn beatmapset_with_stars(stars: f32) -> LocalBeatmapset {
+        let mut beatmapset = LocalBeatmapset::default();
+        beatmapset.beatmaps.push(crate::osu::LocalBeatmap {
+            difficulty_rating: Some(stars),
+            ..Default::default()
+        });
+        beatmapset
+    }
+
+    #[test]
+    fn parse_reads_filters_and_keywords() {
+        let query =
+            LibraryQuery::parse("Artist=Camellia mode=mania stars>=5 stars<7.5 Ghost id=42");
+
+        assert_eq!(query.artist.as_deref(), Some("camellia"));
+        assert_eq!(query.mode, Some(OsuMode::Mania));
+        assert_eq!(query.id, Some(42));
+        assert_eq!(
+            query.min_stars,
+            Some(StarBound {
+                stars: 5.0,
+                inclusive: true
+            })
+        );
+        assert_eq!(
+            query.max_stars,
+            Some(StarBound {
+                stars: 7.5,
+                inclusive: false
+            })
+        );
+        assert_eq!(query.text, "ghost");
+    }
+
+    #[test]
+    fn strict_star_bounds_exclude_the_boundary() {
+        let five_stars = beatmapset_with_stars(5.0);
+
+        assert!(!LibraryQuery::parse("stars>5").matches("a.osz", &five_stars));
+        assert!(LibraryQuery::parse("stars>=5").matches("a.osz", &five_stars));
+        assert!(!LibraryQuery::parse("stars<5").matches("a.osz", &five_stars));
+        assert!(LibraryQuery::parse("stars<=5").matches("a.osz", &five_stars));
+        assert!(LibraryQuery::parse("stars>4.9 stars<5.1").matches("a.osz", &five_stars));
+        // 沒有星級的難度不符合星級條件
+        assert!(!LibraryQuery::parse("stars>=0").matches("a.osz", &LocalBeatmapset::default()));
+    }
+
+    #[test]
+    fn save_replaces_index_without_leaving_temp_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(LIBRARY_FILE_NAME);
+        fs::write(&path, "old index").unwrap();
+        let mut library = empty_library(dir.path());
+        library.dirty = true;
+
+        library.save_to(&path).unwrap();
+
+        let file: LibraryFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(file.download_directory, dir.path());
+        assert!(!path.with_extension("json.tmp").exists());
+        assert!(!library.dirty);
+    }
+}
@@ -1,7 +1,5 @@
 // 本地模組
-mod osu;
 mod osuhelper;
-mod spotify;
 
 // 標準庫導入
 use std::cmp::Reverse;
@@ -18,7 +16,7 @@ use std::time::SystemTime;
 use std::time::{Duration, Instant};
 
 // 第三方庫導入
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
 use backoff::backoff::Backoff;
 use backoff::exponential::ExponentialBackoff;
 use backoff::SystemClock;
@@ -30,7 +28,6 @@ use egui::{
 };
 
 use log::{debug, error, info, LevelFilter};
-use parking_lot::Mutex as ParkingLotMutex;
 use reqwest::Client;
 use rodio::{OutputStream, OutputStreamHandle, Sink};
 use rspotify::{
@@ -46,29 +43,45 @@ use tokio::{
     self,
     net::TcpListener,
     sync::{
-        mpsc,
         mpsc::{Receiver, Sender},
-        Mutex as TokioMutex, RwLock, Semaphore,
+        Mutex as TokioMutex, Notify, RwLock,
     },
-    task::JoinHandle,
+    task::{AbortHandle, JoinHandle},
 };
 
 // 本地模組導入
-use crate::osu::{
-    delete_beatmap, get_beatmapset_by_id, get_beatmapset_details, get_beatmapsets,
-    get_downloaded_beatmaps, get_osu_token, load_osu_covers, parse_osu_url, preview_beatmap,
-    print_beatmap_info_gui, Beatmapset,
+use lib::batch::{
+    approved_track_ids, confident_beatmapset_ids, queueable_beatmapset_ids, BatchMatchEntry,
+    BatchMatchJob, LocalLibraryMatchJob, LocalSongMatch, LOCAL_LIBRARY_PLAYLIST_NAME,
+    MIN_AUTO_ADD_CONFIDENCE, MIN_QUEUE_CONFIDENCE,
 };
-use crate::spotify::{
-    add_track_to_liked, authorize_spotify, get_access_token, get_playlist_tracks, get_track_info,
-    get_user_playlists, is_valid_spotify_url, load_spotify_icon, open_spotify_url,
-    remove_track_from_liked, search_track, update_currently_playing_wrapper, Album, AuthStatus,
-    CurrentlyPlaying, Image, SpotifyError, SpotifyUrlStatus, Track, TrackWithCover,
+use lib::download_queue::{DownloadQueue, QueueItemState};
+use lib::download_settings::{BandwidthLimiter, DownloadSettings, MAX_CONCURRENT_DOWNLOADS};
+use lib::lazer;
+use lib::library::{LibraryQuery, LocalLibrary};
+use lib::matching::BestMatch;
+use lib::mirror::{DownloadVariant, MirrorPool};
+use lib::osu::{
+    delete_beatmap, get_downloaded_beatmaps, load_osu_covers, preview_beatmap,
+    print_beatmap_info_gui, BeatmapSearchFilter, Beatmapset, Genre, Language, LocalBeatmapset,
+    OsuError, OsuMode, OsuUserListEntry, OsuUserListKind, RankedStatus,
 };
+use lib::osu_client::{self, OsuClientKind, OsuClientSettings};
+use lib::osu_db;
+use lib::search::{CrossSearchEngine, QueryKind, SPOTIFY_MAX_OFFSET};
+use lib::spotify::{
+    add_track_to_liked, add_tracks_to_named_playlist, authorize_spotify, get_access_token,
+    get_playlist_tracks, get_user_playlists, load_spotify_icon, open_spotify_url,
+    remove_track_from_liked, track_id_from_track, update_currently_playing_wrapper, AuthStatus,
+    CurrentlyPlaying, SpotifySearchType, Track, SPOTIFY_ACCOUNTS_BASE_URL,
+};
+use lib::watcher::{DirectoryEvent, DownloadDirWatcher};
+use lib::{osu, spotify, AuthManager, AuthPlatform, DownloadStatus};
 use lib::{
-    check_and_refresh_token, get_app_data_path, load_background_path, load_download_directory,
-    load_scale_factor, need_select_download_directory, read_config, read_login_info,
-    save_background_path, save_download_directory, save_scale_factor, set_log_level, ConfigError,
+    check_and_refresh_token, format_bytes, get_app_data_path, load_background_path,
+    load_download_directory, load_scale_factor, need_select_download_directory, read_config,
+    read_login_info, remove_platform_login_info, save_background_path, save_download_directory,
+    save_scale_factor, set_log_level, ConfigError,
 };
 
 use osuhelper::OsuHelper;
@@ -80,6 +93,13 @@ const BUTTON_SIZE: f32 = 40.0;
 const ANIMATION_SPEED: f32 = 4.0;
 const SEARCH_BAR_WIDTH_RATIO: f32 = 0.6;
 
+// 搜尋結果封面：索引對應到材質與原始尺寸，載入中為 None
+type CoverTextures = HashMap<usize, Option<(Arc<TextureHandle>, (f32, f32))>>;
+// 背景載入完成的封面，經由通道送回 UI 執行緒
+type CoverTextureUpdate = (usize, Arc<TextureHandle>, (f32, f32));
+// 等待載入的封面網址，依索引由小到大處理
+type TextureLoadQueue = BinaryHeap<Reverse<(usize, String)>>;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("配置錯誤: {0}")]
@@ -96,20 +116,6 @@ pub enum AppError {
     Other(String),
 }
 
-// 定義 AuthPlatform 列舉，用於標識不同的授權平台
-#[derive(Eq, PartialEq, Hash, Debug, Clone)]
-pub enum AuthPlatform {
-    Spotify,
-    Osu,
-}
-// 定義 DownloadStatus 列舉，用於標識不同的下載狀態
-#[derive(Clone, Copy, PartialEq)]
-pub enum DownloadStatus {
-    NotStarted,
-    Waiting,
-    Downloading,
-    Completed,
-}
 // 定義 PlaylistCache 結構，用於緩存播放列表曲目
 #[derive(Serialize, Deserialize)]
 struct PlaylistCache {
@@ -117,55 +123,15 @@ struct PlaylistCache {
     last_updated: SystemTime,
 }
 
-// 定義 AuthManager 結構，儲存授權狀態和錯誤記錄
-pub struct AuthManager {
-    status: ParkingLotMutex<HashMap<AuthPlatform, AuthStatus>>,
-    error_logged: AtomicBool,
-}
-
-impl AuthManager {
-    pub fn new() -> Self {
-        let mut status = HashMap::new();
-        status.insert(AuthPlatform::Spotify, AuthStatus::NotStarted);
-        Self {
-            status: ParkingLotMutex::new(status),
-            error_logged: AtomicBool::new(false),
-        }
-    }
-
-    pub fn reset(&self, platform: &AuthPlatform) {
-        self.status
-            .lock()
-            .insert(platform.clone(), AuthStatus::NotStarted);
-        self.error_logged.store(false, Ordering::Relaxed);
-    }
-
-    pub fn update_status(&self, platform: &AuthPlatform, new_status: AuthStatus) {
-        let mut status = self.status.lock();
-        let old_status = status
-            .get(platform)
-            .cloned()
-            .unwrap_or(AuthStatus::NotStarted);
-        status.entry(platform.clone()).or_insert(new_status.clone());
-
-        if let AuthStatus::Failed(ref error) = new_status {
-            if !matches!(old_status, AuthStatus::Failed(_)) {
-                error!("{:?} 授權失敗: {}", platform, error);
-            }
-        }
-    }
-
-    pub fn get_status(&self, platform: &AuthPlatform) -> AuthStatus {
-        self.status
-            .lock()
-            .get(platform)
-            .cloned()
-            .unwrap_or(AuthStatus::NotStarted)
-    }
-
-    pub fn get_all_statuses(&self) -> HashMap<AuthPlatform, AuthStatus> {
-        self.status.lock().clone()
-    }
+// 下載佇列視窗中對單一項目的操作
+#[derive(Clone, Copy, PartialEq)]
+enum QueueAction {
+    Pause,
+    Resume,
+    Retry,
+    Cancel,
+    MoveUp,
+    MoveDown,
 }
 
 // 定義 SpotifySearchApp結構，儲存程式狀態和數據
@@ -182,12 +148,27 @@ struct SearchApp {
     spotify_user_avatar: Arc<Mutex<Option<egui::TextureHandle>>>,
     spotify_user_avatar_url: Arc<Mutex<Option<String>>>,
     spotify_user_name: Arc<Mutex<Option<String>>>,
+    osu_authorized: Arc<AtomicBool>,
+    osu_user_avatar: Arc<Mutex<Option<egui::TextureHandle>>>,
+    osu_user_name: Arc<Mutex<Option<String>>>,
 
     // 搜索相關
     search_query: String,
     is_searching: Arc<AtomicBool>,
     search_results: Arc<tokio::sync::Mutex<Vec<Track>>>,
     osu_search_results: Arc<tokio::sync::Mutex<Vec<Beatmapset>>>,
+    // 搜尋結果中被標記為最佳匹配的譜面（展開專輯時每首曲目各一個）
+    osu_best_matches: Arc<tokio::sync::Mutex<Vec<BestMatch>>>,
+    spotify_search_type: SpotifySearchType,
+    // Spotify 下一頁的查詢字串與 offset
+    spotify_next_page: Arc<tokio::sync::Mutex<Option<(String, u32)>>>,
+    spotify_total_results: Arc<AtomicUsize>,
+    is_loading_spotify_page: Arc<AtomicBool>,
+    // osu! 下一頁的查詢字串與游標
+    osu_next_page: Arc<tokio::sync::Mutex<Option<(String, String)>>>,
+    osu_total_results: Arc<AtomicUsize>,
+    is_loading_osu_page: Arc<AtomicBool>,
+    osu_search_filter: BeatmapSearchFilter,
     displayed_spotify_results: usize,
     displayed_osu_results: usize,
     downloaded_maps_search: String,
@@ -200,6 +181,9 @@ struct SearchApp {
     spotify_liked_tracks: Arc<Mutex<Vec<FullTrack>>>,
     selected_playlist: Option<SimplifiedPlaylist>,
     currently_playing: Arc<Mutex<Option<CurrentlyPlaying>>>,
+    osu_user_lists: Arc<Mutex<HashMap<OsuUserListKind, Vec<OsuUserListEntry>>>>,
+    selected_osu_list: Option<OsuUserListKind>,
+    loading_osu_lists: Arc<Mutex<HashSet<OsuUserListKind>>>,
 
     // UI 狀態
     show_auth_progress: bool,
@@ -224,7 +208,7 @@ struct SearchApp {
 
     // 紋理和圖像
     avatar_load_handle: Option<tokio::task::JoinHandle<()>>,
-    cover_textures: Arc<RwLock<HashMap<usize, Option<(Arc<TextureHandle>, (f32, f32))>>>>,
+    cover_textures: Arc<RwLock<CoverTextures>>,
     playlist_cover_textures: Arc<Mutex<HashMap<String, Option<TextureHandle>>>>,
     default_avatar_texture: Option<egui::TextureHandle>,
     spotify_icon: Option<egui::TextureHandle>,
@@ -249,8 +233,8 @@ struct SearchApp {
     beatmapset_download_statuses: Arc<Mutex<HashMap<i32, DownloadStatus>>>,
 
     // 異步通信
-    receiver: Option<tokio::sync::mpsc::Receiver<(usize, Arc<TextureHandle>, (f32, f32))>>,
-    sender: Sender<(usize, Arc<TextureHandle>, (f32, f32))>,
+    receiver: Option<tokio::sync::mpsc::Receiver<CoverTextureUpdate>>,
+    sender: Sender<CoverTextureUpdate>,
 
     // UI 元素狀態
     side_menu_animation: HashMap<egui::Id, f32>,
@@ -270,7 +254,7 @@ struct SearchApp {
     // 快取
     liked_songs_cache: Arc<Mutex<Option<PlaylistCache>>>,
     cache_ttl: Duration,
-    texture_load_queue: Arc<Mutex<BinaryHeap<Reverse<(usize, String)>>>>,
+    texture_load_queue: Arc<Mutex<TextureLoadQueue>>,
 
     // 更新檢查
     update_check_result: Arc<Mutex<Option<bool>>>,
@@ -282,10 +266,43 @@ struct SearchApp {
     download_directory: PathBuf,
     status_sender: tokio::sync::mpsc::Sender<(i32, DownloadStatus)>,
     status_receiver: tokio::sync::mpsc::Receiver<(i32, DownloadStatus)>,
-    download_queue_sender: mpsc::Sender<i32>,
-    download_queue_receiver: Arc<Mutex<Option<mpsc::Receiver<i32>>>>,
-    download_semaphore: Arc<Semaphore>,
-    current_downloads: Arc<AtomicUsize>,
+    download_queue: Arc<Mutex<DownloadQueue>>,
+    download_queue_notify: Arc<Notify>,
+    active_downloads: Arc<Mutex<HashMap<i32, AbortHandle>>>,
+    download_settings: Arc<Mutex<DownloadSettings>>,
+    bandwidth_limiter: Arc<BandwidthLimiter>,
+    show_download_queue: bool,
+    local_library: Arc<Mutex<LocalLibrary>>,
+    is_refreshing_library: Arc<AtomicBool>,
+    download_watcher: Option<DownloadDirWatcher>,
+    pending_beatmap_delete: Option<(i32, Vec<PathBuf>)>,
+    beatmap_mirrors: Arc<MirrorPool>,
+
+    // osu! 客戶端
+    osu_client_settings: Arc<Mutex<OsuClientSettings>>,
+    running_osu_client: Arc<Mutex<Option<OsuClientKind>>>,
+    // osu!stable 的收藏：譜面集 ID 對應收藏名稱
+    osu_collections: Arc<Mutex<HashMap<i32, Vec<String>>>>,
+    new_collection_name: String,
+    is_writing_collection: Arc<AtomicBool>,
+    collection_message: Arc<Mutex<Option<String>>>,
+
+    // 批次比對播放清單
+    batch_match_entries: Arc<Mutex<Vec<BatchMatchEntry>>>,
+    batch_match_total: Arc<AtomicUsize>,
+    is_batch_matching: Arc<AtomicBool>,
+    batch_match_cancel: Arc<AtomicBool>,
+    batch_match_source: String,
+    show_batch_match_report: bool,
+
+    // 本機譜面反向比對 Spotify
+    local_song_matches: Arc<Mutex<Vec<LocalSongMatch>>>,
+    local_match_total: Arc<AtomicUsize>,
+    is_local_matching: Arc<AtomicBool>,
+    local_match_cancel: Arc<AtomicBool>,
+    is_syncing_playlist: Arc<AtomicBool>,
+    playlist_sync_message: Arc<Mutex<Option<String>>>,
+    show_local_match_report: bool,
 
     // 預覽播放
     audio_output: Option<(OutputStream, OutputStreamHandle)>,
@@ -388,10 +405,8 @@ impl SearchApp {
     }
 
     async fn process_texture_updates(
-        mut receiver: tokio::sync::mpsc::Receiver<(usize, Arc<TextureHandle>, (f32, f32))>,
-        cover_textures: std::sync::Weak<
-            RwLock<HashMap<usize, Option<(Arc<TextureHandle>, (f32, f32))>>>,
-        >,
+        mut receiver: tokio::sync::mpsc::Receiver<CoverTextureUpdate>,
+        cover_textures: std::sync::Weak<RwLock<CoverTextures>>,
         need_repaint: std::sync::Weak<AtomicBool>,
     ) {
         while let Some((id, texture, dimensions)) = receiver.recv().await {
@@ -459,7 +474,7 @@ impl SearchApp {
         need_repaint: Arc<AtomicBool>,
     ) {
         let client_guard = client.lock().await;
-        match get_access_token(&*client_guard, debug_mode).await {
+        match get_access_token(&client_guard, SPOTIFY_ACCOUNTS_BASE_URL, debug_mode).await {
             Ok(token) => {
                 let mut token_guard = access_token.lock().await;
                 *token_guard = token;
@@ -496,7 +511,7 @@ impl SearchApp {
         if !err_msg.is_empty() {
             ctx.request_repaint();
             egui::Window::new("錯誤").show(&ctx, |ui| {
-                ui.label(&err_msg.to_string());
+                ui.label(err_msg.as_str());
             });
         }
     }
@@ -658,6 +673,10 @@ impl SearchApp {
 
         self.render_side_menu(ctx);
         self.render_central_panel(ctx);
+        self.render_batch_match_window(ctx);
+        self.render_local_match_window(ctx);
+        self.render_delete_confirm_window(ctx);
+        self.render_download_queue_window(ctx);
     }
 
     fn handle_debug_mode(&mut self) {
@@ -745,8 +764,8 @@ impl SearchApp {
         let status_updates = self.collect_status_updates();
         let completed_downloads = self.process_status_updates(&status_updates);
 
-        for completed_beatmapset in completed_downloads {
-            self.handle_completed_download(&[completed_beatmapset]);
+        if !completed_downloads.is_empty() {
+            self.refresh_local_library(completed_downloads.clone(), false);
         }
 
         if !status_updates.is_empty() {
@@ -754,6 +773,95 @@ impl SearchApp {
         }
     }
 
+    // 在背景掃描下載資料夾更新譜面索引，rated 為剛下載完成、帶有星級資訊的譜面集；
+    // lazer 的檔案庫很大，只在 rescan_lazer 時（啟動、變更設定或手動重新掃描）重新讀取
+    fn refresh_local_library(&self, rated: Vec<Beatmapset>, rescan_lazer: bool) {
+        if self.is_refreshing_library.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let local_library = self.local_library.clone();
+        let beatmapset_download_statuses = self.beatmapset_download_statuses.clone();
+        let is_refreshing_library = self.is_refreshing_library.clone();
+        let need_repaint = self.need_repaint.clone();
+        let client_settings = self.osu_client_settings.lock().unwrap().clone();
+        let debug_mode = self.debug_mode;
+
+        tokio::task::spawn_blocking(move || {
+            // 掃描 lazer 檔案庫時不鎖住索引；None 為不重新掃描，Some(None) 為沒有使用 lazer
+            let lazer_scan = rescan_lazer.then(|| {
+                (client_settings.client == OsuClientKind::Lazer).then(|| {
+                    let known = local_library.lock().unwrap().lazer_files().clone();
+                    lazer::scan_library(
+                        client_settings.lazer_data_directory().as_deref(),
+                        client_settings.lazer_exported_list.as_deref(),
+                        &known,
+                        debug_mode,
+                    )
+                })
+            });
+
+            let mut library = local_library.lock().unwrap();
+            library.refresh();
+            if let Some(lazer_scan) = lazer_scan {
+                library.set_lazer_scan(lazer_scan);
+            }
+            for beatmapset in &rated {
+                library.record_difficulty_ratings(beatmapset);
+            }
+            if let Err(e) = library.save() {
+                error!("保存譜面索引失敗: {:?}", e);
+            }
+
+            // 以索引為準同步下載狀態
+            let downloaded = library.beatmapset_ids();
+            let mut statuses = beatmapset_download_statuses.lock().unwrap();
+            for (id, status) in statuses.iter_mut() {
+                if *status == DownloadStatus::Completed && !downloaded.contains(id) {
+                    *status = DownloadStatus::NotStarted;
+                }
+            }
+            for id in downloaded {
+                statuses.insert(id, DownloadStatus::Completed);
+            }
+
+            is_refreshing_library.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
+    }
+
+    // 監看下載資料夾，osu! 客戶端匯入或外部刪除時即時更新索引與下載狀態
+    fn start_download_watcher(&mut self) {
+        // 先停止監看舊的資料夾
+        self.download_watcher = None;
+
+        let local_library = self.local_library.clone();
+        let beatmapset_download_statuses = self.beatmapset_download_statuses.clone();
+        let need_repaint = self.need_repaint.clone();
+
+        let on_events = move |events: Vec<DirectoryEvent>| {
+            let mut library = local_library.lock().unwrap();
+            let affected = library.apply_events(&events);
+            if let Err(e) = library.save() {
+                error!("保存譜面索引失敗: {:?}", e);
+            }
+
+            let mut statuses = beatmapset_download_statuses.lock().unwrap();
+            for id in affected {
+                if library.contains(id) {
+                    statuses.insert(id, DownloadStatus::Completed);
+                } else if statuses.get(&id) == Some(&DownloadStatus::Completed) {
+                    statuses.insert(id, DownloadStatus::NotStarted);
+                }
+            }
+            need_repaint.store(true, Ordering::SeqCst);
+        };
+
+        match DownloadDirWatcher::start(&self.download_directory, self.debug_mode, on_events) {
+            Ok(watcher) => self.download_watcher = Some(watcher),
+            Err(e) => error!("無法監看下載資料夾 {:?}: {:?}", self.download_directory, e),
+        }
+    }
+
     fn collect_status_updates(&mut self) -> Vec<(i32, DownloadStatus)> {
         let mut status_updates = Vec::new();
         while let Ok(update) = self.status_receiver.try_recv() {
@@ -768,11 +876,11 @@ impl SearchApp {
     ) -> Vec<Beatmapset> {
         let mut completed_downloads = Vec::new();
         if let Ok(guard) = self.osu_search_results.try_lock() {
-            for &(beatmapset_id, status) in status_updates {
-                if let Some(index) = guard.iter().position(|b| b.id == beatmapset_id) {
+            for (beatmapset_id, status) in status_updates {
+                if let Some(index) = guard.iter().position(|b| b.id == *beatmapset_id) {
                     self.osu_download_statuses
-                        .insert(beatmapset_id.try_into().unwrap(), status);
-                    if status == DownloadStatus::Completed {
+                        .insert((*beatmapset_id).try_into().unwrap(), status.clone());
+                    if *status == DownloadStatus::Completed {
                         completed_downloads.push(guard[index].clone());
                         // 移除這兩行代碼：
                         // guard.remove(index);
@@ -784,29 +892,6 @@ impl SearchApp {
         completed_downloads
     }
 
-    fn handle_completed_download(&mut self, guard: &[Beatmapset]) {
-        if let Some((waiting_index, waiting_beatmapset)) = self.find_waiting_download(guard) {
-            self.start_waiting_download(waiting_index, waiting_beatmapset);
-        }
-    }
-
-    fn find_waiting_download(&self, guard: &[Beatmapset]) -> Option<(usize, i32)> {
-        self.osu_download_statuses
-            .iter()
-            .find(|(_, &status)| status == DownloadStatus::Waiting)
-            .map(|(index, _)| (*index, guard[*index].id))
-    }
-
-    fn start_waiting_download(&mut self, waiting_index: usize, waiting_beatmapset: i32) {
-        self.osu_download_statuses
-            .insert(waiting_index, DownloadStatus::Downloading);
-        if let Err(e) = self.download_queue_sender.try_send(waiting_beatmapset) {
-            error!("無法將等待中的圖譜加入下載隊列: {:?}", e);
-            self.osu_download_statuses
-                .insert(waiting_index, DownloadStatus::Waiting);
-        }
-    }
-
     // 新增清理方法
     fn clean_up_resources(&mut self) {
         // 清理搜尋結果
@@ -857,9 +942,9 @@ impl SearchApp {
 impl SearchApp {
     fn new(
         client: Arc<tokio::sync::Mutex<Client>>,
-        sender: Sender<(usize, Arc<TextureHandle>, (f32, f32))>,
-        receiver: tokio::sync::mpsc::Receiver<(usize, Arc<TextureHandle>, (f32, f32))>,
-        cover_textures: Arc<RwLock<HashMap<usize, Option<(Arc<TextureHandle>, (f32, f32))>>>>,
+        sender: Sender<CoverTextureUpdate>,
+        receiver: tokio::sync::mpsc::Receiver<CoverTextureUpdate>,
+        cover_textures: Arc<RwLock<CoverTextures>>,
         need_repaint: Arc<AtomicBool>,
         ctx: egui::Context,
         config_errors: Arc<Mutex<Vec<String>>>,
@@ -867,7 +952,7 @@ impl SearchApp {
     ) -> Result<Self, AppError> {
         let texture_cache: Arc<RwLock<HashMap<String, Arc<TextureHandle>>>> =
             Arc::new(RwLock::new(HashMap::new()));
-        let texture_load_queue: Arc<Mutex<BinaryHeap<Reverse<(usize, String)>>>> =
+        let texture_load_queue: Arc<Mutex<TextureLoadQueue>> =
             Arc::new(Mutex::new(BinaryHeap::new()));
 
         let texture_cache_clone = Arc::clone(&texture_cache);
@@ -877,11 +962,14 @@ impl SearchApp {
 
         let spotify_icon = load_spotify_icon(&ctx);
         let config = read_config(debug_mode)?;
+        let beatmap_mirrors = Arc::new(MirrorPool::from_names(&config.mirrors));
 
         let (update_check_sender, update_check_receiver) = tokio::sync::mpsc::channel(100); // 設置適當的緩衝區大小
-        let mut oauth = OAuth::default();
-        oauth.redirect_uri = "http://localhost:8888/callback".to_string();
-        oauth.scopes = scopes!("user-read-currently-playing");
+        let oauth = OAuth {
+            redirect_uri: "http://localhost:8888/callback".to_string(),
+            scopes: scopes!("user-read-currently-playing"),
+            ..Default::default()
+        };
 
         let spotify_client = Arc::new(Mutex::new(None));
         let spotify_authorized = Arc::new(AtomicBool::new(false));
@@ -900,9 +988,26 @@ impl SearchApp {
         let ctx_clone2 = ctx.clone();
 
         let download_directory = load_download_directory().unwrap_or_else(|| PathBuf::from("."));
+        let local_library = LocalLibrary::load(&download_directory, debug_mode);
+
+        // 還原上次的下載佇列，未完成的項目在下載處理器啟動後繼續下載
+        let download_queue = DownloadQueue::load(debug_mode);
+        let mut initial_download_statuses = HashMap::new();
+        for item in download_queue.items() {
+            let status = match item.state {
+                QueueItemState::Failed => {
+                    DownloadStatus::Failed(item.last_error.clone().unwrap_or_default())
+                }
+                state if state.is_pending() => DownloadStatus::Waiting,
+                _ => continue,
+            };
+            initial_download_statuses.insert(item.beatmapset_id, status);
+        }
+
+        let download_settings = DownloadSettings::load();
+        let bandwidth_limiter = BandwidthLimiter::new(download_settings.bandwidth_limit_bytes());
 
         let (status_sender, status_receiver) = tokio::sync::mpsc::channel(100);
-        let (download_queue_sender, download_queue_receiver) = mpsc::channel(100);
 
         let audio_output = OutputStream::try_default().ok();
 
@@ -1065,12 +1170,24 @@ impl SearchApp {
             spotify_user_avatar,
             spotify_user_avatar_url,
             spotify_user_name,
+            osu_authorized: Arc::new(AtomicBool::new(false)),
+            osu_user_avatar: Arc::new(Mutex::new(None)),
+            osu_user_name: Arc::new(Mutex::new(None)),
 
             // 搜索相關
             search_query: String::new(),
             is_searching: Arc::new(AtomicBool::new(false)),
             search_results: Arc::new(tokio::sync::Mutex::new(Vec::new())),
             osu_search_results: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            osu_best_matches: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            spotify_search_type: SpotifySearchType::default(),
+            spotify_next_page: Arc::new(tokio::sync::Mutex::new(None)),
+            spotify_total_results: Arc::new(AtomicUsize::new(0)),
+            is_loading_spotify_page: Arc::new(AtomicBool::new(false)),
+            osu_next_page: Arc::new(tokio::sync::Mutex::new(None)),
+            osu_total_results: Arc::new(AtomicUsize::new(0)),
+            is_loading_osu_page: Arc::new(AtomicBool::new(false)),
+            osu_search_filter: BeatmapSearchFilter::default(),
             displayed_spotify_results: 10,
             displayed_osu_results: 10,
             downloaded_maps_search: String::new(),
@@ -1082,6 +1199,9 @@ impl SearchApp {
             spotify_liked_tracks: Arc::new(Mutex::new(Vec::new())),
             selected_playlist: None,
             currently_playing: Arc::new(Mutex::new(None)),
+            osu_user_lists: Arc::new(Mutex::new(HashMap::new())),
+            selected_osu_list: None,
+            loading_osu_lists: Arc::new(Mutex::new(HashSet::new())),
 
             // UI 狀態
             show_auth_progress: false,
@@ -1130,7 +1250,7 @@ impl SearchApp {
             need_repaint,
             last_update: Arc::new(Mutex::new(None)),
             last_avatar_update: Utc::now(),
-            beatmapset_download_statuses: Arc::new(Mutex::new(HashMap::new())),
+            beatmapset_download_statuses: Arc::new(Mutex::new(initial_download_statuses)),
 
             // 異步通信
             receiver: Some(receiver),
@@ -1163,10 +1283,42 @@ impl SearchApp {
             download_directory,
             status_sender,
             status_receiver,
-            download_queue_sender,
-            download_queue_receiver: Arc::new(Mutex::new(Some(download_queue_receiver))),
-            download_semaphore: Arc::new(Semaphore::new(3)), // 允許3個同時下載
-            current_downloads: Arc::new(AtomicUsize::new(0)),
+            download_queue: Arc::new(Mutex::new(download_queue)),
+            download_queue_notify: Arc::new(Notify::new()),
+            active_downloads: Arc::new(Mutex::new(HashMap::new())),
+            download_settings: Arc::new(Mutex::new(download_settings)),
+            bandwidth_limiter: Arc::new(bandwidth_limiter),
+            show_download_queue: false,
+            local_library: Arc::new(Mutex::new(local_library)),
+            is_refreshing_library: Arc::new(AtomicBool::new(false)),
+            download_watcher: None,
+            pending_beatmap_delete: None,
+            beatmap_mirrors,
+
+            // osu! 客戶端
+            osu_client_settings: Arc::new(Mutex::new(OsuClientSettings::load())),
+            running_osu_client: Arc::new(Mutex::new(None)),
+            osu_collections: Arc::new(Mutex::new(HashMap::new())),
+            new_collection_name: String::new(),
+            is_writing_collection: Arc::new(AtomicBool::new(false)),
+            collection_message: Arc::new(Mutex::new(None)),
+
+            // 批次比對播放清單
+            batch_match_entries: Arc::new(Mutex::new(Vec::new())),
+            batch_match_total: Arc::new(AtomicUsize::new(0)),
+            is_batch_matching: Arc::new(AtomicBool::new(false)),
+            batch_match_cancel: Arc::new(AtomicBool::new(false)),
+            batch_match_source: String::new(),
+            show_batch_match_report: false,
+
+            // 本機譜面反向比對 Spotify
+            local_song_matches: Arc::new(Mutex::new(Vec::new())),
+            local_match_total: Arc::new(AtomicUsize::new(0)),
+            is_local_matching: Arc::new(AtomicBool::new(false)),
+            local_match_cancel: Arc::new(AtomicBool::new(false)),
+            is_syncing_playlist: Arc::new(AtomicBool::new(false)),
+            playlist_sync_message: Arc::new(Mutex::new(None)),
+            show_local_match_report: false,
 
             // 音頻播放
             audio_output,
@@ -1183,7 +1335,12 @@ impl SearchApp {
         }
 
         app.load_default_avatar();
+        app.restore_osu_login();
         app.start_download_processor();
+        app.refresh_local_library(Vec::new(), true);
+        app.start_download_watcher();
+        app.start_osu_client_monitor();
+        app.reload_osu_collections();
 
         Ok(app)
     }
@@ -1298,6 +1455,152 @@ impl SearchApp {
         });
     }
 
+    // 讀取上次的 osu! 登入，令牌過期時自動刷新
+    fn restore_osu_login(&self) {
+        let client = self.client.clone();
+        let osu_authorized = self.osu_authorized.clone();
+        let osu_user_name = self.osu_user_name.clone();
+        let osu_user_avatar = self.osu_user_avatar.clone();
+        let beatmap_mirrors = self.beatmap_mirrors.clone();
+        let ctx = self.ctx.clone();
+        let debug_mode = self.debug_mode;
+
+        tokio::spawn(async move {
+            if !read_login_info().is_ok_and(|infos| infos.contains_key("osu")) {
+                return;
+            }
+            let config = match read_config(debug_mode) {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("讀取配置文件時出錯: {}", e);
+                    return;
+                }
+            };
+            let http = client.lock().await.clone();
+            match check_and_refresh_token(&http, &config, "osu").await {
+                Ok(login_info) => {
+                    osu_authorized.store(true, Ordering::SeqCst);
+                    beatmap_mirrors.set_osu_login(Some(login_info.clone()));
+                    if let Some(user_name) = login_info.user_name {
+                        *osu_user_name.lock().unwrap() = Some(user_name.clone());
+                        Self::load_osu_avatar(
+                            &ctx,
+                            &user_name,
+                            login_info.avatar_url.as_deref(),
+                            osu_user_avatar,
+                        )
+                        .await;
+                    }
+                }
+                Err(e) => error!("無法刷新 osu! 令牌: {}", e),
+            }
+        });
+    }
+
+    fn start_osu_authorization(&mut self, ctx: egui::Context) {
+        if self.auth_in_progress.load(Ordering::SeqCst) {
+            info!("Spotify 授權進行中，請稍後再登入 osu!");
+            return;
+        }
+        if matches!(
+            self.auth_manager.get_status(&AuthPlatform::Osu),
+            AuthStatus::WaitingForBrowser | AuthStatus::Processing | AuthStatus::TokenObtained
+        ) {
+            info!("osu! 授權已在進行中，請等待");
+            return;
+        }
+
+        info!("開始 osu! 授權流程");
+        let client = self.client.clone();
+        let auth_manager = self.auth_manager.clone();
+        let listener = self.listener.clone();
+        let osu_authorized = self.osu_authorized.clone();
+        let osu_user_name = self.osu_user_name.clone();
+        let osu_user_avatar = self.osu_user_avatar.clone();
+        let beatmap_mirrors = self.beatmap_mirrors.clone();
+        let debug_mode = self.debug_mode;
+
+        tokio::spawn(async move {
+            // 授權要等使用者操作瀏覽器，不要一直鎖住共用的 HTTP 客戶端
+            let http = client.lock().await.clone();
+            match osu::authorize_osu(&http, auth_manager, listener, debug_mode).await {
+                Ok(login_info) => {
+                    osu_authorized.store(true, Ordering::SeqCst);
+                    beatmap_mirrors.set_osu_login(Some(login_info.clone()));
+                    if let Some(user_name) = login_info.user_name {
+                        *osu_user_name.lock().unwrap() = Some(user_name.clone());
+                        Self::load_osu_avatar(
+                            &ctx,
+                            &user_name,
+                            login_info.avatar_url.as_deref(),
+                            osu_user_avatar,
+                        )
+                        .await;
+                    }
+                }
+                Err(e) => error!("osu! 授權失敗: {}", e),
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn cancel_osu_authorization(&mut self) {
+        self.auth_manager.reset(&AuthPlatform::Osu);
+        // 關閉監聽器，等待中的授權會因此結束
+        if let Ok(mut listener_guard) = self.listener.try_lock() {
+            *listener_guard = None;
+        }
+        info!("用戶取消了 osu! 授權流程");
+    }
+
+    fn logout_osu(&mut self) {
+        info!("用戶登出 osu!");
+        self.osu_authorized.store(false, Ordering::SeqCst);
+        *self.osu_user_avatar.lock().unwrap() = None;
+        self.osu_user_lists.lock().unwrap().clear();
+        self.selected_osu_list = None;
+        self.auth_manager.reset(&AuthPlatform::Osu);
+        self.beatmap_mirrors.set_osu_login(None);
+        if let Err(e) = remove_platform_login_info("osu") {
+            error!("刪除 osu! 登入信息失敗: {}", e);
+        }
+        if let Some(user_name) = self.osu_user_name.lock().unwrap().take() {
+            if let Err(e) = std::fs::remove_file(Self::get_osu_avatar_path(&user_name)) {
+                error!("刪除 osu! 使用者頭像失敗: {}", e);
+            }
+        }
+    }
+
+    fn get_osu_avatar_path(user_name: &str) -> PathBuf {
+        Self::get_avatar_path(&format!("osu_{}", user_name))
+    }
+
+    // 優先使用已下載的頭像，沒有時從 avatar_url 下載
+    async fn load_osu_avatar(
+        ctx: &egui::Context,
+        user_name: &str,
+        avatar_url: Option<&str>,
+        osu_user_avatar: Arc<Mutex<Option<egui::TextureHandle>>>,
+    ) {
+        let avatar_path = Self::get_osu_avatar_path(user_name);
+        if !avatar_path.exists() {
+            let Some(url) = avatar_url else {
+                return;
+            };
+            if let Err(e) = Self::download_and_save_avatar(url, &avatar_path).await {
+                error!("下載 osu! 頭像失敗: {:?}", e);
+                return;
+            }
+        }
+        match Self::load_local_avatar(ctx, &avatar_path) {
+            Ok(texture) => {
+                *osu_user_avatar.lock().unwrap() = texture;
+                ctx.request_repaint();
+            }
+            Err(e) => error!("加載 osu! 頭像失敗: {:?}", e),
+        }
+    }
+
     fn should_update_current_playing(&self) -> bool {
         if !self.spotify_authorized.load(Ordering::SeqCst) {
             return false; // 如果未授權，不更新
@@ -1387,6 +1690,13 @@ impl SearchApp {
         let query = self.search_query.clone();
         let search_results = self.search_results.clone();
         let osu_search_results = self.osu_search_results.clone();
+        let osu_best_matches = self.osu_best_matches.clone();
+        let spotify_search_type = self.spotify_search_type;
+        let osu_next_page = self.osu_next_page.clone();
+        let osu_total_results = self.osu_total_results.clone();
+        let spotify_next_page = self.spotify_next_page.clone();
+        let spotify_total_results = self.spotify_total_results.clone();
+        let osu_search_filter = self.osu_search_filter.clone();
         let is_searching = self.is_searching.clone();
         let need_repaint = self.need_repaint.clone();
         let err_msg = self.err_msg.clone();
@@ -1394,6 +1704,7 @@ impl SearchApp {
         let spotify_client = self.spotify_client.clone(); // 添加這行
         let ctx_clone = ctx.clone(); // 在這裡克隆 ctx
         self.displayed_osu_results = 10;
+        self.displayed_spotify_results = 10;
         self.clear_cover_textures();
         self.expanded_beatmapset_index = None;
 
@@ -1403,336 +1714,64 @@ impl SearchApp {
 
         tokio::spawn(async move {
             let result: Result<()> = async {
-                let mut error = err_msg.lock().await;
-                error.clear();
+                err_msg.lock().await.clear();
+                *osu_next_page.lock().await = None;
+                *spotify_next_page.lock().await = None;
                 if debug_mode {
                     debug!("除錯模式開啟");
                 }
 
-                let spotify_token = get_access_token(&*client.lock().await, debug_mode)
-                    .await
-                    .map_err(|e| match e {
-                        SpotifyError::AccessTokenError(msg) => {
-                            anyhow!("Spotify 錯誤：無法獲取 token: {}", msg)
-                        }
-                        SpotifyError::RequestError(e) => anyhow!("Spotify 請求錯誤：{}", e),
-                        _ => anyhow!("Spotify 錯誤：{}", e),
-                    })?;
-
-                let osu_token = get_osu_token(&*client.lock().await, debug_mode)
-                    .await
-                    .map_err(|e| {
-                        error!("獲取 Osu token 錯誤: {:?}", e);
-                        anyhow!("Osu 錯誤：無法獲取 token")
-                    })?;
-
-                if let Some((beatmapset_id, _)) = parse_osu_url(&query) {
-                    info!("Osu 搜尋: {}", query);
-
-                    // 如果是 osu! URL，獲取譜面信息並進行反搜索
-                    let (artist, title) = get_beatmapset_details(
-                        &*client.lock().await,
-                        &osu_token,
-                        &beatmapset_id,
-                        debug_mode,
-                    )
-                    .await
-                    .map_err(|e| {
-                        error!("獲取 Osu 譜面詳情錯誤: {:?}", e);
-                        anyhow!("Osu 錯誤：獲取譜面詳情失敗")
-                    })?;
-
-                    let spotify_query = format!("{} {}", artist, title);
-                    info!("Spotify 查詢 (從 osu): {}", spotify_query);
-
-                    // 使用獲取的 artist 和 title 進行 Spotify 搜索
-                    let tracks_with_cover = search_track(
-                        &*client.lock().await,
-                        &spotify_query,
-                        &spotify_token,
-                        10,
-                        0,
-                        debug_mode,
-                    )
-                    .await
-                    .map(|(tracks_with_cover, _)| tracks_with_cover)
-                    .map_err(|e| {
-                        error!("Spotify 反搜索錯誤: {:?}", e);
-                        anyhow!("Spotify 錯誤：反搜索失敗")
-                    })?;
-
-                    // 更新 Spotify 搜索結果
-                    let mut search_results = search_results.lock().await;
-                    *search_results = tracks_with_cover
-                        .iter()
-                        .map(|twc| Track {
-                            name: twc.name.clone(),
-                            artists: twc.artists.clone(),
-                            album: Album {
-                                name: twc.album_name.clone(),
-                                album_type: String::new(),
-                                artists: Vec::new(),
-                                external_urls: HashMap::new(),
-                                images: twc
-                                    .cover_url
-                                    .as_ref()
-                                    .map(|url| {
-                                        vec![Image {
-                                            url: url.clone(),
-                                            width: 0,
-                                            height: 0,
-                                        }]
-                                    })
-                                    .unwrap_or_default(),
-                                id: String::new(),
-                                release_date: String::new(),
-                                total_tracks: 0,
-                            },
-                            external_urls: twc.external_urls.clone(),
-                            index: twc.index,
-                            is_liked: None, // 添加缺失的 is_liked 字段
-                        })
-                        .collect();
-
-                    // 獲取 osu! beatmapset
-                    let beatmapset = get_beatmapset_by_id(
-                        &*client.lock().await,
-                        &osu_token,
-                        &beatmapset_id,
-                        debug_mode,
-                    )
-                    .await
-                    .map_err(|e| {
-                        error!("獲取 Osu 譜面錯誤: {:?}", e);
-                        anyhow!("Osu 錯誤：獲取譜面失敗")
-                    })?;
-
-                    let results = vec![beatmapset];
-                    *osu_search_results.lock().await = results.clone();
+                let engine = CrossSearchEngine::new(client.lock().await.clone(), debug_mode)
+                    .with_spotify_search_type(spotify_search_type)
+                    .with_osu_filter(osu_search_filter);
+                let search_result = engine.search(&query).await?;
 
-                    let mut osu_covers = Vec::new();
-                    for (index, beatmapset) in results.iter().enumerate() {
-                        osu_covers.push((index, beatmapset.covers.clone()));
-                    }
-                    *osu_search_results.lock().await = results;
-
-                    if let Err(e) =
-                        load_osu_covers(osu_covers, ctx_clone.clone(), sender.clone()).await
-                    {
-                        error!("載入 osu 封面時發生錯誤: {:?}", e);
-                        if debug_mode {
-                            ctx_clone.request_repaint();
-                            egui::Window::new("Error").show(&ctx_clone, |ui| {
-                                ui.label("部分 osu 封面載入失敗:");
-                                ui.label(format!("{:?}", e));
-                            });
-                        }
-                    }
-                } else {
-                    // 如果不是 osu! URL，執行原有的搜索邏輯
-                    let spotify_result: Result<Vec<TrackWithCover>> =
-                        match is_valid_spotify_url(&query) {
-                            Ok(status) => match status {
-                                SpotifyUrlStatus::Valid => {
-                                    info!("Spotify 查詢 (URL): {}", query);
-                                    let track_id = query
-                                        .split('/')
-                                        .last()
-                                        .unwrap_or("")
-                                        .split('?')
-                                        .next()
-                                        .unwrap_or("");
-                                    let track = get_track_info(
-                                        &*client.lock().await,
-                                        track_id,
-                                        &spotify_token,
-                                    )
-                                    .await
-                                    .map_err(|e| anyhow!("獲取曲目資訊錯誤: {:?}", e))?;
-
-                                    Ok(vec![TrackWithCover {
-                                        name: track.name.clone(),
-                                        artists: track.artists.clone(),
-                                        external_urls: track.external_urls.clone(),
-                                        album_name: track.album.name.clone(),
-                                        cover_url: track
-                                            .album
-                                            .images
-                                            .first()
-                                            .map(|img| img.url.clone()),
-                                        index: 0, // 添加這行，給予一個固定的索引
-                                    }])
-                                }
-                                SpotifyUrlStatus::Incomplete => {
-                                    *error = "Spotify URL 不完整，請輸入完整的 URL".to_string();
-                                    return Ok(());
-                                }
-                                SpotifyUrlStatus::Invalid => {
-                                    *error = "無效的 Spotify URL".to_string();
-                                    return Ok(());
-                                }
-                                SpotifyUrlStatus::NotSpotify => {
-                                    // 執行普通搜索
-                                    if !query.is_empty() {
-                                        info!("Spotify 查詢 (關鍵字): {}", query);
-                                        let limit = 50;
-                                        let offset = 0;
-                                        search_track(
-                                            &*client.lock().await,
-                                            &query,
-                                            &spotify_token,
-                                            limit,
-                                            offset,
-                                            debug_mode,
-                                        )
-                                        .await
-                                        .map(|(tracks_with_cover, _)| tracks_with_cover)
-                                        .map_err(|e| anyhow!("Spotify 搜索錯誤: {}", e))
-                                    } else {
-                                        Ok(Vec::new())
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                error!("驗證 Spotify URL 時發生錯誤: {:?}", e);
-                                Err(anyhow!("Spotify URL 驗證錯誤"))
-                            }
-                        };
+                // 更新 Spotify 搜索結果
+                let mut tracks = search_result.spotify_tracks;
 
-                    let osu_query = match spotify_result {
-                        Ok(ref tracks_with_cover) => {
-                            info!("Spotify 搜索結果: {} 首曲目", tracks_with_cover.len());
-                            let mut search_results = search_results.lock().await;
-                            *search_results = tracks_with_cover
-                                .iter()
-                                .map(|twc| Track {
-                                    name: twc.name.clone(),
-                                    artists: twc.artists.clone(),
-                                    album: Album {
-                                        name: twc.album_name.clone(),
-                                        album_type: String::new(),
-                                        artists: Vec::new(),
-                                        external_urls: HashMap::new(),
-                                        images: twc
-                                            .cover_url
-                                            .as_ref()
-                                            .map(|url| {
-                                                vec![Image {
-                                                    url: url.clone(),
-                                                    width: 0,
-                                                    height: 0,
-                                                }]
-                                            })
-                                            .unwrap_or_default(),
-                                        id: String::new(),
-                                        release_date: String::new(),
-                                        total_tracks: 0,
-                                    },
-                                    external_urls: twc.external_urls.clone(),
-                                    index: twc.index,
-                                    is_liked: None, // 初始化為 None
-                                })
-                                .collect();
-
-                            // 檢查前十首歌曲的喜歡狀態
-                            if !search_results.is_empty() {
-                                let track_ids: Vec<TrackId> = search_results
-                                    .iter()
-                                    .take(10)
-                                    .filter_map(|track| {
-                                        track
-                                            .external_urls
-                                            .get("spotify")
-                                            .and_then(|url| url.split('/').last())
-                                            .and_then(|id| TrackId::from_id(id).ok())
-                                    })
-                                    .collect();
-
-                                let spotify_option = {
-                                    let spotify_guard = spotify_client.lock().unwrap();
-                                    spotify_guard.as_ref().cloned()
-                                };
+                // 檢查前十首歌曲的喜歡狀態（osu! 反查時不檢查，與原行為一致）
+                if !matches!(search_result.kind, QueryKind::OsuBeatmapset { .. }) {
+                    let first_ten = tracks.len().min(10);
+                    Self::update_liked_statuses(&spotify_client, &mut tracks[..first_ten]).await;
+                }
+                let loaded_tracks = tracks.len() as u32;
+                *search_results.lock().await = tracks;
+                // 還有未載入的曲目時記下下一頁的 offset
+                *spotify_next_page.lock().await = search_result.spotify_query.and_then(|q| {
+                    (loaded_tracks < search_result.spotify_total.min(SPOTIFY_MAX_OFFSET))
+                        .then_some((q, loaded_tracks))
+                });
+                spotify_total_results.store(search_result.spotify_total as usize, Ordering::SeqCst);
 
-                                if let Some(spotify) = spotify_option {
-                                    match spotify
-                                        .current_user_saved_tracks_contains(track_ids)
-                                        .await
-                                    {
-                                        Ok(statuses) => {
-                                            for (track, &is_liked) in
-                                                search_results.iter_mut().zip(statuses.iter())
-                                            {
-                                                track.is_liked = Some(is_liked);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!("無法檢查歌曲喜歡狀態: {:?}", e);
-                                        }
-                                    }
-                                }
-                            }
+                let results = search_result.osu_beatmapsets;
+                let mut osu_covers = Vec::new();
+                for (index, beatmapset) in results.iter().enumerate().take(10) {
+                    osu_covers.push((index, beatmapset.covers.clone()));
+                }
+                *osu_search_results.lock().await = results;
+                *osu_best_matches.lock().await = search_result
+                    .best_match
+                    .into_iter()
+                    .chain(search_result.track_matches.iter().filter_map(|m| m.best_match))
+                    .collect();
+                *osu_next_page.lock().await = search_result.osu_query.zip(search_result.osu_cursor);
+                osu_total_results.store(search_result.osu_total as usize, Ordering::SeqCst);
 
-                            if matches!(is_valid_spotify_url(&query), Ok(SpotifyUrlStatus::Valid))
-                                && !tracks_with_cover.is_empty()
-                            {
-                                let osu_query = format!(
-                                    "{} {}",
-                                    tracks_with_cover[0]
-                                        .artists
-                                        .iter()
-                                        .map(|a| a.name.clone())
-                                        .collect::<Vec<_>>()
-                                        .join(", "),
-                                    tracks_with_cover[0].name
-                                );
-                                info!("Osu 查詢 (從 Spotify): {}", osu_query);
-                                osu_query
-                            } else {
-                                info!("Osu 查詢 (關鍵字): {}", query);
-                                query.clone()
-                            }
-                        }
-                        Err(e) => {
-                            error!("Spotify 搜索錯誤: {:?}", e);
-                            return Err(anyhow!("Spotify 錯誤：搜索失敗"));
-                        }
-                    };
-                    let results =
-                        get_beatmapsets(&*client.lock().await, &osu_token, &osu_query, debug_mode)
-                            .await
-                            .map_err(|e| {
-                                error!("Osu 搜索錯誤: {:?}", e);
-                                anyhow!("Osu 錯誤：搜索失敗")
-                            })?;
+                info!("初始加載 osu 封面：共 {} 個", osu_covers.len());
 
-                    info!("Osu 搜索結果: {} 個 beatmapsets", results.len());
+                let osu_covers_len = osu_covers.len();
+                if let Err(e) = load_osu_covers(osu_covers, ctx_clone.clone(), sender.clone()).await
+                {
+                    error!("載入 osu 封面時發生錯誤: {:?}", e);
                     if debug_mode {
-                        debug!("Osu 搜索結果詳情: {:?}", results);
-                    }
-
-                    let mut osu_covers = Vec::new();
-                    for (index, beatmapset) in results.iter().enumerate().take(10) {
-                        osu_covers.push((index, beatmapset.covers.clone()));
-                    }
-                    *osu_search_results.lock().await = results;
-
-                    info!("初始加載 osu 封面：共 {} 個", osu_covers.len());
-
-                    let osu_covers_len = osu_covers.len();
-                    if let Err(e) =
-                        load_osu_covers(osu_covers, ctx_clone.clone(), sender.clone()).await
-                    {
-                        error!("載入 osu 封面時發生錯誤: {:?}", e);
-                        if debug_mode {
-                            ctx_clone.request_repaint();
-                            egui::Window::new("Error").show(&ctx_clone, |ui| {
-                                ui.label("部分 osu 封面載入失敗:");
-                                ui.label(format!("{:?}", e));
-                            });
-                        }
-                    } else {
-                        info!("成功初始加載 {} 個 osu 封面", osu_covers_len);
+                        ctx_clone.request_repaint();
+                        egui::Window::new("Error").show(&ctx_clone, |ui| {
+                            ui.label("部分 osu 封面載入失敗:");
+                            ui.label(format!("{:?}", e));
+                        });
                     }
+                } else {
+                    info!("成功初始加載 {} 個 osu 封面", osu_covers_len);
                 }
 
                 Ok(())
@@ -1758,8 +1797,9 @@ impl SearchApp {
         // 計算實際顯示的結果數量
         let displayed_results = self.displayed_spotify_results.min(total_results);
 
-        // 顯示 Spotify 搜索結果的標題和統計信息
-        self.display_spotify_header(ui, total_results, displayed_results);
+        // 顯示 Spotify 搜索結果的標題和統計信息（總數以 API 回傳的為準）
+        let api_total = self.spotify_total_results.load(Ordering::SeqCst);
+        self.display_spotify_header(ui, total_results.max(api_total), displayed_results);
 
         if !sorted_results.is_empty() {
             // 遍歷並顯示每個搜索結果
@@ -1773,47 +1813,167 @@ impl SearchApp {
         };
     }
 
-    fn get_sorted_spotify_results(&self) -> Vec<Track> {
-        self.search_results
+    fn has_next_spotify_page(&self) -> bool {
+        self.spotify_next_page
             .try_lock()
-            .map(|guard| {
-                let mut results = guard.clone();
-                results.sort_by_key(|track| track.index);
-                results
-            })
-            .unwrap_or_default()
+            .map(|guard| guard.is_some())
+            .unwrap_or(false)
     }
 
-    fn display_spotify_header(
-        &self,
-        ui: &mut egui::Ui,
-        total_results: usize,
-        displayed_results: usize,
-    ) {
-        ui.horizontal(|ui| {
-            // 左側：標題、結果統計和總結果數
-            ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
-                ui.add_space(20.0);
-                let text_color = if ui.visuals().dark_mode {
-                    egui::Color32::from_hex("#FFFFFF").unwrap_or(egui::Color32::WHITE)
-                } else {
-                    egui::Color32::from_hex("#121212").unwrap_or(egui::Color32::BLACK)
-                };
-                ui.label(
-                    egui::RichText::new(format!("總結果數: {}", total_results))
-                        .size(self.global_font_size)
-                        .color(text_color),
-                );
-                ui.label(
-                    egui::RichText::new(format!("當前顯示結果數: {}", displayed_results))
-                        .size(self.global_font_size)
-                        .color(text_color),
-                );
-            });
+    //以 offset 載入下一頁 Spotify 搜尋結果並接在目前結果後面
+    fn load_next_spotify_page(&mut self) {
+        let Some((spotify_query, offset)) = self
+            .spotify_next_page
+            .try_lock()
+            .ok()
+            .and_then(|guard| guard.clone())
+        else {
+            return;
+        };
+        if self.is_loading_spotify_page.swap(true, Ordering::SeqCst) {
+            return;
+        }
 
-            // 右側：Spotify logo
-            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                let logo_key = if ui.visuals().dark_mode {
+        let client = self.client.clone();
+        let debug_mode = self.debug_mode;
+        let search_results = self.search_results.clone();
+        let spotify_next_page = self.spotify_next_page.clone();
+        let spotify_client = self.spotify_client.clone();
+        let is_loading_spotify_page = self.is_loading_spotify_page.clone();
+        let need_repaint = self.need_repaint.clone();
+        let err_msg = self.err_msg.clone();
+        self.displayed_spotify_results += 10;
+
+        tokio::spawn(async move {
+            let engine = CrossSearchEngine::new(client.lock().await.clone(), debug_mode);
+
+            match engine.next_spotify_page(&spotify_query, offset).await {
+                Ok(mut page) => {
+                    let mut next_page = spotify_next_page.lock().await;
+                    // 載入期間使用者可能已重新搜尋，查詢不同時丟棄這一頁
+                    if next_page.as_ref() == Some(&(spotify_query.clone(), offset)) {
+                        let next_offset = offset + page.tracks.len() as u32;
+                        *next_page = (!page.tracks.is_empty()
+                            && next_offset < page.total.min(SPOTIFY_MAX_OFFSET))
+                        .then_some((spotify_query, next_offset));
+                        drop(next_page);
+
+                        Self::update_liked_statuses(&spotify_client, &mut page.tracks).await;
+
+                        // 翻頁期間排名可能變動，略過已經顯示過的曲目以保持列表穩定
+                        let mut results = search_results.lock().await;
+                        let existing: HashSet<String> = results
+                            .iter()
+                            .filter_map(|track| track.external_urls.get("spotify").cloned())
+                            .collect();
+                        let new_tracks: Vec<Track> = page
+                            .tracks
+                            .into_iter()
+                            .filter(|track| {
+                                track
+                                    .external_urls
+                                    .get("spotify")
+                                    .is_none_or(|url| !existing.contains(url))
+                            })
+                            .collect();
+                        info!("Spotify 下一頁載入 {} 首曲目", new_tracks.len());
+                        results.extend(new_tracks);
+                    }
+                }
+                Err(e) => {
+                    error!("載入 Spotify 下一頁失敗: {:?}", e);
+                    // 清除 offset，避免畫面停在底部時不斷自動重試
+                    *spotify_next_page.lock().await = None;
+                    *err_msg.lock().await = e.to_string();
+                }
+            }
+
+            is_loading_spotify_page.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
+    }
+
+    //查詢使用者是否已收藏這些曲目（需已登入 Spotify，一次最多 50 首）
+    async fn update_liked_statuses(
+        spotify_client: &Arc<Mutex<Option<AuthCodeSpotify>>>,
+        tracks: &mut [Track],
+    ) {
+        let spotify_option = {
+            let spotify_guard = spotify_client.lock().unwrap();
+            spotify_guard.as_ref().cloned()
+        };
+        let Some(spotify) = spotify_option else {
+            return;
+        };
+
+        for chunk in tracks.chunks_mut(50) {
+            // 索引與 ID 一起收集，沒有網址的曲目被略過時結果才不會錯位
+            let (indices, track_ids): (Vec<usize>, Vec<TrackId>) = chunk
+                .iter()
+                .enumerate()
+                .filter_map(|(index, track)| {
+                    let track_id = TrackId::from_id(track_id_from_track(track)?).ok()?;
+                    Some((index, track_id))
+                })
+                .unzip();
+            if track_ids.is_empty() {
+                continue;
+            }
+
+            match spotify.current_user_saved_tracks_contains(track_ids).await {
+                Ok(statuses) => {
+                    for (index, is_liked) in indices.into_iter().zip(statuses) {
+                        chunk[index].is_liked = Some(is_liked);
+                    }
+                }
+                Err(e) => {
+                    error!("無法檢查歌曲喜歡狀態: {:?}", e);
+                }
+            }
+        }
+    }
+
+    fn get_sorted_spotify_results(&self) -> Vec<Track> {
+        self.search_results
+            .try_lock()
+            .map(|guard| {
+                let mut results = guard.clone();
+                results.sort_by_key(|track| track.index);
+                results
+            })
+            .unwrap_or_default()
+    }
+
+    fn display_spotify_header(
+        &self,
+        ui: &mut egui::Ui,
+        total_results: usize,
+        displayed_results: usize,
+    ) {
+        ui.horizontal(|ui| {
+            // 左側：標題、結果統計和總結果數
+            ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
+                ui.add_space(20.0);
+                let text_color = if ui.visuals().dark_mode {
+                    egui::Color32::from_hex("#FFFFFF").unwrap_or(egui::Color32::WHITE)
+                } else {
+                    egui::Color32::from_hex("#121212").unwrap_or(egui::Color32::BLACK)
+                };
+                ui.label(
+                    egui::RichText::new(format!("總結果數: {}", total_results))
+                        .size(self.global_font_size)
+                        .color(text_color),
+                );
+                ui.label(
+                    egui::RichText::new(format!("當前顯示結果數: {}", displayed_results))
+                        .size(self.global_font_size)
+                        .color(text_color),
+                );
+            });
+
+            // 右側：Spotify logo
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let logo_key = if ui.visuals().dark_mode {
                     "Spotify_Full_Logo_RGB_White.png"
                 } else {
                     "Spotify_Full_Logo_RGB_Black.png"
@@ -1856,10 +2016,34 @@ impl SearchApp {
                     self.displayed_spotify_results =
                         (self.displayed_spotify_results + 10).min(total_results);
                 }
+            } else if self.has_next_spotify_page() {
+                if self.is_loading_spotify_page.load(Ordering::SeqCst) {
+                    ui.add(egui::Spinner::new().size(24.0));
+                    ui.label(egui::RichText::new("載入中...").size(18.0));
+                } else {
+                    let response = ui.add_sized(
+                        [150.0, 40.0],
+                        egui::Button::new(egui::RichText::new("載入更多").size(18.0)),
+                    );
+                    // 捲動到底部（按鈕出現在畫面中）時自動載入下一頁
+                    if response.clicked() || ui.is_rect_visible(response.rect) {
+                        self.load_next_spotify_page();
+                    }
+                }
             } else {
                 ui.label(egui::RichText::new("已顯示所有結果").size(18.0));
             }
             ui.add_space(20.0);
+            let api_total = self.spotify_total_results.load(Ordering::SeqCst);
+            ui.label(
+                egui::RichText::new(format!(
+                    "{} / {} 筆結果",
+                    displayed_results,
+                    api_total.max(total_results)
+                ))
+                .size(18.0),
+            );
+            ui.add_space(20.0);
             if ui
                 .add_sized(
                     [150.0, 40.0],
@@ -2171,6 +2355,12 @@ impl SearchApp {
         self.perform_search(self.ctx.clone());
     }
 
+    // 以 osu! 譜面集網址反查 Spotify 曲目
+    fn handle_osu_beatmapset_search_click(&mut self, beatmapset_id: i32) {
+        self.search_query = format!("https://osu.ppy.sh/beatmapsets/{}", beatmapset_id);
+        self.perform_search(self.ctx.clone());
+    }
+
     fn handle_open_click(&self, track: &Track) {
         if let Some(url) = track.external_urls.get("spotify") {
             if let Err(e) = open_spotify_url(url) {
@@ -2183,13 +2373,9 @@ impl SearchApp {
         if self.spotify_authorized.load(Ordering::SeqCst)
             && self.spotify_client.lock().unwrap().is_some()
         {
-            let track_id = track
-                .external_urls
-                .get("spotify")
-                .and_then(|url| url.split('/').last())
-                .unwrap_or("");
+            let track_id = track_id_from_track(track).unwrap_or_default();
             let is_liked = track.is_liked.unwrap_or(false);
-            self.toggle_track_like_status(track_id, is_liked, index, ctx);
+            self.toggle_track_like_status(&track_id, is_liked, index, ctx);
         }
     }
 
@@ -2248,7 +2434,7 @@ impl SearchApp {
                 add_button(
                     "開啟",
                     Box::new(move || {
-                        if let Err(e) = open_spotify_url(&url) {
+                        if let Err(e) = open_spotify_url(url) {
                             log::error!("無法開啟 URL: {}", e);
                         }
                     }),
@@ -2264,8 +2450,9 @@ impl SearchApp {
         // 計算實際顯示的結果數量
         let displayed_results = self.displayed_osu_results.min(total_results);
 
-        // 顯示 osu 搜索結果的標題和統計信息
-        self.display_osu_header(ui, total_results, displayed_results);
+        // 顯示 osu 搜索結果的標題和統計信息（總數以 API 回傳的為準）
+        let api_total = self.osu_total_results.load(Ordering::SeqCst);
+        self.display_osu_header(ui, total_results.max(api_total), displayed_results);
 
         if !sorted_results.is_empty() {
             // 檢查是否有選中的譜面集
@@ -2294,7 +2481,7 @@ impl SearchApp {
 
     //顯示osu搜索結果的標題和統計信息
     fn display_osu_header(
-        &self,
+        &mut self,
         ui: &mut egui::Ui,
         total_results: usize,
         displayed_results: usize,
@@ -2330,9 +2517,126 @@ impl SearchApp {
                 }
             });
         });
+        self.display_osu_filter(ui);
         ui.add_space(10.0);
     }
 
+    //顯示osu譜面搜尋篩選條件，變更後重新搜尋
+    fn display_osu_filter(&mut self, ui: &mut egui::Ui) {
+        let previous_filter = self.osu_search_filter.clone();
+        let filter = &mut self.osu_search_filter;
+
+        let title = if filter.is_default() {
+            "篩選條件".to_string()
+        } else {
+            "篩選條件（已套用）".to_string()
+        };
+
+        egui::CollapsingHeader::new(egui::RichText::new(title).size(self.global_font_size * 0.9))
+            .id_source("osu_search_filter")
+            .show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("模式:");
+                    egui::ComboBox::from_id_source("osu_filter_mode")
+                        .selected_text(filter.mode.map_or("全部", |m| m.label()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut filter.mode, None, "全部");
+                            for mode in OsuMode::ALL {
+                                ui.selectable_value(&mut filter.mode, Some(mode), mode.label());
+                            }
+                        });
+
+                    ui.label("狀態:");
+                    egui::ComboBox::from_id_source("osu_filter_status")
+                        .selected_text(filter.status.map_or("預設", |s| s.label()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut filter.status, None, "預設");
+                            for status in RankedStatus::ALL {
+                                ui.selectable_value(
+                                    &mut filter.status,
+                                    Some(status),
+                                    status.label(),
+                                );
+                            }
+                        });
+
+                    ui.label("曲風:");
+                    egui::ComboBox::from_id_source("osu_filter_genre")
+                        .selected_text(filter.genre.map_or("全部", |g| g.label()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut filter.genre, None, "全部");
+                            for genre in Genre::ALL {
+                                ui.selectable_value(&mut filter.genre, Some(genre), genre.label());
+                            }
+                        });
+
+                    ui.label("語言:");
+                    egui::ComboBox::from_id_source("osu_filter_language")
+                        .selected_text(filter.language.map_or("全部", |l| l.label()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut filter.language, None, "全部");
+                            for language in Language::ALL {
+                                ui.selectable_value(
+                                    &mut filter.language,
+                                    Some(language),
+                                    language.label(),
+                                );
+                            }
+                        });
+                });
+
+                ui.horizontal_wrapped(|ui| {
+                    // 0 代表不限制
+                    let mut min_stars = filter.min_stars.unwrap_or(0.0);
+                    let mut max_stars = filter.max_stars.unwrap_or(0.0);
+                    ui.label("星級:");
+                    ui.add(
+                        egui::DragValue::new(&mut min_stars)
+                            .clamp_range(0.0..=15.0)
+                            .speed(0.1)
+                            .custom_formatter(|v, _| {
+                                if v <= 0.0 {
+                                    "不限".to_string()
+                                } else {
+                                    format!("{:.1}", v)
+                                }
+                            }),
+                    );
+                    ui.label("~");
+                    ui.add(
+                        egui::DragValue::new(&mut max_stars)
+                            .clamp_range(0.0..=15.0)
+                            .speed(0.1)
+                            .custom_formatter(|v, _| {
+                                if v <= 0.0 {
+                                    "不限".to_string()
+                                } else {
+                                    format!("{:.1}", v)
+                                }
+                            }),
+                    );
+                    filter.min_stars = (min_stars > 0.0).then_some(min_stars);
+                    filter.max_stars = (max_stars > 0.0).then_some(max_stars);
+
+                    ui.checkbox(&mut filter.has_video, "有影片");
+                    ui.checkbox(&mut filter.has_storyboard, "有 Storyboard");
+                    ui.checkbox(&mut filter.nsfw, "顯示 NSFW");
+
+                    if ui.button("重設").clicked() {
+                        *filter = BeatmapSearchFilter::default();
+                    }
+                });
+            });
+
+        if self.osu_search_filter != previous_filter
+            && !self.search_query.is_empty()
+            && !self.is_searching.load(Ordering::SeqCst)
+        {
+            info!("osu 篩選條件變更，重新搜尋");
+            self.perform_search(self.ctx.clone());
+        }
+    }
+
     //顯示osu搜索結果的底部控制元素
     fn display_osu_footer(
         &mut self,
@@ -2354,6 +2658,20 @@ impl SearchApp {
                     self.displayed_osu_results = new_displayed_results;
                     self.load_more_osu_covers(displayed_results, new_displayed_results);
                 }
+            } else if self.has_next_osu_page() {
+                if self.is_loading_osu_page.load(Ordering::SeqCst) {
+                    ui.add(egui::Spinner::new().size(24.0));
+                    ui.label(egui::RichText::new("載入中...").size(18.0));
+                } else {
+                    let response = ui.add_sized(
+                        [150.0, 40.0],
+                        egui::Button::new(egui::RichText::new("載入更多").size(18.0)),
+                    );
+                    // 捲動到底部（按鈕出現在畫面中）時自動載入下一頁
+                    if response.clicked() || ui.is_rect_visible(response.rect) {
+                        self.load_next_osu_page();
+                    }
+                }
             } else {
                 ui.label(egui::RichText::new("已顯示所有結果").size(18.0));
             }
@@ -2376,14 +2694,93 @@ impl SearchApp {
     //獲取排序後的osu搜索結果
     fn get_sorted_osu_results(&self) -> Vec<Beatmapset> {
         if let Ok(osu_search_results_guard) = self.osu_search_results.try_lock() {
-            let results = osu_search_results_guard.clone();
-            results
+            osu_search_results_guard.clone()
         } else {
             error!("無法獲取 osu 搜索結果鎖");
             Vec::new()
         }
     }
 
+    fn has_next_osu_page(&self) -> bool {
+        self.osu_next_page
+            .try_lock()
+            .map(|guard| guard.is_some())
+            .unwrap_or(false)
+    }
+
+    //從osu! API 載入下一頁搜尋結果並接在目前結果後面
+    fn load_next_osu_page(&mut self) {
+        let Some((osu_query, cursor)) = self
+            .osu_next_page
+            .try_lock()
+            .ok()
+            .and_then(|guard| guard.clone())
+        else {
+            return;
+        };
+        if self.is_loading_osu_page.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let client = self.client.clone();
+        let debug_mode = self.debug_mode;
+        let osu_search_filter = self.osu_search_filter.clone();
+        let osu_search_results = self.osu_search_results.clone();
+        let osu_next_page = self.osu_next_page.clone();
+        let is_loading_osu_page = self.is_loading_osu_page.clone();
+        let need_repaint = self.need_repaint.clone();
+        let err_msg = self.err_msg.clone();
+        let sender = self.sender.clone();
+        let ctx = self.ctx.clone();
+        self.displayed_osu_results += 10;
+
+        tokio::spawn(async move {
+            let engine = CrossSearchEngine::new(client.lock().await.clone(), debug_mode)
+                .with_osu_filter(osu_search_filter);
+
+            match engine.next_osu_page(&osu_query, &cursor).await {
+                Ok(page) => {
+                    let mut next_page = osu_next_page.lock().await;
+                    // 載入期間使用者可能已重新搜尋，游標不同時丟棄這一頁
+                    if next_page.as_ref() == Some(&(osu_query.clone(), cursor.clone())) {
+                        let mut results = osu_search_results.lock().await;
+                        let start = results.len();
+                        let osu_covers: Vec<_> = page
+                            .beatmapsets
+                            .iter()
+                            .enumerate()
+                            .take(10)
+                            .map(|(offset, beatmapset)| (start + offset, beatmapset.covers.clone()))
+                            .collect();
+                        info!("osu 下一頁載入 {} 個 beatmapsets", page.beatmapsets.len());
+
+                        *next_page = if page.beatmapsets.is_empty() {
+                            None
+                        } else {
+                            page.cursor_string.map(|cursor| (osu_query, cursor))
+                        };
+                        results.extend(page.beatmapsets);
+                        drop(results);
+                        drop(next_page);
+
+                        if let Err(e) = load_osu_covers(osu_covers, ctx.clone(), sender).await {
+                            error!("載入更多 osu 封面時發生錯誤: {:?}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("載入 osu 下一頁失敗: {:?}", e);
+                    // 清除游標，避免畫面停在底部時不斷自動重試
+                    *osu_next_page.lock().await = None;
+                    *err_msg.lock().await = e.to_string();
+                }
+            }
+
+            is_loading_osu_page.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
+    }
+
     //加載更多osu封面
     fn load_more_osu_covers(&self, start: usize, end: usize) {
         if let Ok(osu_search_results_guard) = self.osu_search_results.try_lock() {
@@ -2441,7 +2838,7 @@ impl SearchApp {
                 if !self.show_side_menu {
                     ui.vertical(|ui| {
                         let is_image_loaded = if let Ok(textures) = self.cover_textures.try_read() {
-                            textures.get(&index).map_or(false, |opt| opt.is_some())
+                            textures.get(&index).is_some_and(|opt| opt.is_some())
                         } else {
                             false
                         };
@@ -2493,6 +2890,30 @@ impl SearchApp {
                         egui::RichText::new(format!("by {}", beatmapset.creator))
                             .font(egui::FontId::proportional(self.global_font_size * 0.7)),
                     );
+                    if let Some(best_match) = self.osu_best_matches.try_lock().ok().and_then(|guard| {
+                        guard
+                            .iter()
+                            .find(|best| best.beatmapset_id == beatmapset.id)
+                            .copied()
+                    }) {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "最佳匹配 {:.0}%",
+                                best_match.score.confidence * 100.0
+                            ))
+                            .font(egui::FontId::proportional(self.global_font_size * 0.7))
+                            .color(
+                                egui::Color32::from_hex("#FF66AA").unwrap_or(egui::Color32::WHITE),
+                            ),
+                        );
+                    }
+                    let download_status = self.get_download_status(beatmapset.id);
+                    if let DownloadStatus::Failed(reason) = &download_status {
+                        ui.colored_label(egui::Color32::RED, "下載失敗")
+                            .on_hover_text(reason);
+                    } else if download_status.is_downloading() {
+                        self.render_download_progress(ui, download_status);
+                    }
                 });
             });
         });
@@ -2502,6 +2923,34 @@ impl SearchApp {
         ui.separator();
     }
 
+    fn render_download_progress(&self, ui: &mut egui::Ui, status: DownloadStatus) {
+        let DownloadStatus::Downloading {
+            received,
+            total,
+            bytes_per_sec,
+        } = status
+        else {
+            return;
+        };
+
+        let mut text = match total {
+            Some(total) => format!("{} / {}", format_bytes(received), format_bytes(total)),
+            None => format_bytes(received),
+        };
+        if bytes_per_sec > 0 {
+            text.push_str(&format!(" · {}/s", format_bytes(bytes_per_sec)));
+        }
+        if let Some(eta) = status.eta() {
+            text.push_str(&format!(" · 剩餘 {} 秒", eta.as_secs()));
+        }
+
+        let progress_bar = egui::ProgressBar::new(status.progress().unwrap_or(0.0))
+            .desired_width(ui.available_width().min(250.0))
+            .text(egui::RichText::new(text).size(self.global_font_size * 0.6));
+        // 不知道總大小時用動畫表示仍在下載
+        ui.add(progress_bar.animate(total.is_none()));
+    }
+
     //顯示osu譜面集按鈕
     fn draw_osu_circular_buttons(
         &mut self,
@@ -2575,6 +3024,21 @@ impl SearchApp {
                     if response.clicked() {
                         self.handle_osu_button_click(i, beatmapset, ui.ctx().clone());
                     }
+                    // 有影片的譜面可以右鍵選擇下載版本
+                    if i == 2 && beatmapset.video && !self.is_beatmap_downloaded(beatmapset.id) {
+                        response.context_menu(|ui| {
+                            for variant in DownloadVariant::ALL {
+                                if ui.button(format!("下載{}版本", variant.label())).clicked() {
+                                    self.queue_beatmap_download(
+                                        beatmapset.id,
+                                        format!("{} - {}", beatmapset.artist, beatmapset.title),
+                                        variant,
+                                    );
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
                     if response.hovered() {
                         ui.painter().circle(
                             rect.center(),
@@ -2588,6 +3052,8 @@ impl SearchApp {
                             2 => {
                                 if self.is_beatmap_downloaded(beatmapset.id) {
                                     "刪除"
+                                } else if beatmapset.video {
+                                    "下載（右鍵選擇版本）"
                                 } else {
                                     "下載"
                                 }
@@ -2665,7 +3131,7 @@ impl SearchApp {
             2 => {
                 let icon_key = if self.is_beatmap_downloaded(beatmapset.id) {
                     "delete.png"
-                } else if self.get_download_status(beatmapset.id) == DownloadStatus::Downloading {
+                } else if self.get_download_status(beatmapset.id).is_downloading() {
                     "downloading.png"
                 } else {
                     "download.png"
@@ -2765,269 +3231,1012 @@ impl SearchApp {
     }
 
     fn handle_osu_open_click(&self, beatmapset: &Beatmapset) {
+        // 有安裝 osu! 時直接在遊戲中打開，否則開網頁
+        if osu_client::open_beatmapset(beatmapset.id) {
+            return;
+        }
         let url = format!("https://osu.ppy.sh/beatmapsets/{}", beatmapset.id);
         if let Err(e) = open::that(url) {
             error!("無法在osu!中打開譜面: {:?}", e);
         }
     }
 
-    fn handle_osu_download_click(&mut self, beatmapset: &Beatmapset, ctx: egui::Context) {
-        let beatmapset_id = beatmapset.id;
-        if self.is_beatmap_downloaded(beatmapset_id) {
-            // 如果已下載,則刪除
-            match delete_beatmap(&self.download_directory, beatmapset_id) {
-                Ok(_) => {
-                    info!("成功刪除譜面 {}", beatmapset_id);
-                    self.beatmapset_download_statuses
-                        .lock()
-                        .unwrap()
-                        .insert(beatmapset_id, DownloadStatus::NotStarted);
-                }
-                Err(e) => {
-                    error!("無法刪除譜面 {}: {:?}", beatmapset_id, e);
+    // 定期檢查 osu! 是否正在執行，設定頁顯示用
+    fn start_osu_client_monitor(&self) {
+        let running_osu_client = self.running_osu_client.clone();
+        let need_repaint = self.need_repaint.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match tokio::task::spawn_blocking(osu_client::running_client).await {
+                    Ok(running) => {
+                        let mut current = running_osu_client.lock().unwrap();
+                        if *current != running {
+                            info!("osu! 執行狀態變更: {:?}", running);
+                            *current = running;
+                            need_repaint.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    Err(e) => error!("檢查 osu! 執行狀態失敗: {:?}", e),
                 }
+                tokio::time::sleep(Duration::from_secs(5)).await;
             }
-        } else {
-            // 如果未下載,則開始下載
-            info!("將譜面 {} 加入下載隊列", beatmapset_id);
-            let current_downloads = self.current_downloads.load(Ordering::SeqCst);
-            if current_downloads < 3 {
-                self.beatmapset_download_statuses
-                    .lock()
-                    .unwrap()
-                    .insert(beatmapset_id, DownloadStatus::Downloading);
-            } else {
-                self.beatmapset_download_statuses
-                    .lock()
-                    .unwrap()
-                    .insert(beatmapset_id, DownloadStatus::Waiting);
-            }
-            if let Err(e) = self.download_queue_sender.try_send(beatmapset_id) {
-                error!("無法將譜面加入下載隊列: {:?}", e);
-                self.beatmapset_download_statuses
-                    .lock()
-                    .unwrap()
-                    .insert(beatmapset_id, DownloadStatus::NotStarted);
+        });
+    }
+
+    fn stable_directory(&self) -> Option<PathBuf> {
+        self.osu_client_settings
+            .lock()
+            .unwrap()
+            .stable_directory(&self.download_directory)
+    }
+
+    // 在背景讀取 osu!stable 的收藏，找不到 osu!stable 時不顯示
+    fn reload_osu_collections(&self) {
+        let Some(stable_directory) = self.stable_directory() else {
+            return;
+        };
+        let osu_collections = self.osu_collections.clone();
+        let need_repaint = self.need_repaint.clone();
+
+        tokio::task::spawn_blocking(move || {
+            match osu_db::read_collection_index(&stable_directory) {
+                Ok(index) => {
+                    info!("已讀取 osu! 收藏，{} 個譜面集在收藏中", index.len());
+                    *osu_collections.lock().unwrap() = index;
+                    need_repaint.store(true, Ordering::SeqCst);
+                }
+                Err(e) => error!("無法讀取 osu! 收藏: {}", e),
             }
-        }
-        ctx.request_repaint();
+        });
     }
 
-    fn is_beatmap_downloaded(&self, beatmapset_id: i32) -> bool {
-        osu::is_beatmap_downloaded(&self.download_directory, beatmapset_id)
+    // 把譜面集加入 osu!stable 的收藏，只有已匯入（在 osu!.db 中）的譜面能加入
+    fn create_osu_collection(&self, name: String, beatmapset_ids: Vec<i32>) {
+        let Some(stable_directory) = self.stable_directory() else {
+            *self.collection_message.lock().unwrap() =
+                Some("找不到 osu!stable 資料夾，請在 osu! 客戶端設定中選擇".to_string());
+            return;
+        };
+        if self.is_writing_collection.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let osu_collections = self.osu_collections.clone();
+        let is_writing_collection = self.is_writing_collection.clone();
+        let collection_message = self.collection_message.clone();
+        let need_repaint = self.need_repaint.clone();
+
+        tokio::task::spawn_blocking(move || {
+            // osu! 關閉時會用記憶體中的收藏覆寫 collection.db
+            let message = if osu_client::running_client() == Some(OsuClientKind::Stable) {
+                "osu!stable 執行中，請先關閉遊戲再建立收藏".to_string()
+            } else {
+                match osu_db::add_beatmapsets_to_collection(
+                    &stable_directory,
+                    &name,
+                    &beatmapset_ids,
+                ) {
+                    Ok(update) => {
+                        info!("已將 {} 個難度加入收藏「{}」", update.added, name);
+                        match osu_db::read_collection_index(&stable_directory) {
+                            Ok(index) => *osu_collections.lock().unwrap() = index,
+                            Err(e) => error!("無法讀取 osu! 收藏: {}", e),
+                        }
+                        let mut message =
+                            format!("已加入 {} 個難度到收藏「{}」", update.added, name);
+                        if !update.missing.is_empty() {
+                            message.push_str(&format!(
+                                "，{} 個譜面集尚未匯入 osu!，匯入後再建立一次即可",
+                                update.missing.len()
+                            ));
+                        }
+                        message
+                    }
+                    Err(e) => {
+                        error!("寫入 osu! 收藏失敗: {}", e);
+                        format!("寫入 osu! 收藏失敗：{}", e)
+                    }
+                }
+            };
+            *collection_message.lock().unwrap() = Some(message);
+            is_writing_collection.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
     }
 
-    fn get_download_status(&self, beatmapset_id: i32) -> DownloadStatus {
-        if osu::is_beatmap_downloaded(&self.download_directory, beatmapset_id) {
-            DownloadStatus::Completed
-        } else {
-            self.beatmapset_download_statuses
+    fn import_beatmapset_to_osu(&self, beatmapset_id: i32) {
+        let settings = self.osu_client_settings.lock().unwrap().clone();
+        match osu_client::import_downloaded_beatmapset(
+            &settings,
+            &self.download_directory,
+            beatmapset_id,
+        ) {
+            Ok(()) if settings.client == OsuClientKind::Lazer => self
+                .local_library
                 .lock()
                 .unwrap()
-                .get(&beatmapset_id)
-                .cloned()
-                .unwrap_or(DownloadStatus::NotStarted)
+                .record_lazer_import(beatmapset_id),
+            Ok(()) => {}
+            Err(e) => error!("無法將譜面 {} 匯入 osu!: {}", beatmapset_id, e),
         }
     }
 
-    fn start_download_processor(&self) {
-        let download_queue_receiver = self.download_queue_receiver.clone();
-        let download_directory = self.download_directory.clone();
-        let status_sender = self.status_sender.clone();
-        let semaphore = self.download_semaphore.clone();
-        let current_downloads = self.current_downloads.clone();
-        let beatmapset_download_statuses = self.beatmapset_download_statuses.clone();
-        let osu_search_results = self.osu_search_results.clone();
+    fn render_osu_client_settings(&mut self, ui: &mut egui::Ui) {
+        let mut settings = self.osu_client_settings.lock().unwrap().clone();
 
-        tokio::spawn(async move {
-            let mut receiver = match download_queue_receiver.lock().unwrap().take() {
-                Some(r) => r,
-                None => {
-                    error!("下載隊列接收器已被關閉");
-                    return;
-                }
-            };
+        ui.horizontal(|ui| {
+            ui.label("客戶端:");
+            for kind in OsuClientKind::ALL {
+                ui.radio_value(&mut settings.client, kind, kind.label());
+            }
+        });
 
-            while let Some(beatmapset_id) = receiver.recv().await {
-                let permit = match semaphore.clone().acquire_owned().await {
-                    Ok(p) => p,
-                    Err(e) => {
-                        error!("無法獲取下載許可: {:?}", e);
-                        continue;
-                    }
-                };
+        ui.horizontal(|ui| {
+            ui.label("執行檔:");
+            match settings.executable() {
+                Some(path) => ui.label(path.display().to_string()),
+                None => ui.weak("使用系統檔案關聯"),
+            };
+        });
+        ui.horizontal(|ui| {
+            if ui.button("選擇執行檔").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    settings.executable_path = Some(path);
+                }
+            }
+            if settings.executable_path.is_some() && ui.button("改回自動偵測").clicked() {
+                settings.executable_path = None;
+            }
+        });
 
-                let download_directory = download_directory.clone();
-                let status_sender = status_sender.clone();
-                let current_downloads = current_downloads.clone();
-                let beatmapset_download_statuses = beatmapset_download_statuses.clone();
-                let osu_search_results = osu_search_results.clone();
+        if settings.client == OsuClientKind::Stable {
+            ui.horizontal(|ui| {
+                ui.label("osu! 資料夾:");
+                match settings.stable_directory(&self.download_directory) {
+                    Some(path) => ui.label(path.display().to_string()),
+                    None => ui.colored_label(egui::Color32::YELLOW, "找不到"),
+                };
+                if ui.button("選擇").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                        if path.join(osu_db::OSU_DB_FILE_NAME).is_file() {
+                            settings.stable_directory = Some(path);
+                        } else {
+                            error!("{:?} 中沒有 osu!.db", path);
+                        }
+                    }
+                }
+            })
+            .response
+            .on_hover_text("讀取收藏用，osu!.db 與 collection.db 所在的資料夾");
+        }
 
-                current_downloads.fetch_add(1, Ordering::SeqCst);
-                if let Err(e) = status_sender
-                    .send((beatmapset_id, DownloadStatus::Downloading))
-                    .await
+        if settings.client == OsuClientKind::Lazer {
+            ui.horizontal(|ui| {
+                ui.label("lazer 資料夾:");
+                match settings.lazer_data_directory() {
+                    Some(path) => ui.label(path.display().to_string()),
+                    None => ui.colored_label(egui::Color32::YELLOW, "找不到"),
+                };
+                if ui.button("選擇").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                        if lazer::is_lazer_data_directory(&path) {
+                            settings.lazer_data_directory = Some(path);
+                        } else {
+                            error!("{:?} 不是 osu!lazer 的資料夾", path);
+                        }
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("譜面集清單:");
+                match &settings.lazer_exported_list {
+                    Some(path) => ui.label(path.display().to_string()),
+                    None => ui.weak("未使用"),
+                };
+                if ui.button("選擇").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("文字檔", &["txt", "csv"])
+                        .pick_file()
+                    {
+                        settings.lazer_exported_list = Some(path);
+                    }
+                }
+                if settings.lazer_exported_list.is_some() && ui.button("清除").clicked() {
+                    settings.lazer_exported_list = None;
+                }
+            })
+            .response
+            .on_hover_text("每行一個譜面集 ID 或網址，讀不到 lazer 資料夾時使用");
+            ui.horizontal(|ui| {
+                if let Ok(library) = self.local_library.try_lock() {
+                    ui.label(format!("osu!lazer 中有 {} 個譜面集", library.lazer_len()));
+                }
+                let is_refreshing = self.is_refreshing_library.load(Ordering::SeqCst);
+                if ui
+                    .add_enabled(!is_refreshing, egui::Button::new("重新掃描"))
+                    .on_hover_text("在 lazer 中新增或刪除譜面後，重新讀取 lazer 的檔案庫")
+                    .clicked()
                 {
-                    error!("無法發送下載狀態: {:?}", e);
+                    self.refresh_local_library(Vec::new(), true);
                 }
+            });
+            // lazer 沒有會自動匯入的資料夾，只能下載後交給 lazer 匯入
+            if !settings.auto_import {
+                ui.weak("lazer 不會自動讀取下載資料夾，建議開啟自動匯入");
+            }
+        }
 
-                tokio::spawn(async move {
-                    let status_sender_clone = status_sender.clone();
-                    let download_result = tokio::time::timeout(
-                        std::time::Duration::from_secs(300),
-                        osu::download_beatmap(beatmapset_id, &download_directory, {
-                            let status_sender = status_sender.clone();
-                            move |status| {
-                                let beatmapset_id = beatmapset_id;
-                                let status_sender = status_sender.clone();
-                                tokio::spawn(async move {
-                                    if let Err(e) =
-                                        status_sender.send((beatmapset_id, status)).await
-                                    {
-                                        error!("無法發送下載狀態更新: {:?}", e);
-                                    }
-                                });
-                            }
-                        }),
-                    )
-                    .await;
+        ui.checkbox(&mut settings.auto_import, "下載完成後自動匯入 osu!")
+            .on_hover_text("匯入後 osu! 會把 .osz 從下載資料夾移走");
 
-                    match download_result {
-                        Ok(Ok(_)) => {
-                            info!("圖譜 {} 下載成功", beatmapset_id);
+        match *self.running_osu_client.lock().unwrap() {
+            Some(kind) => ui.label(format!("{} 執行中", kind.label())),
+            None => ui.weak("osu! 未執行"),
+        };
 
-                            {
-                                let search_results = osu_search_results.lock().await;
-                                let results_count_before = search_results.len();
+        let mut current = self.osu_client_settings.lock().unwrap();
+        if *current != settings {
+            if let Err(e) = settings.save() {
+                error!("保存 osu! 客戶端設定失敗: {:?}", e);
+            }
+            // 譜面來源改變時重新整理本機譜面
+            let library_changed = current.client != settings.client
+                || current.lazer_data_directory != settings.lazer_data_directory
+                || current.lazer_exported_list != settings.lazer_exported_list;
+            let stable_changed = current.stable_directory != settings.stable_directory;
+            *current = settings;
+            drop(current);
+            if library_changed {
+                self.refresh_local_library(Vec::new(), true);
+            }
+            if stable_changed {
+                self.reload_osu_collections();
+            }
+        }
+    }
 
-                                beatmapset_download_statuses
-                                    .lock()
-                                    .unwrap()
-                                    .insert(beatmapset_id, DownloadStatus::Completed);
+    fn handle_osu_download_click(&mut self, beatmapset: &Beatmapset, ctx: egui::Context) {
+        let beatmapset_id = beatmapset.id;
+        if self.is_beatmap_downloaded(beatmapset_id) {
+            if self
+                .local_library
+                .lock()
+                .unwrap()
+                .is_lazer_only(beatmapset_id)
+            {
+                info!("譜面 {} 已在 osu!lazer 中，請在遊戲內刪除", beatmapset_id);
+                return;
+            }
+            // 如果已下載,先列出會被刪除的項目讓使用者確認
+            match delete_beatmap(&self.download_directory, beatmapset_id, true) {
+                Ok(paths) => self.pending_beatmap_delete = Some((beatmapset_id, paths)),
+                Err(e) => {
+                    error!("無法刪除譜面 {}: {:?}", beatmapset_id, e);
+                }
+            }
+        } else {
+            // 如果未下載,則開始下載
+            let variant = self.default_download_variant(beatmapset);
+            self.queue_beatmap_download(
+                beatmapset_id,
+                format!("{} - {}", beatmapset.artist, beatmapset.title),
+                variant,
+            );
+        }
+        ctx.request_repaint();
+    }
 
-                                let results_count_after = search_results.len();
+    // 沒有影片的譜面集不論設定都下載完整版本，可用的鏡像站較多
+    fn default_download_variant(&self, beatmapset: &Beatmapset) -> DownloadVariant {
+        if beatmapset.video {
+            self.download_settings.lock().unwrap().default_variant
+        } else {
+            DownloadVariant::Full
+        }
+    }
 
-                                if results_count_before != results_count_after {
-                                    error!(
-                                        "警告：下載完成後搜索結果數量發生變化。之前：{}，之後：{}",
-                                        results_count_before, results_count_after
-                                    );
-                                } else {
-                                    info!("搜索結果數量未變化，保持為 {}", results_count_after);
-                                }
-                            }
+    fn confirm_beatmap_delete(&mut self, beatmapset_id: i32) {
+        match delete_beatmap(&self.download_directory, beatmapset_id, false) {
+            Ok(paths) => {
+                info!("成功刪除譜面 {}", beatmapset_id);
+                self.beatmapset_download_statuses
+                    .lock()
+                    .unwrap()
+                    .insert(beatmapset_id, DownloadStatus::NotStarted);
+                let mut library = self.local_library.lock().unwrap();
+                for path in &paths {
+                    if let Some(file_name) = path.file_name() {
+                        library.remove_file(&file_name.to_string_lossy());
+                    }
+                }
+                if let Err(e) = library.save() {
+                    error!("保存譜面索引失敗: {:?}", e);
+                }
+            }
+            Err(e) => {
+                error!("無法刪除譜面 {}: {:?}", beatmapset_id, e);
+            }
+        }
+    }
 
-                            if let Err(e) = status_sender_clone
-                                .send((beatmapset_id, DownloadStatus::Completed))
-                                .await
-                            {
-                                error!("無法發送下載完成狀態: {:?}", e);
-                            }
-                        }
-                        Ok(Err(e)) => {
-                            error!("圖譜 {} 下載失敗: {:?}", beatmapset_id, e);
-                            beatmapset_download_statuses
-                                .lock()
-                                .unwrap()
-                                .insert(beatmapset_id, DownloadStatus::NotStarted);
-                            if let Err(e) = status_sender_clone
-                                .send((beatmapset_id, DownloadStatus::NotStarted))
-                                .await
-                            {
-                                error!("無法發送下載失敗狀態: {:?}", e);
-                            }
-                        }
-                        Err(_) => {
-                            error!("圖譜 {} 下載超時", beatmapset_id);
-                            beatmapset_download_statuses
-                                .lock()
-                                .unwrap()
-                                .insert(beatmapset_id, DownloadStatus::NotStarted);
-                            if let Err(e) = status_sender_clone
-                                .send((beatmapset_id, DownloadStatus::NotStarted))
-                                .await
-                            {
-                                error!("無法發送下載超時狀態: {:?}", e);
-                            }
+    fn render_download_settings(&mut self, ui: &mut egui::Ui) {
+        let mut settings = self.download_settings.lock().unwrap().clone();
+
+        egui::Grid::new("download_settings_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("同時下載數:");
+                ui.add(
+                    egui::DragValue::new(&mut settings.max_concurrent)
+                        .clamp_range(1..=MAX_CONCURRENT_DOWNLOADS),
+                );
+                ui.end_row();
+
+                ui.label("預設下載版本:");
+                egui::ComboBox::from_id_source("default_download_variant")
+                    .selected_text(settings.default_variant.label())
+                    .show_ui(ui, |ui| {
+                        for variant in DownloadVariant::ALL {
+                            ui.selectable_value(
+                                &mut settings.default_variant,
+                                variant,
+                                variant.label(),
+                            );
                         }
-                    }
+                    });
+                ui.end_row();
 
-                    current_downloads.fetch_sub(1, Ordering::SeqCst);
-                    drop(permit);
-                });
+                ui.label("下載逾時:");
+                ui.add(
+                    egui::DragValue::new(&mut settings.timeout_secs)
+                        .clamp_range(30..=3600)
+                        .suffix(" 秒"),
+                );
+                ui.end_row();
+
+                let mut limit_bandwidth = settings.bandwidth_limit_kbps.is_some();
+                ui.checkbox(&mut limit_bandwidth, "限制頻寬:");
+                match (limit_bandwidth, settings.bandwidth_limit_kbps) {
+                    (true, None) => settings.bandwidth_limit_kbps = Some(1024),
+                    (false, Some(_)) => settings.bandwidth_limit_kbps = None,
+                    _ => {}
+                }
+                if let Some(limit) = settings.bandwidth_limit_kbps.as_mut() {
+                    ui.add(
+                        egui::DragValue::new(limit)
+                            .clamp_range(64..=102400)
+                            .speed(16)
+                            .suffix(" KB/s"),
+                    );
+                }
+                ui.end_row();
+
+                let mut limit_hours = settings.active_hours.is_some();
+                ui.checkbox(&mut limit_hours, "只在這些時段下載:");
+                match (limit_hours, settings.active_hours) {
+                    (true, None) => settings.active_hours = Some((0, 8)),
+                    (false, Some(_)) => settings.active_hours = None,
+                    _ => {}
+                }
+                if let Some((start, end)) = settings.active_hours.as_mut() {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(start)
+                                .clamp_range(0..=23)
+                                .suffix(":00"),
+                        );
+                        ui.label("到");
+                        ui.add(egui::DragValue::new(end).clamp_range(0..=23).suffix(":00"));
+                    });
+                }
+                ui.end_row();
+            });
+
+        if let Some((start, end)) = settings.active_hours {
+            if start == end {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    "開始與結束時間相同，不會開始任何下載",
+                );
+            } else if !settings.is_within_active_hours(chrono::Local::now()) {
+                ui.weak("目前不在下載時段，佇列中的項目會等到時段開始");
             }
-        });
+        }
+
+        let mut current = self.download_settings.lock().unwrap();
+        if *current != settings {
+            self.bandwidth_limiter
+                .set_limit(settings.bandwidth_limit_bytes());
+            if let Err(e) = settings.save() {
+                error!("保存下載設定失敗: {:?}", e);
+            }
+            *current = settings;
+            drop(current);
+            // 同時下載數或時段改變後可能可以開始新的下載
+            self.download_queue_notify.notify_one();
+        }
     }
 
-    //顯示osu譜面集詳情
-    fn display_selected_beatmapset(&mut self, ui: &mut egui::Ui, beatmapset: &Beatmapset) {
-        let beatmap_info = print_beatmap_info_gui(beatmapset);
+    fn render_mirror_health(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("mirror_health_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("鏡像站");
+                ui.strong("成功/失敗");
+                ui.strong("平均耗時");
+                ui.strong("狀態");
+                ui.end_row();
+
+                for (name, available, health) in self.beatmap_mirrors.health_snapshot() {
+                    ui.label(name);
+                    ui.label(format!("{}/{}", health.successes, health.failures));
+                    ui.label(
+                        health
+                            .average_latency()
+                            .map(|latency| format!("{:.1}s", latency.as_secs_f32()))
+                            .unwrap_or_else(|| "-".to_string()),
+                    );
+                    let status = if !available {
+                        ui.weak("未登入")
+                    } else if health.is_cooling_down() {
+                        ui.colored_label(egui::Color32::RED, "暫停使用")
+                    } else if health.consecutive_failures > 0 {
+                        ui.colored_label(egui::Color32::YELLOW, "不穩定")
+                    } else {
+                        ui.label("正常")
+                    };
+                    if let Some(error) = &health.last_error {
+                        status.on_hover_text(error);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
 
-        ui.heading(
-            egui::RichText::new(format!("{} - {}", beatmap_info.title, beatmap_info.artist))
-                .font(egui::FontId::proportional(self.global_font_size * 1.1)),
-        );
-        ui.label(
-            egui::RichText::new(format!("by {}", beatmap_info.creator))
-                .font(egui::FontId::proportional(self.global_font_size * 0.9)),
-        );
-        ui.add_space(10.0);
+    fn render_delete_confirm_window(&mut self, ctx: &egui::Context) {
+        let Some((beatmapset_id, paths)) = self.pending_beatmap_delete.clone() else {
+            return;
+        };
 
-        for beatmap_info in beatmap_info.beatmaps {
-            ui.add_space(10.0);
-            ui.label(
-                egui::RichText::new(beatmap_info)
-                    .font(egui::FontId::proportional(self.global_font_size * 1.0)),
-            );
-            ui.add_space(10.0);
-            ui.separator();
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        egui::Window::new("確認刪除譜面")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("將會刪除譜面集 {} 的以下項目：", beatmapset_id));
+                ui.add_space(5.0);
+                for path in &paths {
+                    let name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+                    let kind = if path.is_dir() { "📁" } else { "📦" };
+                    ui.label(format!("{} {}", kind, name))
+                        .on_hover_text(path.to_string_lossy());
+                }
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button("確定刪除").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+        if confirmed {
+            self.confirm_beatmap_delete(beatmapset_id);
         }
-        if ui
-            .add_sized(
-                [100.0, 40.0],
-                egui::Button::new(
-                    egui::RichText::new("Back")
-                        .font(egui::FontId::proportional(self.global_font_size * 1.0)),
-                ),
-            )
-            .clicked()
-        {
-            self.selected_beatmapset = None;
+        if confirmed || cancelled || !open {
+            self.pending_beatmap_delete = None;
         }
     }
 
-    //清除封面紋理
-    fn clear_cover_textures(&self) {
-        if let Ok(mut textures) = self.cover_textures.try_write() {
-            textures.clear();
+    fn queue_beatmap_download(
+        &mut self,
+        beatmapset_id: i32,
+        title: String,
+        variant: DownloadVariant,
+    ) {
+        let mut queue = self.download_queue.lock().unwrap();
+        if !queue.enqueue(beatmapset_id, title, variant) {
+            info!("譜面 {} 已在下載佇列中", beatmapset_id);
+            return;
         }
-    }
+        info!(
+            "將譜面 {} 加入下載隊列（{}版本）",
+            beatmapset_id,
+            variant.label()
+        );
+        if let Err(e) = queue.save() {
+            error!("保存下載佇列失敗: {:?}", e);
+        }
+        drop(queue);
 
-    //加載默認頭像
-    fn load_default_avatar(&mut self) {
-        let default_avatar_bytes = include_bytes!("assets/login.png");
-        let default_avatar_image = image::load_from_memory(default_avatar_bytes).unwrap();
-        let default_avatar_size = [
-            default_avatar_image.width() as _,
-            default_avatar_image.height() as _,
-        ];
-        let default_avatar_pixels = default_avatar_image.to_rgba8();
-        self.default_avatar_texture = Some(self.ctx.load_texture(
-            "default_avatar",
-            egui::ColorImage::from_rgba_unmultiplied(
-                default_avatar_size,
-                default_avatar_pixels.as_flat_samples().as_slice(),
-            ),
-            egui::TextureOptions::default(),
-        ));
+        self.beatmapset_download_statuses
+            .lock()
+            .unwrap()
+            .insert(beatmapset_id, DownloadStatus::Waiting);
+        self.download_queue_notify.notify_one();
     }
-    //渲染頂部面板
-    fn render_top_panel(&mut self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
-                // 渲染側邊選單按鈕
-                let (rect, response) = ui.allocate_exact_size(
-                    egui::vec2(BUTTON_SIZE, BUTTON_SIZE),
+
+    // 下載佇列視窗中的操作
+    fn apply_queue_action(&mut self, beatmapset_id: i32, action: QueueAction) {
+        let mut queue = self.download_queue.lock().unwrap();
+        let status = match action {
+            QueueAction::Pause => queue
+                .pause(beatmapset_id)
+                .then_some(DownloadStatus::Waiting),
+            QueueAction::Resume => queue
+                .resume(beatmapset_id)
+                .then_some(DownloadStatus::Waiting),
+            QueueAction::Retry => queue
+                .retry(beatmapset_id)
+                .then_some(DownloadStatus::Waiting),
+            QueueAction::Cancel => queue
+                .cancel(beatmapset_id)
+                .filter(|item| item.state != QueueItemState::Completed)
+                .map(|_| DownloadStatus::NotStarted),
+            QueueAction::MoveUp => {
+                queue.move_by(beatmapset_id, -1);
+                None
+            }
+            QueueAction::MoveDown => {
+                queue.move_by(beatmapset_id, 1);
+                None
+            }
+        };
+        if let Err(e) = queue.save() {
+            error!("保存下載佇列失敗: {:?}", e);
+        }
+        drop(queue);
+
+        // 暫停或取消下載中的項目時中止下載，.part 檔保留以便之後續傳
+        if matches!(action, QueueAction::Pause | QueueAction::Cancel) {
+            if let Some(handle) = self.active_downloads.lock().unwrap().remove(&beatmapset_id) {
+                handle.abort();
+                info!("已中止譜面 {} 的下載", beatmapset_id);
+            }
+        }
+        if let Some(status) = status {
+            self.beatmapset_download_statuses
+                .lock()
+                .unwrap()
+                .insert(beatmapset_id, status);
+        }
+        self.download_queue_notify.notify_one();
+    }
+
+    fn render_download_queue_window(&mut self, ctx: &egui::Context) {
+        if !self.show_download_queue {
+            return;
+        }
+
+        let items = self.download_queue.lock().unwrap().items().to_vec();
+        let mut open = true;
+        let mut actions = Vec::new();
+        let mut clear_completed = false;
+
+        egui::Window::new("下載佇列")
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(egui::vec2(600.0, 400.0))
+            .show(ctx, |ui| {
+                let pending = items.iter().filter(|item| item.state.is_pending()).count();
+                ui.horizontal(|ui| {
+                    ui.label(format!("共 {} 個項目，{} 個待下載", items.len(), pending));
+                    if ui.button("清除已完成").clicked() {
+                        clear_completed = true;
+                    }
+                });
+                ui.separator();
+
+                if items.is_empty() {
+                    ui.label("下載佇列是空的");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("download_queue_grid")
+                        .num_columns(5)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("譜面");
+                            ui.strong("版本");
+                            ui.strong("狀態");
+                            ui.strong("重試");
+                            ui.strong("操作");
+                            ui.end_row();
+
+                            for (position, item) in items.iter().enumerate() {
+                                let id = item.beatmapset_id;
+                                ui.label(&item.title).on_hover_text(format!("ID: {}", id));
+                                ui.label(item.variant.label());
+
+                                let status = self.get_download_status(id);
+                                if item.state == QueueItemState::Downloading
+                                    && status.is_downloading()
+                                {
+                                    self.render_download_progress(ui, status);
+                                } else {
+                                    let label = match item.state {
+                                        QueueItemState::Failed => {
+                                            ui.colored_label(egui::Color32::RED, item.state.label())
+                                        }
+                                        _ => ui.label(item.state.label()),
+                                    };
+                                    if let Some(error) = &item.last_error {
+                                        label.on_hover_text(error);
+                                    }
+                                }
+
+                                ui.label(item.retries.to_string());
+
+                                ui.horizontal(|ui| {
+                                    if ui
+                                        .add_enabled(position > 0, egui::Button::new("⬆"))
+                                        .on_hover_text("往前移")
+                                        .clicked()
+                                    {
+                                        actions.push((id, QueueAction::MoveUp));
+                                    }
+                                    if ui
+                                        .add_enabled(
+                                            position + 1 < items.len(),
+                                            egui::Button::new("⬇"),
+                                        )
+                                        .on_hover_text("往後移")
+                                        .clicked()
+                                    {
+                                        actions.push((id, QueueAction::MoveDown));
+                                    }
+                                    match item.state {
+                                        QueueItemState::Queued | QueueItemState::Downloading => {
+                                            if ui.button("⏸").on_hover_text("暫停").clicked() {
+                                                actions.push((id, QueueAction::Pause));
+                                            }
+                                        }
+                                        QueueItemState::Paused => {
+                                            if ui.button("▶").on_hover_text("繼續").clicked() {
+                                                actions.push((id, QueueAction::Resume));
+                                            }
+                                        }
+                                        QueueItemState::Failed => {
+                                            if ui.button("🔁").on_hover_text("重試").clicked() {
+                                                actions.push((id, QueueAction::Retry));
+                                            }
+                                        }
+                                        QueueItemState::Completed => {}
+                                    }
+                                    let cancel_text = if item.state == QueueItemState::Completed {
+                                        "移除"
+                                    } else {
+                                        "取消"
+                                    };
+                                    if ui.button("✖").on_hover_text(cancel_text).clicked() {
+                                        actions.push((id, QueueAction::Cancel));
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        for (beatmapset_id, action) in actions {
+            self.apply_queue_action(beatmapset_id, action);
+        }
+        if clear_completed {
+            let mut queue = self.download_queue.lock().unwrap();
+            queue.clear_completed();
+            if let Err(e) = queue.save() {
+                error!("保存下載佇列失敗: {:?}", e);
+            }
+        }
+        if !open {
+            self.show_download_queue = false;
+        }
+    }
+
+    // 下載狀態由譜面索引與資料夾監看維護，不必每次掃描資料夾
+    fn is_beatmap_downloaded(&self, beatmapset_id: i32) -> bool {
+        self.get_download_status(beatmapset_id) == DownloadStatus::Completed
+    }
+
+    fn get_download_status(&self, beatmapset_id: i32) -> DownloadStatus {
+        self.beatmapset_download_statuses
+            .lock()
+            .unwrap()
+            .get(&beatmapset_id)
+            .cloned()
+            .unwrap_or(DownloadStatus::NotStarted)
+    }
+
+    // 依序從持久化的下載佇列取出項目下載，同時下載數、時段與逾時依下載設定
+    fn start_download_processor(&self) {
+        let download_queue = self.download_queue.clone();
+        let download_queue_notify = self.download_queue_notify.clone();
+        let active_downloads = self.active_downloads.clone();
+        let download_directory = self.download_directory.clone();
+        let status_sender = self.status_sender.clone();
+        let download_settings = self.download_settings.clone();
+        let bandwidth_limiter = self.bandwidth_limiter.clone();
+        let osu_client_settings = self.osu_client_settings.clone();
+        let local_library = self.local_library.clone();
+        let beatmapset_download_statuses = self.beatmapset_download_statuses.clone();
+        let osu_search_results = self.osu_search_results.clone();
+        let beatmap_mirrors = self.beatmap_mirrors.clone();
+        let client = self.client.clone();
+        let debug_mode = self.debug_mode;
+
+        tokio::spawn(async move {
+            loop {
+                let settings = download_settings.lock().unwrap().clone();
+                let can_start = active_downloads.lock().unwrap().len() < settings.max_concurrent
+                    && settings.is_within_active_hours(chrono::Local::now());
+
+                let next = if !can_start {
+                    None
+                } else {
+                    let mut queue = download_queue.lock().unwrap();
+                    let next = queue.start_next();
+                    if next.is_some() {
+                        if let Err(e) = queue.save() {
+                            error!("保存下載佇列失敗: {:?}", e);
+                        }
+                    }
+                    next
+                };
+                let Some((beatmapset_id, variant)) = next else {
+                    // 佇列空了、下載數已滿或不在下載時段，等有變化時再檢查；
+                    // 時段是依時間變化的，所以也定期醒來
+                    tokio::select! {
+                        _ = download_queue_notify.notified() => {}
+                        _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                    }
+                    continue;
+                };
+
+                let download_directory = download_directory.clone();
+                let status_sender = status_sender.clone();
+                let beatmapset_download_statuses = beatmapset_download_statuses.clone();
+                let osu_search_results = osu_search_results.clone();
+                let beatmap_mirrors = beatmap_mirrors.clone();
+                let download_queue = download_queue.clone();
+                let download_queue_notify = download_queue_notify.clone();
+                let task_active_downloads = active_downloads.clone();
+                let bandwidth_limiter = bandwidth_limiter.clone();
+                let osu_client_settings = osu_client_settings.clone();
+                let local_library = local_library.clone();
+                let client = client.clone();
+
+                beatmapset_download_statuses
+                    .lock()
+                    .unwrap()
+                    .insert(beatmapset_id, DownloadStatus::started());
+                if let Err(e) = status_sender
+                    .send((beatmapset_id, DownloadStatus::started()))
+                    .await
+                {
+                    error!("無法發送下載狀態: {:?}", e);
+                }
+
+                // 先鎖住再啟動，下載任務結束時的移除才不會早於加入
+                let mut active = active_downloads.lock().unwrap();
+                let handle = tokio::spawn(async move {
+                    // 官方下載的 token 過期時先刷新，官方鏡像站才不會用過期的 token
+                    match read_config(debug_mode) {
+                        Ok(config) => {
+                            let http = client.lock().await.clone();
+                            beatmap_mirrors.refresh_osu_login(&http, &config).await;
+                        }
+                        Err(e) => error!("讀取配置文件時出錯: {}", e),
+                    }
+                    let status_sender_clone = status_sender.clone();
+                    let download_result = tokio::time::timeout(
+                        settings.timeout(),
+                        osu::download_beatmap(
+                            beatmapset_id,
+                            &download_directory,
+                            variant,
+                            &beatmap_mirrors,
+                            &bandwidth_limiter,
+                            {
+                                let status_sender = status_sender.clone();
+                                let beatmapset_download_statuses =
+                                    beatmapset_download_statuses.clone();
+                                move |status| {
+                                    // 下載進度直接寫入狀態表，結果列才能顯示進度條
+                                    beatmapset_download_statuses
+                                        .lock()
+                                        .unwrap()
+                                        .insert(beatmapset_id, status.clone());
+                                    let beatmapset_id = beatmapset_id;
+                                    let status_sender = status_sender.clone();
+                                    tokio::spawn(async move {
+                                        if let Err(e) =
+                                            status_sender.send((beatmapset_id, status)).await
+                                        {
+                                            error!("無法發送下載狀態更新: {:?}", e);
+                                        }
+                                    });
+                                }
+                            },
+                        ),
+                    )
+                    .await;
+
+                    match download_result {
+                        Ok(Ok(downloaded_variant)) => {
+                            info!("圖譜 {} 下載成功", beatmapset_id);
+
+                            {
+                                let search_results = osu_search_results.lock().await;
+                                let results_count_before = search_results.len();
+
+                                beatmapset_download_statuses
+                                    .lock()
+                                    .unwrap()
+                                    .insert(beatmapset_id, DownloadStatus::Completed);
+
+                                let results_count_after = search_results.len();
+
+                                if results_count_before != results_count_after {
+                                    error!(
+                                        "警告：下載完成後搜索結果數量發生變化。之前：{}，之後：{}",
+                                        results_count_before, results_count_after
+                                    );
+                                } else {
+                                    info!("搜索結果數量未變化，保持為 {}", results_count_after);
+                                }
+                            }
+
+                            download_queue
+                                .lock()
+                                .unwrap()
+                                .mark_completed(beatmapset_id, downloaded_variant);
+                            let client_settings = osu_client_settings.lock().unwrap().clone();
+                            if client_settings.auto_import {
+                                match osu_client::import_downloaded_beatmapset(
+                                    &client_settings,
+                                    &download_directory,
+                                    beatmapset_id,
+                                ) {
+                                    Ok(()) if client_settings.client == OsuClientKind::Lazer => {
+                                        local_library
+                                            .lock()
+                                            .unwrap()
+                                            .record_lazer_import(beatmapset_id)
+                                    }
+                                    Ok(()) => {}
+                                    Err(e) => {
+                                        error!("無法將譜面 {} 匯入 osu!: {}", beatmapset_id, e)
+                                    }
+                                }
+                            }
+                            if let Err(e) = status_sender_clone
+                                .send((beatmapset_id, DownloadStatus::Completed))
+                                .await
+                            {
+                                error!("無法發送下載完成狀態: {:?}", e);
+                            }
+                        }
+                        Ok(Err(_)) | Err(_) => {
+                            let reason = match download_result {
+                                Ok(Err(e)) => e.to_string(),
+                                _ => "下載超時".to_string(),
+                            };
+                            error!("圖譜 {} 下載失敗: {}", beatmapset_id, reason);
+                            // 還沒超過重試次數就排回佇列
+                            let will_retry = download_queue
+                                .lock()
+                                .unwrap()
+                                .mark_failed(beatmapset_id, reason.clone());
+                            let status = if will_retry {
+                                info!("圖譜 {} 將重新下載", beatmapset_id);
+                                DownloadStatus::Waiting
+                            } else {
+                                DownloadStatus::Failed(reason)
+                            };
+                            beatmapset_download_statuses
+                                .lock()
+                                .unwrap()
+                                .insert(beatmapset_id, status.clone());
+                            if let Err(e) = status_sender_clone.send((beatmapset_id, status)).await
+                            {
+                                error!("無法發送下載失敗狀態: {:?}", e);
+                            }
+                        }
+                    }
+
+                    if let Err(e) = download_queue.lock().unwrap().save() {
+                        error!("保存下載佇列失敗: {:?}", e);
+                    }
+                    task_active_downloads.lock().unwrap().remove(&beatmapset_id);
+                    download_queue_notify.notify_one();
+                });
+                // 暫停或取消時用來中止下載
+                active.insert(beatmapset_id, handle.abort_handle());
+            }
+        });
+    }
+
+    //顯示osu譜面集詳情
+    fn display_selected_beatmapset(&mut self, ui: &mut egui::Ui, beatmapset: &Beatmapset) {
+        let beatmap_info = print_beatmap_info_gui(beatmapset);
+
+        ui.heading(
+            egui::RichText::new(format!("{} - {}", beatmap_info.title, beatmap_info.artist))
+                .font(egui::FontId::proportional(self.global_font_size * 1.1)),
+        );
+        ui.label(
+            egui::RichText::new(format!("by {}", beatmap_info.creator))
+                .font(egui::FontId::proportional(self.global_font_size * 0.9)),
+        );
+        ui.add_space(10.0);
+
+        for beatmap_info in beatmap_info.beatmaps {
+            ui.add_space(10.0);
+            ui.label(
+                egui::RichText::new(beatmap_info)
+                    .font(egui::FontId::proportional(self.global_font_size * 1.0)),
+            );
+            ui.add_space(10.0);
+            ui.separator();
+        }
+        if ui
+            .add_sized(
+                [100.0, 40.0],
+                egui::Button::new(
+                    egui::RichText::new("Back")
+                        .font(egui::FontId::proportional(self.global_font_size * 1.0)),
+                ),
+            )
+            .clicked()
+        {
+            self.selected_beatmapset = None;
+        }
+    }
+
+    //清除封面紋理
+    fn clear_cover_textures(&self) {
+        if let Ok(mut textures) = self.cover_textures.try_write() {
+            textures.clear();
+        }
+    }
+
+    //加載默認頭像
+    fn load_default_avatar(&mut self) {
+        let default_avatar_bytes = include_bytes!("assets/login.png");
+        let default_avatar_image = image::load_from_memory(default_avatar_bytes).unwrap();
+        let default_avatar_size = [
+            default_avatar_image.width() as _,
+            default_avatar_image.height() as _,
+        ];
+        let default_avatar_pixels = default_avatar_image.to_rgba8();
+        self.default_avatar_texture = Some(self.ctx.load_texture(
+            "default_avatar",
+            egui::ColorImage::from_rgba_unmultiplied(
+                default_avatar_size,
+                default_avatar_pixels.as_flat_samples().as_slice(),
+            ),
+            egui::TextureOptions::default(),
+        ));
+    }
+    //渲染頂部面板
+    fn render_top_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                // 渲染側邊選單按鈕
+                let (rect, response) = ui.allocate_exact_size(
+                    egui::vec2(BUTTON_SIZE, BUTTON_SIZE),
                     egui::Sense::click(),
                 );
                 if ui.is_rect_visible(rect) {
@@ -3112,6 +4321,10 @@ impl SearchApp {
                             } else {
                                 self.render_guest_user(ui);
                             }
+
+                            if self.osu_authorized.load(Ordering::SeqCst) {
+                                self.render_osu_user(ui);
+                            }
                         });
                     },
                 );
@@ -3150,6 +4363,8 @@ impl SearchApp {
             self.render_downloaded_maps_list(ui);
         } else if self.show_liked_tracks || self.selected_playlist.is_some() {
             self.render_playlist_content(ui);
+        } else if let Some(kind) = self.selected_osu_list {
+            self.render_osu_user_list(ui, kind);
         } else if self.show_playlists {
             self.render_playlists(ui);
         } else {
@@ -3251,6 +4466,17 @@ impl SearchApp {
                 {
                     info!("點擊了: 已下載圖譜");
                     self.show_downloaded_maps = true;
+                    // 開啟時重新掃描，反映在程式外新增或刪除的譜面
+                    self.refresh_local_library(Vec::new(), false);
+                }
+
+                ui.add_space(5.0);
+                if self
+                    .create_auth_button(ui, "下載佇列", "osu!logo.png")
+                    .clicked()
+                {
+                    info!("點擊了: 下載佇列");
+                    self.show_download_queue = true;
                 }
             });
 
@@ -3316,6 +4542,15 @@ impl SearchApp {
                                 error!("保存下載目錄失敗: {:?}", e);
                             }
                             info!("下載目錄已更改為: {:?}", self.download_directory);
+                            // lazer 的掃描結果與下載資料夾無關，直接沿用
+                            let mut library = self.local_library.lock().unwrap();
+                            let lazer_scan = library.lazer_scan();
+                            *library =
+                                LocalLibrary::load(&self.download_directory, self.debug_mode);
+                            library.set_lazer_scan(Some(lazer_scan));
+                            drop(library);
+                            self.refresh_local_library(Vec::new(), false);
+                            self.start_download_watcher();
                         }
                     }
                 });
@@ -3358,9 +4593,25 @@ impl SearchApp {
 
                 ui.add_space(10.0);
 
-                // 自定義背景設置
-                ui.horizontal(|ui| {
-                    ui.label("背景圖片:");
+                // 同時下載數、頻寬與下載時段
+                ui.collapsing("下載設定", |ui| {
+                    self.render_download_settings(ui);
+                });
+
+                ui.collapsing("osu! 客戶端", |ui| {
+                    self.render_osu_client_settings(ui);
+                });
+
+                // 下載鏡像站狀態
+                ui.collapsing("下載鏡像站", |ui| {
+                    self.render_mirror_health(ui);
+                });
+
+                ui.add_space(10.0);
+
+                // 自定義背景設置
+                ui.horizontal(|ui| {
+                    ui.label("背景圖片:");
                     if ui.button("選擇背景").clicked() {
                         if let Some(path) = rfd::FileDialog::new()
                             .add_filter("圖片", &["png", "jpg", "jpeg"])
@@ -3438,6 +4689,20 @@ impl SearchApp {
                             self.show_osu_search_bar = !self.show_osu_search_bar;
                         }
                     }
+
+                    let can_match = self.spotify_authorized.load(Ordering::SeqCst)
+                        && !self.is_local_matching.load(Ordering::SeqCst);
+                    if ui
+                        .add_enabled(can_match, egui::Button::new("🎵"))
+                        .on_hover_text(format!(
+                            "在 Spotify 上找出這些歌曲並加入「{}」播放清單",
+                            LOCAL_LIBRARY_PLAYLIST_NAME
+                        ))
+                        .on_disabled_hover_text("需要先登入 Spotify")
+                        .clicked()
+                    {
+                        self.start_local_library_match();
+                    }
                 });
             });
 
@@ -3455,7 +4720,7 @@ impl SearchApp {
                     ui.add_space(5.0);
                     let response = ui.add(
                         egui::TextEdit::singleline(&mut self.downloaded_maps_search)
-                            .hint_text("搜尋圖譜...")
+                            .hint_text("搜尋圖譜（可用 artist= creator= mode= stars>）")
                             .desired_width(fixed_width - 50.0),
                     );
                     if response.changed() {
@@ -3467,21 +4732,31 @@ impl SearchApp {
 
             // 圖譜列表
             egui::ScrollArea::vertical().show(ui, |ui| {
-                let downloaded = get_downloaded_beatmaps(&self.download_directory);
-                if downloaded.is_empty() {
-                    ui.label("尚未下載任何圖譜");
-                } else {
-                    // 先收集所有符合搜尋條件的檔案
-                    let search_term = self.downloaded_maps_search.to_lowercase();
-                    let filtered_maps: Vec<_> = downloaded
-                        .into_iter()
-                        .filter(|file_name| {
-                            search_term.is_empty()
-                                || file_name.to_lowercase().contains(&search_term)
-                        })
-                        .collect();
+                // 索引在背景更新時先顯示載入中，避免卡住介面
+                let filtered_maps: Option<Vec<LocalBeatmapset>> =
+                    self.local_library.try_lock().ok().map(|library| {
+                        library
+                            .query(&LibraryQuery::parse(&self.downloaded_maps_search))
+                            .into_iter()
+                            .cloned()
+                            .collect()
+                    });
+
+                let Some(filtered_maps) = filtered_maps else {
+                    ui.add(egui::Spinner::new());
+                    ui.label("正在更新譜面索引...");
+                    return;
+                };
 
-                    for file_name in filtered_maps {
+                if filtered_maps.is_empty() {
+                    if self.downloaded_maps_search.trim().is_empty() {
+                        ui.label("尚未下載任何圖譜");
+                    } else {
+                        ui.label("沒有符合條件的圖譜");
+                    }
+                } else {
+                    for beatmapset in filtered_maps {
+                        let file_name = beatmapset.file_name.clone();
                         ui.horizontal(|ui| {
                             let is_expanded = self.expanded_map_indices.contains(&file_name);
 
@@ -3506,19 +4781,50 @@ impl SearchApp {
                                 }
                             }
 
-                            // 檔案名稱顯示
+                            // 譜面資訊顯示
                             let available_width = fixed_width - 50.0;
-                            let text = egui::RichText::new(&file_name).size(14.0);
 
                             egui::Frame::none().show(ui, |ui| {
                                 ui.set_max_width(available_width);
-                                ui.label(text).on_hover_text(&file_name);
+                                ui.vertical(|ui| {
+                                    ui.label(
+                                        egui::RichText::new(beatmapset.display_name()).size(14.0),
+                                    )
+                                    .on_hover_text(&file_name);
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "by {} · {} 個難度 · {}:{:02}{}",
+                                            beatmapset.creator,
+                                            beatmapset.beatmaps.len(),
+                                            beatmapset.length_secs / 60,
+                                            beatmapset.length_secs % 60,
+                                            match beatmapset.variant {
+                                                DownloadVariant::Full => String::new(),
+                                                variant => format!(" · {}", variant.label()),
+                                            }
+                                        ))
+                                        .size(12.0)
+                                        .weak(),
+                                    );
+                                    let collections = beatmapset.id.and_then(|id| {
+                                        self.osu_collections.try_lock().ok()?.get(&id).cloned()
+                                    });
+                                    if let Some(collections) = collections {
+                                        ui.label(
+                                            egui::RichText::new(format!(
+                                                "收藏: {}",
+                                                collections.join(", ")
+                                            ))
+                                            .size(12.0)
+                                            .weak(),
+                                        );
+                                    }
+                                });
                             });
                         });
 
                         // 如果展開，顯示操作按鈕
                         if self.expanded_map_indices.contains(&file_name) {
-                            let file_name_clone = file_name.clone();
                             ui.horizontal(|ui| {
                                 ui.add_space(20.0);
 
@@ -3536,6 +4842,20 @@ impl SearchApp {
                                         ) {
                                             error!("刪除檔案失敗: {}", e);
                                         }
+                                        let mut library = self.local_library.lock().unwrap();
+                                        library.remove_file(&file_name);
+                                        if let Err(e) = library.save() {
+                                            error!("保存譜面索引失敗: {:?}", e);
+                                        }
+                                    }
+                                }
+
+                                // 匯入按鈕，只有 .osz 檔可以匯入
+                                if let Some(id) =
+                                    beatmapset.id.filter(|_| file_name.ends_with(".osz"))
+                                {
+                                    if ui.small_button("匯入 osu!").clicked() {
+                                        self.import_beatmapset_to_osu(id);
                                     }
                                 }
 
@@ -3548,11 +4868,8 @@ impl SearchApp {
                                         )))
                                         .clicked()
                                     {
-                                        if let Some(id) = Self::extract_beatmap_id(&file_name_clone)
-                                        {
-                                            self.search_query =
-                                                format!("https://osu.ppy.sh/beatmapsets/{}", id);
-                                            self.perform_search(ui.ctx().clone());
+                                        if let Some(id) = beatmapset.id {
+                                            self.handle_osu_beatmapset_search_click(id);
                                         }
                                     }
                                 }
@@ -3565,11 +4882,6 @@ impl SearchApp {
         });
     }
 
-    // 新增一個輔助函數來從檔名提取 beatmap ID
-    fn extract_beatmap_id(file_name: &str) -> Option<&str> {
-        file_name.split(' ').find(|s| s.parse::<u32>().is_ok())
-    }
-
     fn load_custom_background(
         &mut self,
         ctx: &egui::Context,
@@ -3643,6 +4955,15 @@ impl SearchApp {
                 self.render_liked_songs_item(ui);
                 ui.add_space(5.0);
                 ui.separator();
+
+                // 登入 osu! 後顯示最愛、最常玩與最近遊玩
+                if self.osu_authorized.load(Ordering::SeqCst) {
+                    for kind in OsuUserListKind::ALL {
+                        self.render_osu_list_item(ui, kind);
+                    }
+                    ui.add_space(5.0);
+                    ui.separator();
+                }
     
                 // 過濾播放清單
                 let playlists_clone = {
@@ -3721,6 +5042,148 @@ impl SearchApp {
         }
     }
 
+    fn render_osu_list_item(&mut self, ui: &mut egui::Ui, kind: OsuUserListKind) {
+        ui.add_space(5.0);
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 70.0), egui::Sense::click());
+
+        if ui.is_rect_visible(rect) {
+            let cover_size = egui::vec2(60.0, 60.0);
+            let text_rect = rect.shrink2(egui::vec2(cover_size.x + 30.0, 0.0));
+
+            ui.painter().text(
+                text_rect.left_center() + egui::vec2(0.0, -10.0),
+                egui::Align2::LEFT_CENTER,
+                kind.label(),
+                egui::FontId::proportional(18.0),
+                ui.visuals().text_color(),
+            );
+
+            let subtitle = match self.osu_user_lists.lock().unwrap().get(&kind) {
+                Some(entries) => format!("osu! · {} 個譜面集", entries.len()),
+                None => "osu!".to_string(),
+            };
+            ui.painter().text(
+                text_rect.left_center() + egui::vec2(0.0, 15.0),
+                egui::Align2::LEFT_CENTER,
+                subtitle,
+                egui::FontId::proportional(14.0),
+                ui.visuals().weak_text_color(),
+            );
+
+            let image_rect = egui::Rect::from_min_size(
+                rect.left_center() - egui::vec2(0.0, cover_size.y / 2.0),
+                cover_size,
+            );
+            ui.painter()
+                .rect_filled(image_rect, 0.0, egui::Color32::from_rgb(255, 102, 170));
+            if let Some(icon) = self.preloaded_icons.get("osu!logo.png") {
+                ui.painter().image(
+                    icon.id(),
+                    image_rect.shrink(10.0),
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::WHITE,
+                );
+            }
+        }
+
+        if response.clicked() {
+            if !self.osu_user_lists.lock().unwrap().contains_key(&kind) {
+                self.load_osu_user_list(kind);
+            }
+            self.selected_osu_list = Some(kind);
+            self.show_playlists = false;
+            info!("切換到 {} 視圖", kind.label());
+        }
+    }
+
+    fn render_osu_user_list(&mut self, ui: &mut egui::Ui, kind: OsuUserListKind) {
+        ui.horizontal(|ui| {
+            if ui.button("< 返回").clicked() {
+                self.selected_osu_list = None;
+                self.show_playlists = true;
+            }
+            ui.heading(kind.label());
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let is_loading = self.loading_osu_lists.lock().unwrap().contains(&kind);
+                if ui
+                    .add_enabled(!is_loading, egui::Button::new("🔄 重新加載"))
+                    .clicked()
+                {
+                    self.load_osu_user_list(kind);
+                }
+            });
+        });
+        ui.add_space(10.0);
+
+        if self.loading_osu_lists.lock().unwrap().contains(&kind) {
+            ui.add_space(20.0);
+            ui.add(egui::Spinner::new().size(32.0));
+            ui.label("正在加載...");
+            return;
+        }
+        let entries = self.osu_user_lists.lock().unwrap().get(&kind).cloned();
+        let Some(entries) = entries else {
+            ui.add_space(20.0);
+            ui.label("無法載入清單，請確認已登入 osu! 後重新加載");
+            return;
+        };
+        if entries.is_empty() {
+            ui.add_space(20.0);
+            ui.label("沒有找到譜面");
+            return;
+        }
+
+        egui::ScrollArea::vertical().show_rows(ui, 40.0, entries.len(), |ui, row_range| {
+            for index in row_range {
+                self.render_osu_user_list_entry(ui, &entries[index], index);
+            }
+        });
+    }
+
+    fn render_osu_user_list_entry(
+        &mut self,
+        ui: &mut egui::Ui,
+        entry: &OsuUserListEntry,
+        index: usize,
+    ) {
+        let beatmapset = &entry.beatmapset;
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Label::new(egui::RichText::new(format!("{}.", index + 1)).size(18.0))
+                    .wrap(false),
+            );
+            ui.add_space(10.0);
+
+            let content_width = ui.available_width() - 40.0;
+            ui.vertical(|ui| {
+                ui.set_width(content_width);
+                ui.label(egui::RichText::new(&beatmapset.title).size(18.0).strong());
+                let mut details = format!("{} · by {}", beatmapset.artist, beatmapset.creator);
+                if let Some(play_count) = entry.play_count {
+                    details.push_str(&format!(" · 遊玩 {} 次", play_count));
+                }
+                ui.label(egui::RichText::new(details).size(16.0).weak());
+            });
+
+            // 搜尋按鈕，在 Spotify 上找這首歌
+            if let Some(search_icon) = self.preloaded_icons.get("search.png") {
+                let response = ui.add(egui::ImageButton::new(egui::load::SizedTexture::new(
+                    search_icon.id(),
+                    egui::vec2(16.0, 16.0),
+                )));
+                if response.clicked() {
+                    self.handle_osu_beatmapset_search_click(beatmapset.id);
+                }
+                response.on_hover_text("以此搜尋");
+            }
+        });
+        ui.add_space(5.0);
+        ui.separator();
+    }
+
     fn render_playlist_item(&mut self, ui: &mut egui::Ui, playlist: &SimplifiedPlaylist) {
         ui.add_space(5.0);
 
@@ -3844,7 +5307,7 @@ impl SearchApp {
                         egui::FontId::new(font_size, egui::FontFamily::Proportional),
                         egui::Color32::WHITE,
                     )
-                }).size().x > available_width - 150.0 // 為搜尋按鈕預留更多空間
+                }).size().x > available_width - 280.0 // 為批次比對與搜尋按鈕預留空間
                 {
                     font_size -= 1.0;
                     if font_size < 16.0 {
@@ -3854,7 +5317,7 @@ impl SearchApp {
                                 egui::FontId::new(16.0, egui::FontFamily::Proportional),
                                 egui::Color32::WHITE,
                             )
-                        }).size().x > available_width - 150.0
+                        }).size().x > available_width - 280.0
                         {
                             if title.chars().count() > 3 {
                                 title.pop();
@@ -3912,6 +5375,15 @@ impl SearchApp {
                         });
                     }
 
+                    let is_batch_matching = self.is_batch_matching.load(Ordering::SeqCst);
+                    if ui
+                        .add_enabled(!is_batch_matching, egui::Button::new("🎯 批次比對 osu!"))
+                        .on_hover_text("逐首搜尋 osu! 並找出最佳匹配的譜面")
+                        .clicked()
+                    {
+                        self.start_batch_match();
+                    }
+
                     // 搜尋按鈕
                     if let Some(search_icon) = self.preloaded_icons.get("search.png") {
                         if ui.add(egui::ImageButton::new(
@@ -3924,154 +5396,686 @@ impl SearchApp {
                         }
                     }
                 });
-            });
+            });
+
+            // 搜尋欄
+            if self.show_tracks_search_bar {
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if let Some(search_icon) = self.preloaded_icons.get("search.png") {
+                        ui.image(egui::load::SizedTexture::new(
+                            search_icon.id(),
+                            egui::vec2(16.0, 16.0),
+                        ));
+                    }
+                    ui.add_space(5.0);
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.tracks_search_query)
+                            .hint_text("搜尋歌曲...")
+                            .desired_width(ui.available_width() - 50.0)
+                    );
+                    if response.changed() {
+                        info!("歌曲搜尋關鍵字: {}", self.tracks_search_query);
+                    }
+                });
+            }
+
+            // 處理更新檢查結果
+            while let Ok(has_updates) = self.update_check_receiver.try_recv() {
+                if has_updates {
+                    info!("發現更新，正在重新加載...");
+                    ui.label("發現更新，正在重新加載...");
+                    if self.show_liked_tracks {
+                        self.load_user_liked_tracks();
+                    } else if let Some(playlist) = &self.selected_playlist {
+                        self.load_playlist_tracks(playlist.id.clone());
+                    }
+                } else {
+                    info!("沒有發現更新，使用緩存數據");
+                    ui.label("沒有發現更新，使用緩存數據");
+                }
+            }
+
+            ui.add_space(10.0);
+
+            let is_loading = self.is_searching.load(Ordering::SeqCst);
+            let tracks = if self.show_liked_tracks {
+                self.spotify_liked_tracks.lock().unwrap().clone()
+            } else {
+                self.spotify_playlist_tracks.lock().unwrap().clone()
+            };
+
+            if is_loading {
+                ui.add_space(20.0);
+                ui.add(egui::Spinner::new().size(32.0));
+                ui.label("正在加載...");
+            } else if tracks.is_empty() {
+                ui.add_space(20.0);
+                ui.label("沒有找到曲目");
+            } else {
+                // 過濾歌曲
+                let search_term = self.tracks_search_query.to_lowercase();
+                let filtered_tracks: Vec<_> = tracks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, track)| {
+                        search_term.is_empty() ||
+                        track.name.to_lowercase().contains(&search_term) ||
+                        track.artists.iter().any(|artist| 
+                            artist.name.to_lowercase().contains(&search_term)
+                        )
+                    })
+                    .collect();
+
+                egui::ScrollArea::vertical().show_rows(
+                    ui,
+                    40.0,
+                    filtered_tracks.len(),
+                    |ui, row_range| {
+                        for i in row_range {
+                            if let Some((original_index, track)) = filtered_tracks.get(i) {
+                                self.render_track_item(ui, track, *original_index);
+                            }
+                        }
+                    },
+                );
+            }
+        });
+    }
+
+    fn render_track_item(&mut self, ui: &mut egui::Ui, track: &FullTrack, index: usize) {
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Label::new(egui::RichText::new(format!("{}.", index + 1)).size(18.0))
+                    .wrap(false),
+            );
+            ui.add_space(10.0);
+    
+            let content_width = ui.available_width() - 40.0;
+    
+            ui.vertical(|ui| {
+                ui.set_width(content_width);
+    
+                // 歌曲名稱
+                let title = track.name.clone();
+                ui.label(egui::RichText::new(title).size(18.0).strong());
+    
+                // 歌手名稱
+                let artists = track
+                    .artists
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                ui.label(egui::RichText::new(artists).size(16.0).weak());
+            });
+    
+            // 搜尋按鈕
+            if let Some(search_icon) = self.preloaded_icons.get("search.png") {
+                let response = ui.add(egui::ImageButton::new(
+                    egui::load::SizedTexture::new(
+                        search_icon.id(),
+                        egui::vec2(16.0, 16.0),
+                    ),
+                ));
+    
+                if response.clicked() {
+                    if let Some(spotify_url) = track.external_urls.get("spotify") {
+                        self.search_query = spotify_url.clone();
+                    } else {
+                        self.search_query = format!(
+                            "{} {}",
+                            track.name,
+                            track
+                                .artists
+                                .iter()
+                                .map(|a| a.name.as_str())
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        );
+                    }
+                    let ctx = ui.ctx().clone();
+                    self.perform_search(ctx);
+                }
+    
+                response.on_hover_text("以此搜尋");
+            }
+        });
+        ui.add_space(5.0);
+        ui.separator();
+    }
+
+    // 對目前開啟的播放清單或 Liked Songs 逐首搜尋 osu! 譜面
+    fn start_batch_match(&mut self) {
+        let tracks: Vec<Track> = if self.show_liked_tracks {
+            self.spotify_liked_tracks
+                .lock()
+                .unwrap()
+                .iter()
+                .map(Track::from)
+                .collect()
+        } else {
+            self.spotify_playlist_tracks
+                .lock()
+                .unwrap()
+                .iter()
+                .map(Track::from)
+                .collect()
+        };
+        if tracks.is_empty() {
+            info!("播放清單沒有曲目，略過批次比對");
+            return;
+        }
+
+        self.batch_match_source = if self.show_liked_tracks {
+            "Liked Songs".to_string()
+        } else if let Some(playlist) = &self.selected_playlist {
+            playlist.name.clone()
+        } else {
+            String::new()
+        };
+        self.new_collection_name = self.batch_match_source.clone();
+        *self.collection_message.lock().unwrap() = None;
+        self.show_batch_match_report = true;
+
+        let client = self.client.clone();
+        let debug_mode = self.debug_mode;
+        let osu_search_filter = self.osu_search_filter.clone();
+        let download_directory = self.download_directory.clone();
+        let entries = self.batch_match_entries.clone();
+        let total = self.batch_match_total.clone();
+        let is_batch_matching = self.is_batch_matching.clone();
+        let cancel = self.batch_match_cancel.clone();
+        let need_repaint = self.need_repaint.clone();
+        let err_msg = self.err_msg.clone();
+
+        entries.lock().unwrap().clear();
+        total.store(tracks.len(), Ordering::SeqCst);
+        cancel.store(false, Ordering::SeqCst);
+        is_batch_matching.store(true, Ordering::SeqCst);
+        info!(
+            "開始批次比對 {}，共 {} 首",
+            self.batch_match_source,
+            tracks.len()
+        );
+
+        tokio::spawn(async move {
+            let engine = CrossSearchEngine::new(client.lock().await.clone(), debug_mode)
+                .with_osu_filter(osu_search_filter);
+            let job = BatchMatchJob::new(engine, download_directory).with_cancel_flag(cancel);
+            let result = job
+                .run(tracks, |entry| {
+                    entries.lock().unwrap().push(entry.clone());
+                    need_repaint.store(true, Ordering::SeqCst);
+                })
+                .await;
+
+            if let Err(e) = result {
+                error!("批次比對失敗: {:?}", e);
+                *err_msg.lock().await = e.to_string();
+            }
+            is_batch_matching.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
+    }
+
+    fn render_batch_match_window(&mut self, ctx: &egui::Context) {
+        if !self.show_batch_match_report {
+            return;
+        }
+
+        let entries = self.batch_match_entries.lock().unwrap().clone();
+        let total = self.batch_match_total.load(Ordering::SeqCst);
+        let is_batch_matching = self.is_batch_matching.load(Ordering::SeqCst);
+        let queueable = queueable_beatmapset_ids(&entries);
+        let collection_ids = confident_beatmapset_ids(&entries);
+        let is_writing_collection = self.is_writing_collection.load(Ordering::SeqCst);
+        let collection_message = self.collection_message.lock().unwrap().clone();
+        let mut open = true;
+        let mut queue_all = false;
+        let mut create_collection = false;
+
+        egui::Window::new(format!("批次比對：{}", self.batch_match_source))
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(egui::vec2(700.0, 500.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if is_batch_matching {
+                        ui.add(egui::Spinner::new());
+                        ui.label(format!("比對中 {}/{}", entries.len(), total));
+                        if ui.button("取消").clicked() {
+                            self.batch_match_cancel.store(true, Ordering::SeqCst);
+                        }
+                    } else {
+                        ui.label(format!(
+                            "已比對 {} 首，找到 {} 首，已下載 {} 首",
+                            entries.len(),
+                            entries.iter().filter(|e| e.beatmapset.is_some()).count(),
+                            entries.iter().filter(|e| e.downloaded).count()
+                        ));
+                    }
+                });
+                if total > 0 {
+                    ui.add(egui::ProgressBar::new(entries.len() as f32 / total as f32));
+                }
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !queueable.is_empty(),
+                            egui::Button::new(format!("全部加入下載（{}）", queueable.len())),
+                        )
+                        .on_hover_text(format!(
+                            "加入信心分數 {:.0}% 以上且尚未下載的譜面",
+                            MIN_QUEUE_CONFIDENCE * 100.0
+                        ))
+                        .clicked()
+                    {
+                        queue_all = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("osu! 收藏:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_collection_name)
+                            .desired_width(200.0),
+                    );
+                    let can_create = !collection_ids.is_empty()
+                        && !is_batch_matching
+                        && !is_writing_collection
+                        && !self.new_collection_name.trim().is_empty();
+                    if ui
+                        .add_enabled(
+                            can_create,
+                            egui::Button::new(format!(
+                                "建立 osu! 收藏（{}）",
+                                collection_ids.len()
+                            )),
+                        )
+                        .on_hover_text(
+                            "把信心分數夠高的譜面加入 osu!stable 的收藏，譜面需要先匯入 osu!",
+                        )
+                        .clicked()
+                    {
+                        create_collection = true;
+                    }
+                    if is_writing_collection {
+                        ui.add(egui::Spinner::new());
+                    }
+                });
+                if let Some(message) = &collection_message {
+                    ui.label(message);
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("batch_match_grid")
+                        .striped(true)
+                        .num_columns(4)
+                        .show(ui, |ui| {
+                            ui.strong("Spotify 曲目");
+                            ui.strong("最佳匹配譜面");
+                            ui.strong("信心分數");
+                            ui.strong("狀態");
+                            ui.end_row();
+
+                            for entry in &entries {
+                                let artists = entry
+                                    .track
+                                    .artists
+                                    .iter()
+                                    .map(|a| a.name.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                ui.label(format!("{} - {}", artists, entry.track.name));
+
+                                match &entry.beatmapset {
+                                    Some(beatmapset) => {
+                                        ui.hyperlink_to(
+                                            format!("{} - {}", beatmapset.artist, beatmapset.title),
+                                            format!(
+                                                "https://osu.ppy.sh/beatmapsets/{}",
+                                                beatmapset.id
+                                            ),
+                                        );
+                                    }
+                                    None => {
+                                        ui.weak("-");
+                                    }
+                                }
+
+                                match entry.confidence() {
+                                    Some(confidence) => {
+                                        let color = if confidence >= MIN_QUEUE_CONFIDENCE {
+                                            egui::Color32::from_rgb(100, 200, 100)
+                                        } else {
+                                            egui::Color32::from_rgb(220, 160, 60)
+                                        };
+                                        ui.colored_label(
+                                            color,
+                                            format!("{:.0}%", confidence * 100.0),
+                                        );
+                                    }
+                                    None => {
+                                        ui.weak("-");
+                                    }
+                                }
+
+                                if let Some(error) = &entry.error {
+                                    ui.colored_label(egui::Color32::RED, "搜尋失敗")
+                                        .on_hover_text(error);
+                                } else if entry.downloaded {
+                                    ui.label("已下載");
+                                } else if let Some(beatmapset) = &entry.beatmapset {
+                                    let status = match self.get_download_status(beatmapset.id) {
+                                        DownloadStatus::NotStarted => "未下載",
+                                        DownloadStatus::Waiting => "等待中",
+                                        DownloadStatus::Downloading { .. } => "下載中",
+                                        DownloadStatus::Completed => "已下載",
+                                        DownloadStatus::Failed(_) => "下載失敗",
+                                    };
+                                    ui.label(status);
+                                } else {
+                                    ui.weak("找不到譜面");
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        if queue_all {
+            info!("批次加入 {} 個譜面到下載隊列", queueable.len());
+            let titles: HashMap<i32, (String, DownloadVariant)> = self
+                .batch_match_entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|entry| entry.beatmapset.as_ref())
+                .map(|b| {
+                    (
+                        b.id,
+                        (
+                            format!("{} - {}", b.artist, b.title),
+                            self.default_download_variant(b),
+                        ),
+                    )
+                })
+                .collect();
+            for beatmapset_id in queueable {
+                if matches!(
+                    self.get_download_status(beatmapset_id),
+                    DownloadStatus::NotStarted | DownloadStatus::Failed(_)
+                ) {
+                    let (title, variant) =
+                        titles.get(&beatmapset_id).cloned().unwrap_or_else(|| {
+                            (
+                                beatmapset_id.to_string(),
+                                self.download_settings.lock().unwrap().default_variant,
+                            )
+                        });
+                    self.queue_beatmap_download(beatmapset_id, title, variant);
+                }
+            }
+        }
+
+        if create_collection {
+            self.create_osu_collection(self.new_collection_name.trim().to_string(), collection_ids);
+        }
+
+        if !open {
+            self.batch_match_cancel.store(true, Ordering::SeqCst);
+            self.show_batch_match_report = false;
+        }
+    }
+
+    // 把下載資料夾中的譜面逐一反查 Spotify 曲目
+    fn start_local_library_match(&mut self) {
+        let file_names = get_downloaded_beatmaps(&self.download_directory);
+        if file_names.is_empty() {
+            info!("下載資料夾沒有譜面，略過反向比對");
+            return;
+        }
+        self.show_local_match_report = true;
+
+        let client = self.client.clone();
+        let debug_mode = self.debug_mode;
+        let download_directory = self.download_directory.clone();
+        let matches = self.local_song_matches.clone();
+        let total = self.local_match_total.clone();
+        let is_local_matching = self.is_local_matching.clone();
+        let cancel = self.local_match_cancel.clone();
+        let need_repaint = self.need_repaint.clone();
+        let err_msg = self.err_msg.clone();
+
+        matches.lock().unwrap().clear();
+        *self.playlist_sync_message.lock().unwrap() = None;
+        total.store(file_names.len(), Ordering::SeqCst);
+        cancel.store(false, Ordering::SeqCst);
+        is_local_matching.store(true, Ordering::SeqCst);
+        info!("開始反向比對本機譜面，共 {} 個", file_names.len());
+
+        tokio::spawn(async move {
+            let engine = CrossSearchEngine::new(client.lock().await.clone(), debug_mode);
+            let job =
+                LocalLibraryMatchJob::new(engine, download_directory).with_cancel_flag(cancel);
+            let result = job
+                .run(file_names, |entry| {
+                    matches.lock().unwrap().push(entry.clone());
+                    need_repaint.store(true, Ordering::SeqCst);
+                })
+                .await;
+
+            if let Err(e) = result {
+                error!("反向比對失敗: {:?}", e);
+                *err_msg.lock().await = e.to_string();
+            }
+            is_local_matching.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
+    }
+
+    // 把使用者確認過的曲目加入 Spotify 播放清單
+    fn sync_local_matches_to_playlist(&self) {
+        let track_ids = approved_track_ids(&self.local_song_matches.lock().unwrap());
+        if track_ids.is_empty() {
+            return;
+        }
+
+        let spotify = match self.spotify_client.lock().unwrap().clone() {
+            Some(spotify) => spotify,
+            None => {
+                error!("Spotify 客戶端未初始化，無法建立播放清單");
+                return;
+            }
+        };
+        let is_syncing_playlist = self.is_syncing_playlist.clone();
+        let playlist_sync_message = self.playlist_sync_message.clone();
+        let need_repaint = self.need_repaint.clone();
+
+        is_syncing_playlist.store(true, Ordering::SeqCst);
+        tokio::spawn(async move {
+            let message = match add_tracks_to_named_playlist(
+                &spotify,
+                LOCAL_LIBRARY_PLAYLIST_NAME,
+                &track_ids,
+            )
+            .await
+            {
+                Ok(added) => {
+                    info!(
+                        "已將 {} 首曲目加入「{}」",
+                        added, LOCAL_LIBRARY_PLAYLIST_NAME
+                    );
+                    format!(
+                        "已加入 {} 首新曲目到「{}」（{} 首已在清單中）",
+                        added,
+                        LOCAL_LIBRARY_PLAYLIST_NAME,
+                        track_ids.len() - added
+                    )
+                }
+                Err(e) => {
+                    error!("更新 Spotify 播放清單失敗: {:?}", e);
+                    // 舊的登入沒有 playlist-modify-private 權限，需要重新登入
+                    format!("更新播放清單失敗，請重新登入 Spotify 後再試：{}", e)
+                }
+            };
+            *playlist_sync_message.lock().unwrap() = Some(message);
+            is_syncing_playlist.store(false, Ordering::SeqCst);
+            need_repaint.store(true, Ordering::SeqCst);
+        });
+    }
+
+    fn render_local_match_window(&mut self, ctx: &egui::Context) {
+        if !self.show_local_match_report {
+            return;
+        }
+
+        let total = self.local_match_total.load(Ordering::SeqCst);
+        let is_local_matching = self.is_local_matching.load(Ordering::SeqCst);
+        let is_syncing_playlist = self.is_syncing_playlist.load(Ordering::SeqCst);
+        let sync_message = self.playlist_sync_message.lock().unwrap().clone();
+        let mut open = true;
+        let mut sync = false;
+
+        egui::Window::new(format!("本機譜面 → {}", LOCAL_LIBRARY_PLAYLIST_NAME))
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(egui::vec2(700.0, 500.0))
+            .show(ctx, |ui| {
+                let mut matches = self.local_song_matches.lock().unwrap();
+                let approved = approved_track_ids(&matches).len();
+                let needs_review = matches.iter().filter(|m| m.needs_review()).count();
+
+                ui.horizontal(|ui| {
+                    if is_local_matching {
+                        ui.add(egui::Spinner::new());
+                        ui.label(format!("比對中 {}/{}", matches.len(), total));
+                        if ui.button("取消").clicked() {
+                            self.local_match_cancel.store(true, Ordering::SeqCst);
+                        }
+                    } else {
+                        ui.label(format!(
+                            "已比對 {} 個，找到 {} 首，{} 首待確認",
+                            matches.len(),
+                            matches.iter().filter(|m| m.track.is_some()).count(),
+                            needs_review
+                        ));
+                    }
+                });
+                if total > 0 {
+                    ui.add(egui::ProgressBar::new(matches.len() as f32 / total as f32));
+                }
 
-            // 搜尋欄
-            if self.show_tracks_search_bar {
-                ui.add_space(10.0);
                 ui.horizontal(|ui| {
-                    if let Some(search_icon) = self.preloaded_icons.get("search.png") {
-                        ui.image(egui::load::SizedTexture::new(
-                            search_icon.id(),
-                            egui::vec2(16.0, 16.0),
-                        ));
+                    if ui
+                        .add_enabled(
+                            approved > 0 && !is_syncing_playlist,
+                            egui::Button::new(format!("加入播放清單（{}）", approved)),
+                        )
+                        .on_hover_text(format!(
+                            "信心分數低於 {:.0}% 的曲目需要勾選確認後才會加入",
+                            MIN_AUTO_ADD_CONFIDENCE * 100.0
+                        ))
+                        .clicked()
+                    {
+                        sync = true;
                     }
-                    ui.add_space(5.0);
-                    let response = ui.add(
-                        egui::TextEdit::singleline(&mut self.tracks_search_query)
-                            .hint_text("搜尋歌曲...")
-                            .desired_width(ui.available_width() - 50.0)
-                    );
-                    if response.changed() {
-                        info!("歌曲搜尋關鍵字: {}", self.tracks_search_query);
+                    if is_syncing_playlist {
+                        ui.add(egui::Spinner::new());
                     }
                 });
-            }
-
-            // 處理更新檢查結果
-            while let Ok(has_updates) = self.update_check_receiver.try_recv() {
-                if has_updates {
-                    info!("發現更新，正在重新加載...");
-                    ui.label("發現更新，正在重新加載...");
-                    if self.show_liked_tracks {
-                        self.load_user_liked_tracks();
-                    } else if let Some(playlist) = &self.selected_playlist {
-                        self.load_playlist_tracks(playlist.id.clone());
-                    }
-                } else {
-                    info!("沒有發現更新，使用緩存數據");
-                    ui.label("沒有發現更新，使用緩存數據");
+                if let Some(message) = &sync_message {
+                    ui.label(message);
                 }
-            }
+                ui.separator();
 
-            ui.add_space(10.0);
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("local_match_grid")
+                        .striped(true)
+                        .num_columns(4)
+                        .show(ui, |ui| {
+                            ui.strong("加入");
+                            ui.strong("本機譜面");
+                            ui.strong("Spotify 曲目");
+                            ui.strong("信心分數");
+                            ui.end_row();
+
+                            for entry in matches.iter_mut() {
+                                ui.add_enabled(
+                                    entry.track.is_some(),
+                                    egui::Checkbox::without_text(&mut entry.approved),
+                                );
 
-            let is_loading = self.is_searching.load(Ordering::SeqCst);
-            let tracks = if self.show_liked_tracks {
-                self.spotify_liked_tracks.lock().unwrap().clone()
-            } else {
-                self.spotify_playlist_tracks.lock().unwrap().clone()
-            };
+                                if entry.beatmapset.title.is_empty() {
+                                    ui.label(&entry.file_name);
+                                } else {
+                                    ui.label(entry.beatmapset.display_name())
+                                        .on_hover_text(&entry.file_name);
+                                }
 
-            if is_loading {
-                ui.add_space(20.0);
-                ui.add(egui::Spinner::new().size(32.0));
-                ui.label("正在加載...");
-            } else if tracks.is_empty() {
-                ui.add_space(20.0);
-                ui.label("沒有找到曲目");
-            } else {
-                // 過濾歌曲
-                let search_term = self.tracks_search_query.to_lowercase();
-                let filtered_tracks: Vec<_> = tracks
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, track)| {
-                        search_term.is_empty() ||
-                        track.name.to_lowercase().contains(&search_term) ||
-                        track.artists.iter().any(|artist| 
-                            artist.name.to_lowercase().contains(&search_term)
-                        )
-                    })
-                    .collect();
+                                match &entry.track {
+                                    Some(track) => {
+                                        let artists = track
+                                            .artists
+                                            .iter()
+                                            .map(|a| a.name.as_str())
+                                            .collect::<Vec<_>>()
+                                            .join(", ");
+                                        let text = format!("{} - {}", artists, track.name);
+                                        match track.external_urls.get("spotify") {
+                                            Some(url) => {
+                                                ui.hyperlink_to(text, url);
+                                            }
+                                            None => {
+                                                ui.label(text);
+                                            }
+                                        }
+                                    }
+                                    None => {
+                                        if let Some(error) = &entry.error {
+                                            ui.colored_label(egui::Color32::RED, "搜尋失敗")
+                                                .on_hover_text(error);
+                                        } else {
+                                            ui.weak("找不到曲目");
+                                        }
+                                    }
+                                }
 
-                egui::ScrollArea::vertical().show_rows(
-                    ui,
-                    40.0,
-                    filtered_tracks.len(),
-                    |ui, row_range| {
-                        for i in row_range {
-                            if let Some((original_index, track)) = filtered_tracks.get(i) {
-                                self.render_track_item(ui, track, *original_index);
+                                match entry.confidence() {
+                                    Some(confidence) => {
+                                        let color = if entry.needs_review() {
+                                            egui::Color32::from_rgb(220, 160, 60)
+                                        } else {
+                                            egui::Color32::from_rgb(100, 200, 100)
+                                        };
+                                        ui.colored_label(
+                                            color,
+                                            format!("{:.0}%", confidence * 100.0),
+                                        );
+                                    }
+                                    None => {
+                                        ui.weak("-");
+                                    }
+                                }
+                                ui.end_row();
                             }
-                        }
-                    },
-                );
-            }
-        });
-    }
-
-    fn render_track_item(&mut self, ui: &mut egui::Ui, track: &FullTrack, index: usize) {
-        ui.add_space(5.0);
-        ui.horizontal(|ui| {
-            ui.add(
-                egui::Label::new(egui::RichText::new(format!("{}.", index + 1)).size(18.0))
-                    .wrap(false),
-            );
-            ui.add_space(10.0);
-    
-            let content_width = ui.available_width() - 40.0;
-    
-            ui.vertical(|ui| {
-                ui.set_width(content_width);
-    
-                // 歌曲名稱
-                let title = track.name.clone();
-                ui.label(egui::RichText::new(title).size(18.0).strong());
-    
-                // 歌手名稱
-                let artists = track
-                    .artists
-                    .iter()
-                    .map(|a| a.name.clone())
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                ui.label(egui::RichText::new(artists).size(16.0).weak());
+                        });
+                });
             });
-    
-            // 搜尋按鈕
-            if let Some(search_icon) = self.preloaded_icons.get("search.png") {
-                let response = ui.add(egui::ImageButton::new(
-                    egui::load::SizedTexture::new(
-                        search_icon.id(),
-                        egui::vec2(16.0, 16.0),
-                    ),
-                ));
-    
-                if response.clicked() {
-                    if let Some(spotify_url) = track.external_urls.get("spotify") {
-                        self.search_query = spotify_url.clone();
-                    } else {
-                        self.search_query = format!(
-                            "{} {}",
-                            track.name,
-                            track
-                                .artists
-                                .iter()
-                                .map(|a| a.name.as_str())
-                                .collect::<Vec<_>>()
-                                .join(" ")
-                        );
-                    }
-                    let ctx = ui.ctx().clone();
-                    self.perform_search(ctx);
-                }
-    
-                response.on_hover_text("以此搜尋");
-            }
-        });
-        ui.add_space(5.0);
-        ui.separator();
+
+        if sync {
+            self.sync_local_matches_to_playlist();
+        }
+
+        if !open {
+            self.local_match_cancel.store(true, Ordering::SeqCst);
+            self.show_local_match_report = false;
+        }
     }
 
     fn load_user_playlists(&self) {
@@ -4099,6 +6103,54 @@ impl SearchApp {
         });
     }
 
+    // 以 osu! 使用者令牌讀取譜面清單，令牌過期時會先刷新
+    fn load_osu_user_list(&self, kind: OsuUserListKind) {
+        if !self.loading_osu_lists.lock().unwrap().insert(kind) {
+            return;
+        }
+        let client = self.client.clone();
+        let osu_user_lists = self.osu_user_lists.clone();
+        let loading_osu_lists = self.loading_osu_lists.clone();
+        let beatmap_mirrors = self.beatmap_mirrors.clone();
+        let ctx = self.ctx.clone();
+        let debug_mode = self.debug_mode;
+
+        tokio::spawn(async move {
+            let http = client.lock().await.clone();
+            let result: Result<Vec<OsuUserListEntry>, OsuError> = async {
+                let config =
+                    read_config(debug_mode).map_err(|e| OsuError::ConfigError(e.to_string()))?;
+                let login_info = check_and_refresh_token(&http, &config, "osu")
+                    .await
+                    .map_err(|e| OsuError::AuthorizationError(e.to_string()))?;
+                beatmap_mirrors.set_osu_login(Some(login_info.clone()));
+                let user =
+                    osu::get_current_user(&http, osu::OSU_BASE_URL, &login_info.access_token)
+                        .await?;
+                osu::get_user_beatmapset_list(
+                    &http,
+                    osu::OSU_BASE_URL,
+                    &login_info.access_token,
+                    user.id,
+                    kind,
+                    debug_mode,
+                )
+                .await
+            }
+            .await;
+
+            match result {
+                Ok(entries) => {
+                    info!("已載入{}，共 {} 個譜面集", kind.label(), entries.len());
+                    osu_user_lists.lock().unwrap().insert(kind, entries);
+                }
+                Err(e) => error!("載入{}失敗: {}", kind.label(), e),
+            }
+            loading_osu_lists.lock().unwrap().remove(&kind);
+            ctx.request_repaint();
+        });
+    }
+
     fn load_playlist_tracks(&self, playlist_id: PlaylistId) {
         let spotify_client = self.spotify_client.clone();
         let playlist_tracks = self.spotify_playlist_tracks.clone();
@@ -4539,12 +6591,101 @@ impl SearchApp {
             ui.add_space(5.0);
 
             // Osu 授權部分
-            if self
-                .create_auth_button(ui, "建構中", "osu!logo.png")
-                .clicked()
-            {
-                info!("Osu 授權按鈕被點擊了！");
-                // TODO: 實現 Osu 授權邏輯
+            if self.osu_authorized.load(Ordering::SeqCst) {
+                let button_text = match &*self.osu_user_name.lock().unwrap() {
+                    Some(name) => format!("{} (登出)", name),
+                    None => "osu! (登出)".to_string(),
+                };
+                if self
+                    .create_auth_button(ui, &button_text, "osu!logo.png")
+                    .clicked()
+                {
+                    self.logout_osu();
+                    ui.close_menu();
+                }
+            } else {
+                match self.auth_manager.get_status(&AuthPlatform::Osu) {
+                    AuthStatus::WaitingForBrowser
+                    | AuthStatus::Processing
+                    | AuthStatus::TokenObtained => {
+                        let button =
+                            egui::Button::new(egui::RichText::new("osu! 授權中...").size(16.0))
+                                .min_size(egui::vec2(200.0, 40.0));
+                        if ui.add(button).on_hover_text("點擊取消").clicked() {
+                            self.cancel_osu_authorization();
+                        }
+                    }
+                    status => {
+                        if self
+                            .create_auth_button(ui, "osu! 授權", "osu!logo.png")
+                            .clicked()
+                        {
+                            info!("Osu 授權按鈕被點擊了！");
+                            let ctx = ui.ctx().clone();
+                            self.start_osu_authorization(ctx);
+                        }
+                        if let AuthStatus::Failed(error) = status {
+                            ui.colored_label(egui::Color32::RED, "osu! 授權失敗")
+                                .on_hover_text(error);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // 渲染 osu! 登入使用者的頭像，點擊顯示使用者名稱與登出
+    fn render_osu_user(&mut self, ui: &mut egui::Ui) {
+        let avatar_size = egui::vec2(32.0, 32.0);
+        let button = egui::Button::new("")
+            .fill(egui::Color32::TRANSPARENT)
+            .min_size(egui::vec2(40.0, 40.0))
+            .frame(false);
+
+        let user_name = self.osu_user_name.lock().unwrap().clone();
+        let response = ui.add(button);
+
+        if ui.is_rect_visible(response.rect) {
+            let texture_id = match &*self.osu_user_avatar.lock().unwrap() {
+                Some(avatar) => Some(avatar.id()),
+                None => self
+                    .preloaded_icons
+                    .get("osu!logo.png")
+                    .map(|icon| icon.id()),
+            };
+            if let Some(texture_id) = texture_id {
+                let image_rect = egui::Rect::from_center_size(response.rect.center(), avatar_size);
+                ui.painter().image(
+                    texture_id,
+                    image_rect,
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::WHITE,
+                );
+            }
+        }
+
+        if response.hovered() {
+            ui.painter().rect_stroke(
+                response.rect,
+                egui::Rounding::same(4.0),
+                egui::Stroke::new(1.0, egui::Color32::LIGHT_BLUE),
+            );
+        }
+
+        let popup_id = egui::Id::new("osu_user_popup");
+        let response = response.on_hover_text(user_name.as_deref().unwrap_or("osu!"));
+        if response.clicked() {
+            ui.memory_mut(|mem| mem.toggle_popup(popup_id));
+        }
+
+        egui::popup::popup_below_widget(ui, popup_id, &response, |ui| {
+            ui.set_min_width(150.0);
+            ui.label(format!(
+                "osu!：{}",
+                user_name.as_deref().unwrap_or("未知用戶")
+            ));
+            if ui.button("登出 osu!").clicked() {
+                self.logout_osu();
                 ui.close_menu();
             }
         });
@@ -4574,10 +6715,9 @@ impl SearchApp {
         self.auth_in_progress.store(false, Ordering::SeqCst);
         self.show_auth_progress = false;
 
-        // 刪除 login_info.json 文件
-        let file_path = get_app_data_path().join("login_info.json");
-        if let Err(e) = std::fs::remove_file(file_path) {
-            error!("刪除 login_info.json 失敗: {}", e);
+        // 刪除 Spotify 的登入信息，保留 osu! 的登入
+        if let Err(e) = remove_platform_login_info("spotify") {
+            error!("刪除 Spotify 登入信息失敗: {}", e);
         }
         // 刪除使用者頭像
         if let Some(user_name) = self.spotify_user_name.lock().unwrap().as_ref() {
@@ -4949,7 +7089,9 @@ impl SearchApp {
         let available_width = ui.available_width();
         let button_width = 30.0;
         let spacing = 5.0;
-        let text_edit_width = available_width - 2.0 * button_width - 2.0 * spacing;
+        let type_selector_width = 90.0;
+        let text_edit_width =
+            available_width - type_selector_width - 2.0 * button_width - 3.0 * spacing;
         let text_edit_height = 32.0;
 
         let search_bar_id = egui::Id::new("search_bar");
@@ -4963,6 +7105,20 @@ impl SearchApp {
             ui.style_mut().spacing.item_spacing.x = spacing;
 
             ui.horizontal(|ui| {
+                // 關鍵字搜尋類型，專輯、藝人、播放清單會展開成曲目逐首搜尋 osu!
+                egui::ComboBox::from_id_source("spotify_search_type")
+                    .width(type_selector_width)
+                    .selected_text(self.spotify_search_type.label())
+                    .show_ui(ui, |ui| {
+                        for search_type in SpotifySearchType::ALL {
+                            ui.selectable_value(
+                                &mut self.spotify_search_type,
+                                search_type,
+                                search_type.label(),
+                            );
+                        }
+                    });
+
                 let text_edit = egui::TextEdit::singleline(&mut self.search_query)
                     .id(search_bar_id)
                     .font(egui::FontId::proportional(16.0))
@@ -4982,14 +7138,13 @@ impl SearchApp {
                     self.perform_search(ctx.clone());
                 }
 
-                if !self.search_query.is_empty() {
-                    if ui
+                if !self.search_query.is_empty()
+                    && ui
                         .add_sized([button_width, text_edit_height], egui::Button::new("✖"))
                         .clicked()
-                    {
-                        self.search_query.clear();
-                        ui.memory_mut(|mem| mem.request_focus(search_bar_id));
-                    }
+                {
+                    self.search_query.clear();
+                    ui.memory_mut(|mem| mem.request_focus(search_bar_id));
                 }
 
                 if ui
@@ -5003,9 +7158,8 @@ impl SearchApp {
     }
 
     fn update_font_size(&mut self, ui: &mut egui::Ui) {
-        if ui
-            .memory_mut(|mem| mem.data.get_temp::<f32>(egui::Id::new("global_font_size")))
-            .map_or(true, |old_size| old_size != self.global_font_size)
+        if ui.memory_mut(|mem| mem.data.get_temp::<f32>(egui::Id::new("global_font_size")))
+            != Some(self.global_font_size)
         {
             ui.memory_mut(|mem| {
                 mem.data
@@ -5036,7 +7190,7 @@ impl SearchApp {
     fn display_error_message(&self, ui: &mut egui::Ui) {
         if let Ok(err_msg_guard) = self.err_msg.try_lock() {
             if !err_msg_guard.is_empty() {
-                ui.label(format!("{}", *err_msg_guard));
+                ui.label(err_msg_guard.as_str());
             }
         }
     }
@@ -5246,8 +7400,7 @@ async fn main() -> Result<(), AppError> {
     let (sender, receiver) = tokio::sync::mpsc::channel(100);
 
     // 定義 cover_textures
-    let cover_textures: Arc<RwLock<HashMap<usize, Option<(Arc<TextureHandle>, (f32, f32))>>>> =
-        Arc::new(RwLock::new(HashMap::new()));
+    let cover_textures: Arc<RwLock<CoverTextures>> = Arc::new(RwLock::new(HashMap::new()));
     let need_repaint = Arc::new(AtomicBool::new(false));
 
     // 檢查下載目錄
@@ -5270,15 +7423,17 @@ async fn main() -> Result<(), AppError> {
     let download_dir = load_download_directory().expect("無法獲取下載目錄");
     info!("下載目錄: {:?}", download_dir);
 
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.hardware_acceleration = eframe::HardwareAcceleration::Preferred;
-    native_options.viewport = ViewportBuilder {
-        title: Some(String::from("Search App")),
-        inner_size: Some(egui::Vec2::new(730.0, 430.0)),
-        min_inner_size: Some(egui::Vec2::new(730.0, 430.0)),
-        resizable: Some(true),
-        maximize_button: Some(true),
-        transparent: Some(true),
+    let native_options = eframe::NativeOptions {
+        hardware_acceleration: eframe::HardwareAcceleration::Preferred,
+        viewport: ViewportBuilder {
+            title: Some(String::from("Search App")),
+            inner_size: Some(egui::Vec2::new(730.0, 430.0)),
+            min_inner_size: Some(egui::Vec2::new(730.0, 430.0)),
+            resizable: Some(true),
+            maximize_button: Some(true),
+            transparent: Some(true),
+            ..Default::default()
+        },
         ..Default::default()
     };
 
@@ -0,0 +1,446 @@
+// 第三方庫導入
+use serde::Serialize;
+
+// 本地模組導入
+use crate::normalize::{normalize_artist, normalize_title};
+use crate::osu::{Beatmapset, LocalBeatmapset};
+use crate::spotify::Track;
+
+// 長度差在這個秒數內視為同一個版本（不同平台的裁切誤差）
+const LENGTH_TOLERANCE_SECS: f32 = 3.0;
+// 長度差超過容許值後，再差多少秒分數降到 0
+const LENGTH_FALLOFF_SECS: f32 = 30.0;
+
+// 各項分數的權重
+const TITLE_WEIGHT: f32 = 0.5;
+const ARTIST_WEIGHT: f32 = 0.3;
+const LENGTH_WEIGHT: f32 = 0.2;
+
+// 單一譜面集對 Spotify 曲目的匹配分數，所有值介於 0.0 ~ 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MatchScore {
+    pub title: f32,
+    pub artist: f32,
+    // Spotify 沒有提供長度時為 None，不列入計算
+    pub length: Option<f32>,
+    pub confidence: f32,
+}
+
+// 排序後的最佳匹配
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BestMatch {
+    pub beatmapset_id: i32,
+    pub score: MatchScore,
+}
+
+// 比對時用到的歌曲欄位，API 回傳的譜面集與本機譜面共用
+#[derive(Debug, Clone, Copy)]
+pub struct SongFields<'a> {
+    pub artist: &'a str,
+    pub artist_unicode: &'a str,
+    pub title: &'a str,
+    pub title_unicode: &'a str,
+    // 歌曲長度（秒），未知時為 None
+    pub length_secs: Option<i32>,
+}
+
+impl<'a> From<&'a Beatmapset> for SongFields<'a> {
+    fn from(beatmapset: &'a Beatmapset) -> Self {
+        // 以譜面集中最長的難度作為歌曲長度（部分難度可能裁掉前奏或尾奏）
+        Self {
+            artist: &beatmapset.artist,
+            artist_unicode: &beatmapset.artist_unicode,
+            title: &beatmapset.title,
+            title_unicode: &beatmapset.title_unicode,
+            length_secs: beatmapset.beatmaps.iter().map(|b| b.total_length).max(),
+        }
+    }
+}
+
+impl<'a> From<&'a LocalBeatmapset> for SongFields<'a> {
+    fn from(beatmapset: &'a LocalBeatmapset) -> Self {
+        Self {
+            artist: &beatmapset.artist,
+            artist_unicode: &beatmapset.artist_unicode,
+            title: &beatmapset.title,
+            title_unicode: &beatmapset.title_unicode,
+            length_secs: Some(beatmapset.length_secs).filter(|length| *length > 0),
+        }
+    }
+}
+
+// 計算譜面集與 Spotify 曲目的匹配分數
+pub fn score_beatmapset(track: &Track, beatmapset: &Beatmapset) -> MatchScore {
+    score_song(track, &SongFields::from(beatmapset))
+}
+
+// 計算任意歌曲欄位與 Spotify 曲目的匹配分數
+pub fn score_song(track: &Track, song: &SongFields) -> MatchScore {
+    // Spotify 可能回傳羅馬拼音或原文，兩種寫法都比對並取較高者
+    let spotify_title = normalize_title(&track.name);
+    let title = similarity(&spotify_title, &normalize_title(song.title)).max(similarity(
+        &spotify_title,
+        &normalize_title(song.title_unicode),
+    ));
+    let artist =
+        artist_similarity(track, song.artist).max(artist_similarity(track, song.artist_unicode));
+    let length = length_score(track.duration_ms, song.length_secs);
+
+    let confidence = match length {
+        Some(length) => title * TITLE_WEIGHT + artist * ARTIST_WEIGHT + length * LENGTH_WEIGHT,
+        None => (title * TITLE_WEIGHT + artist * ARTIST_WEIGHT) / (TITLE_WEIGHT + ARTIST_WEIGHT),
+    };
+
+    MatchScore {
+        title,
+        artist,
+        length,
+        confidence,
+    }
+}
+
+// 從多首 Spotify 曲目中挑出與歌曲最相符的一首，回傳其索引與分數
+pub fn best_track_for_song(tracks: &[Track], song: &SongFields) -> Option<(usize, MatchScore)> {
+    tracks
+        .iter()
+        .map(|track| score_song(track, song))
+        .enumerate()
+        // max_by 在分數相同時取最後一個，反轉比較以保留 API 原本較前面的結果
+        .max_by(|a, b| {
+            a.1.confidence
+                .total_cmp(&b.1.confidence)
+                .then(b.0.cmp(&a.0))
+        })
+}
+
+// 依匹配分數由高到低排序譜面集，回傳排序結果與最佳匹配
+pub fn rank_beatmapsets(
+    track: &Track,
+    beatmapsets: Vec<Beatmapset>,
+) -> (Vec<Beatmapset>, Option<BestMatch>) {
+    let mut scored: Vec<(MatchScore, Beatmapset)> = beatmapsets
+        .into_iter()
+        .map(|beatmapset| (score_beatmapset(track, &beatmapset), beatmapset))
+        .collect();
+
+    // sort_by 是穩定排序，分數相同時保留 API 原本的順序
+    scored.sort_by(|a, b| b.0.confidence.total_cmp(&a.0.confidence));
+
+    let best_match = scored.first().map(|(score, beatmapset)| BestMatch {
+        beatmapset_id: beatmapset.id,
+        score: *score,
+    });
+
+    (
+        scored
+            .into_iter()
+            .map(|(_, beatmapset)| beatmapset)
+            .collect(),
+        best_match,
+    )
+}
+
+// 藝人比對：osu! 常把合作藝人寫成一整串，所以同時比對整串與個別藝人
+fn artist_similarity(track: &Track, osu_artist: &str) -> f32 {
+    let normalized_osu = normalize_artist(osu_artist);
+    let joined = normalize_artist(
+        &track
+            .artists
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+
+    track
+        .artists
+        .iter()
+        .map(|a| {
+            let name = normalize_artist(&a.name);
+            if !name.is_empty() && normalized_osu.contains(&name) {
+                // 個別藝人完整出現在 osu! 藝人欄位中
+                0.9
+            } else {
+                similarity(&name, &normalized_osu)
+            }
+        })
+        .fold(similarity(&joined, &normalized_osu), f32::max)
+}
+
+fn length_score(duration_ms: u32, length_secs: Option<i32>) -> Option<f32> {
+    if duration_ms == 0 {
+        return None;
+    }
+    let map_length = length_secs?;
+    if map_length <= 0 {
+        return None;
+    }
+
+    let diff = (duration_ms as f32 / 1000.0 - map_length as f32).abs();
+    if diff <= LENGTH_TOLERANCE_SECS {
+        Some(1.0)
+    } else {
+        Some((1.0 - (diff - LENGTH_TOLERANCE_SECS) / LENGTH_FALLOFF_SECS).max(0.0))
+    }
+}
+
+// 字串相似度（輸入需已正規化）：取字元編輯距離與詞彙重疊兩者較高者
+fn similarity(a: &str, b: &str) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    levenshtein_ratio(a, b).max(token_overlap(a, b))
+}
+
+fn levenshtein_ratio(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let max_len = a.len().max(b.len());
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            current[j + 1] = (previous[j + 1] + 1)
+                .min(current[j] + 1)
+                .min(previous[j] + cost);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    1.0 - previous[b.len()] as f32 / max_len as f32
+}
+
+// Dice 係數，對詞序不同或多出少量字詞的情況較寬容
+fn token_overlap(a: &str, b: &str) -> f32 {
+    let a_tokens: Vec<&str> = a.split_whitespace().collect();
+    let b_tokens: Vec<&str> = b.split_whitespace().collect();
+    let common = a_tokens.iter().filter(|t| b_tokens.contains(t)).count();
+    2.0 * common as f32 / (a_tokens.len() + b_tokens.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{MIN_AUTO_ADD_CONFIDENCE, MIN_QUEUE_CONFIDENCE};
+    use crate::osu::{Beatmap, Covers};
+    use crate::spotify::{Album, Artist};
+    use std::collections::HashMap;
+
+    fn track(name: &str, artists: &[&str], duration_secs: u32) -> Track {
+        let artists: Vec<Artist> = artists
+            .iter()
+            .map(|name| Artist {
+                name: name.to_string(),
+            })
+            .collect();
+        Track {
+            name: name.to_string(),
+            artists: artists.clone(),
+            external_urls: HashMap::new(),
+            album: Album {
+                album_type: "single".to_string(),
+                total_tracks: 1,
+                external_urls: HashMap::new(),
+                id: String::new(),
+                images: Vec::new(),
+                name: name.to_string(),
+                release_date: String::new(),
+                artists,
+            },
+            duration_ms: duration_secs * 1000,
+            is_liked: None,
+            index: 0,
+        }
+    }
+
+    fn beatmapset(id: i32, artist: &str, title: &str, length_secs: i32) -> Beatmapset {
+        Beatmapset {
+            beatmaps: vec![Beatmap {
+                difficulty_rating: 4.0,
+                id: id * 10,
+                mode: "osu".to_string(),
+                status: "ranked".to_string(),
+                total_length: length_secs,
+                user_id: 1,
+                version: "Insane".to_string(),
+            }],
+            id,
+            artist: artist.to_string(),
+            artist_unicode: String::new(),
+            title: title.to_string(),
+            title_unicode: String::new(),
+            creator: "mapper".to_string(),
+            covers: Covers {
+                cover: None,
+                cover_2x: None,
+                card: None,
+                card_2x: None,
+                list: None,
+                list_2x: None,
+                slimcover: None,
+                slimcover_2x: None,
+            },
+            preview_url: None,
+            video: false,
+        }
+    }
+
+    fn ranked_ids(track: &Track, beatmapsets: Vec<Beatmapset>) -> (Vec<i32>, Option<i32>) {
+        let (ranked, best) = rank_beatmapsets(track, beatmapsets);
+        (
+            ranked.iter().map(|b| b.id).collect(),
+            best.map(|best| best.beatmapset_id),
+        )
+    }
+
+    #[test]
+    fn full_track_prefers_full_length_map_over_tv_size() {
+        let track = track("Renai Circulation", &["Kana Hanazawa"], 255);
+        let (ids, best) = ranked_ids(
+            &track,
+            vec![
+                beatmapset(1, "Kana Hanazawa", "Renai Circulation (TV Size)", 90),
+                beatmapset(2, "Kana Hanazawa", "Renai Circulation", 254),
+            ],
+        );
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!(best, Some(2));
+    }
+
+    #[test]
+    fn tv_size_track_prefers_tv_size_map() {
+        let track = track("Renai Circulation (TV Size)", &["Kana Hanazawa"], 90);
+        let (ids, best) = ranked_ids(
+            &track,
+            vec![
+                beatmapset(1, "Kana Hanazawa", "Renai Circulation", 254),
+                beatmapset(2, "Kana Hanazawa", "Renai Circulation (TV Size)", 89),
+            ],
+        );
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!(best, Some(2));
+    }
+
+    #[test]
+    fn cut_version_title_still_matches() {
+        let track = track("Blue Bird", &["Ikimonogakari"], 92);
+        let score = score_beatmapset(
+            &track,
+            &beatmapset(1, "Ikimonogakari", "Blue Bird [Cut Ver.]", 92),
+        );
+        assert_eq!(score.title, 1.0);
+        assert_eq!(score.length, Some(1.0));
+    }
+
+    #[test]
+    fn ranking_keeps_api_order_on_ties() {
+        let track = track("Renai Circulation", &["Kana Hanazawa"], 255);
+        let (ids, best) = ranked_ids(
+            &track,
+            vec![
+                beatmapset(1, "Kana Hanazawa", "Renai Circulation", 255),
+                beatmapset(2, "Kana Hanazawa", "Renai Circulation", 255),
+            ],
+        );
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(best, Some(1));
+    }
+
+    #[test]
+    fn collaboration_artist_matches_individual_artist() {
+        let track = track("Song", &["Someone", "Other"], 200);
+        let score = score_beatmapset(&track, &beatmapset(1, "Someone feat. Other", "Song", 200));
+        assert!(score.artist >= 0.9, "artist score {}", score.artist);
+    }
+
+    #[test]
+    fn unicode_title_matches_original_language_track() {
+        let track = track("恋愛サーキュレーション", &["花澤香菜"], 255);
+        let mut beatmapset = beatmapset(1, "Kana Hanazawa", "Renai Circulation", 255);
+        beatmapset.artist_unicode = "花澤香菜".to_string();
+        beatmapset.title_unicode = "恋愛サーキュレーション".to_string();
+        let score = score_beatmapset(&track, &beatmapset);
+        assert_eq!(score.title, 1.0);
+        assert_eq!(score.artist, 1.0);
+    }
+
+    #[test]
+    fn score_song_separates_good_and_bad_matches_around_thresholds() {
+        let track = track("Renai Circulation", &["Kana Hanazawa"], 255);
+        let good = score_song(
+            &track,
+            &SongFields {
+                artist: "Kana Hanazawa",
+                artist_unicode: "花澤香菜",
+                title: "Renai Circulation (TV Size)",
+                title_unicode: "恋愛サーキュレーション",
+                length_secs: Some(256),
+            },
+        );
+        let bad = score_song(
+            &track,
+            &SongFields {
+                artist: "Camellia",
+                artist_unicode: "",
+                title: "Exit This Earth's Atomosphere",
+                title_unicode: "",
+                length_secs: Some(410),
+            },
+        );
+
+        assert!(
+            good.confidence >= MIN_AUTO_ADD_CONFIDENCE,
+            "good confidence {}",
+            good.confidence
+        );
+        assert!(
+            bad.confidence < MIN_QUEUE_CONFIDENCE,
+            "bad confidence {}",
+            bad.confidence
+        );
+    }
+
+    #[test]
+    fn score_song_ignores_length_when_unknown() {
+        let track = track("Renai Circulation", &["Kana Hanazawa"], 255);
+        let score = score_song(
+            &track,
+            &SongFields {
+                artist: "Kana Hanazawa",
+                artist_unicode: "",
+                title: "Renai Circulation",
+                title_unicode: "",
+                length_secs: None,
+            },
+        );
+        assert_eq!(score.length, None);
+        assert_eq!(score.confidence, 1.0);
+    }
+
+    #[test]
+    fn best_track_for_song_picks_closest_track() {
+        let tracks = vec![
+            track("Renai Circulation (Instrumental)", &["Kana Hanazawa"], 255),
+            track("Renai Circulation", &["Kana Hanazawa"], 255),
+            track("Renai Circulation", &["Kana Hanazawa"], 255),
+        ];
+        let song = SongFields {
+            artist: "Kana Hanazawa",
+            artist_unicode: "",
+            title: "Renai Circulation",
+            title_unicode: "",
+            length_secs: Some(255),
+        };
+        let (index, score) = best_track_for_song(&tracks, &song).unwrap();
+        // 分數相同時取 API 較前面的結果
+        assert_eq!(index, 1);
+        assert_eq!(score.confidence, 1.0);
+        assert!(best_track_for_song(&[], &song).is_none());
+    }
+}
@@ -0,0 +1,582 @@
+// 標準庫導入
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 第三方庫導入
+use log::{error, info, warn};
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+// 本地模組導入
+use crate::{check_and_refresh_token, is_token_valid, Config, LoginInfo};
+
+// 未在設定檔指定時依序嘗試的鏡像站
+pub const DEFAULT_MIRRORS: [&str; 5] = ["nerinyan", "catboy", "osudirect", "sayobot", "official"];
+// 連續失敗這麼多次後暫時排到最後
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+// 排到最後的時間，過後再照原本順序嘗試
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(300);
+
+// 下載的譜面集版本，無影片版本小很多
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DownloadVariant {
+    #[default]
+    Full,
+    NoVideo,
+}
+
+impl DownloadVariant {
+    pub const ALL: [DownloadVariant; 2] = [DownloadVariant::Full, DownloadVariant::NoVideo];
+    // 官網下載無影片版本時檔名也帶這個標記，本機譜面靠它判斷版本
+    const NO_VIDEO_MARKER: &'static str = "[no video]";
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DownloadVariant::Full => "完整",
+            DownloadVariant::NoVideo => "無影片",
+        }
+    }
+
+    pub fn from_file_name(file_name: &str) -> Self {
+        if file_name.to_lowercase().contains(Self::NO_VIDEO_MARKER) {
+            DownloadVariant::NoVideo
+        } else {
+            DownloadVariant::Full
+        }
+    }
+
+    // 確保檔名帶有版本標記，例如 "123 Artist - Title [no video].osz"
+    pub fn apply_to_file_name(&self, file_name: String) -> String {
+        if *self == DownloadVariant::Full || Self::from_file_name(&file_name) == *self {
+            return file_name;
+        }
+        let stem = file_name
+            .strip_suffix(".osz")
+            .or_else(|| file_name.strip_suffix(".OSZ"))
+            .unwrap_or(&file_name);
+        format!("{} {}.osz", stem, Self::NO_VIDEO_MARKER)
+    }
+}
+
+// 提供 .osz 下載的來源
+pub trait BeatmapMirror: Send + Sync {
+    // 設定檔中使用的名稱
+    fn name(&self) -> &'static str;
+
+    fn download_url(&self, beatmapset_id: i32, variant: DownloadVariant) -> String;
+
+    // 預設只提供完整版本，支援其他版本的鏡像站需覆寫
+    fn supports_variant(&self, variant: DownloadVariant) -> bool {
+        variant == DownloadVariant::Full
+    }
+
+    // 需要額外標頭或授權的鏡像站可覆寫
+    fn prepare_request(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+    }
+
+    // 目前是否能使用，例如官方下載需要登入
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+pub struct NerinyanMirror;
+
+impl BeatmapMirror for NerinyanMirror {
+    fn name(&self) -> &'static str {
+        "nerinyan"
+    }
+
+    fn download_url(&self, beatmapset_id: i32, variant: DownloadVariant) -> String {
+        match variant {
+            DownloadVariant::Full => format!("https://api.nerinyan.moe/d/{}", beatmapset_id),
+            DownloadVariant::NoVideo => {
+                format!("https://api.nerinyan.moe/d/{}?nv=1", beatmapset_id)
+            }
+        }
+    }
+
+    fn supports_variant(&self, _variant: DownloadVariant) -> bool {
+        true
+    }
+
+    fn prepare_request(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+            .header("Accept", "application/x-osu-beatmap-archive")
+            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
+            .header("Origin", "https://osu.ppy.sh")
+    }
+}
+
+pub struct CatboyMirror;
+
+impl BeatmapMirror for CatboyMirror {
+    fn name(&self) -> &'static str {
+        "catboy"
+    }
+
+    fn download_url(&self, beatmapset_id: i32, variant: DownloadVariant) -> String {
+        match variant {
+            DownloadVariant::Full => format!("https://catboy.best/d/{}", beatmapset_id),
+            DownloadVariant::NoVideo => format!("https://catboy.best/d/{}n", beatmapset_id),
+        }
+    }
+
+    fn supports_variant(&self, _variant: DownloadVariant) -> bool {
+        true
+    }
+}
+
+pub struct OsuDirectMirror;
+
+impl BeatmapMirror for OsuDirectMirror {
+    fn name(&self) -> &'static str {
+        "osudirect"
+    }
+
+    fn download_url(&self, beatmapset_id: i32, _variant: DownloadVariant) -> String {
+        format!("https://osu.direct/api/d/{}", beatmapset_id)
+    }
+}
+
+pub struct SayobotMirror;
+
+impl BeatmapMirror for SayobotMirror {
+    fn name(&self) -> &'static str {
+        "sayobot"
+    }
+
+    fn download_url(&self, beatmapset_id: i32, variant: DownloadVariant) -> String {
+        let kind = match variant {
+            DownloadVariant::Full => "full",
+            DownloadVariant::NoVideo => "novideo",
+        };
+        format!(
+            "https://dl.sayobot.cn/beatmaps/download/{}/{}",
+            kind, beatmapset_id
+        )
+    }
+
+    fn supports_variant(&self, _variant: DownloadVariant) -> bool {
+        true
+    }
+}
+
+// 官方下載使用的 osu! 登入資訊，由 MirrorPool 持有並在登入狀態改變時更新
+pub type OsuLogin = Arc<Mutex<Option<LoginInfo>>>;
+
+// 官方下載端點，需要使用者登入 osu! 後的 token
+pub struct OfficialMirror {
+    login: OsuLogin,
+}
+
+impl OfficialMirror {
+    pub fn new(login: OsuLogin) -> Self {
+        Self { login }
+    }
+
+    fn access_token(&self) -> Option<String> {
+        self.login
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|login_info| login_info.access_token.clone())
+    }
+}
+
+impl BeatmapMirror for OfficialMirror {
+    fn name(&self) -> &'static str {
+        "official"
+    }
+
+    fn download_url(&self, beatmapset_id: i32, variant: DownloadVariant) -> String {
+        let url = format!(
+            "https://osu.ppy.sh/api/v2/beatmapsets/{}/download",
+            beatmapset_id
+        );
+        match variant {
+            DownloadVariant::Full => url,
+            DownloadVariant::NoVideo => format!("{}?noVideo=1", url),
+        }
+    }
+
+    fn supports_variant(&self, _variant: DownloadVariant) -> bool {
+        true
+    }
+
+    fn prepare_request(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.access_token() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    // token 過期時由 MirrorPool::refresh_osu_login 在下載前更新，這裡只看是否登入
+    fn is_available(&self) -> bool {
+        self.login.lock().unwrap().is_some()
+    }
+}
+
+pub fn mirror_from_name(name: &str, osu_login: &OsuLogin) -> Option<Arc<dyn BeatmapMirror>> {
+    let mirror: Arc<dyn BeatmapMirror> = match name.trim().to_lowercase().as_str() {
+        "nerinyan" => Arc::new(NerinyanMirror),
+        "catboy" | "mino" => Arc::new(CatboyMirror),
+        "osudirect" | "osu.direct" => Arc::new(OsuDirectMirror),
+        "sayobot" => Arc::new(SayobotMirror),
+        "official" | "osu" => Arc::new(OfficialMirror::new(osu_login.clone())),
+        _ => return None,
+    };
+    Some(mirror)
+}
+
+// 單一鏡像站的下載統計
+#[derive(Debug, Clone, Default)]
+pub struct MirrorHealth {
+    pub successes: u32,
+    pub failures: u32,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_latency: Option<Duration>,
+    pub last_failure: Option<Instant>,
+    total_latency: Duration,
+}
+
+impl MirrorHealth {
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.successes > 0).then(|| self.total_latency / self.successes)
+    }
+
+    // 連續失敗太多次且還在冷卻中
+    pub fn is_cooling_down(&self) -> bool {
+        self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+            && self
+                .last_failure
+                .is_some_and(|at| at.elapsed() < FAILURE_COOLDOWN)
+    }
+}
+
+// 依設定順序排列的鏡像站，記錄各自的健康狀態
+pub struct MirrorPool {
+    mirrors: Vec<Arc<dyn BeatmapMirror>>,
+    health: Mutex<HashMap<&'static str, MirrorHealth>>,
+    osu_login: OsuLogin,
+    // 同時開始的下載只刷新一次 token，osu! 的 refresh token 用過就會失效
+    osu_refresh: tokio::sync::Mutex<()>,
+}
+
+impl MirrorPool {
+    // osu_login 要與 mirrors 中的 OfficialMirror 共用，登入狀態才會同步
+    pub fn new(mirrors: Vec<Arc<dyn BeatmapMirror>>, osu_login: OsuLogin) -> Self {
+        Self {
+            mirrors,
+            health: Mutex::new(HashMap::new()),
+            osu_login,
+            osu_refresh: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    // 從設定檔的鏡像站名稱建立，未知名稱會被略過
+    pub fn from_names(names: &[String]) -> Self {
+        let osu_login = OsuLogin::default();
+        let mut mirrors: Vec<Arc<dyn BeatmapMirror>> = Vec::new();
+        for name in names {
+            match mirror_from_name(name, &osu_login) {
+                Some(mirror) if !mirrors.iter().any(|m| m.name() == mirror.name()) => {
+                    mirrors.push(mirror)
+                }
+                Some(_) => {}
+                None => warn!("未知的鏡像站: {}", name),
+            }
+        }
+        if mirrors.is_empty() {
+            error!("沒有可用的鏡像站設定，改用預設順序");
+            mirrors = DEFAULT_MIRRORS
+                .iter()
+                .filter_map(|name| mirror_from_name(name, &osu_login))
+                .collect();
+        }
+        info!(
+            "鏡像站順序: {:?}",
+            mirrors.iter().map(|m| m.name()).collect::<Vec<_>>()
+        );
+        Self::new(mirrors, osu_login)
+    }
+
+    // 登入、登出或刷新 osu! token 後呼叫，官方下載才會使用最新的 token
+    pub fn set_osu_login(&self, login_info: Option<LoginInfo>) {
+        *self.osu_login.lock().unwrap() = login_info;
+    }
+
+    // 下載前確認官方下載的 token 未過期，過期時以 refresh token 更新
+    pub async fn refresh_osu_login(&self, client: &Client, config: &Config) {
+        let _guard = self.osu_refresh.lock().await;
+        let expired = self
+            .osu_login
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|login_info| !is_token_valid(login_info));
+        if !expired {
+            return;
+        }
+        match check_and_refresh_token(client, config, "osu").await {
+            Ok(login_info) => {
+                info!("已刷新官方下載使用的 osu! token");
+                self.set_osu_login(Some(login_info));
+            }
+            // 保留舊的 token，官方下載失敗時會記錄在鏡像站狀態中
+            Err(e) => warn!("無法刷新官方下載使用的 osu! token: {}", e),
+        }
+    }
+
+    // 本次下載要嘗試的順序與版本：可用的鏡像站依設定順序，冷卻中的排到最後；
+    // 要求的版本沒有鏡像站提供時，最後改用其他鏡像站下載完整版本
+    pub fn candidates(
+        &self,
+        variant: DownloadVariant,
+    ) -> Vec<(Arc<dyn BeatmapMirror>, DownloadVariant)> {
+        let health = self.health.lock().unwrap();
+        let (cooling, healthy): (Vec<_>, Vec<_>) = self
+            .mirrors
+            .iter()
+            .filter(|mirror| mirror.is_available())
+            .cloned()
+            .partition(|mirror| {
+                health
+                    .get(mirror.name())
+                    .is_some_and(|h| h.is_cooling_down())
+            });
+        let (supported, fallback): (Vec<_>, Vec<_>) = healthy
+            .into_iter()
+            .chain(cooling)
+            .partition(|mirror| mirror.supports_variant(variant));
+        supported
+            .into_iter()
+            .map(|mirror| (mirror, variant))
+            .chain(
+                fallback
+                    .into_iter()
+                    .map(|mirror| (mirror, DownloadVariant::Full)),
+            )
+            .collect()
+    }
+
+    pub fn record_success(&self, mirror: &dyn BeatmapMirror, latency: Duration) {
+        let mut health = self.health.lock().unwrap();
+        let entry = health.entry(mirror.name()).or_default();
+        entry.successes += 1;
+        entry.consecutive_failures = 0;
+        entry.last_latency = Some(latency);
+        entry.total_latency += latency;
+    }
+
+    pub fn record_failure(&self, mirror: &dyn BeatmapMirror, error: String) {
+        let mut health = self.health.lock().unwrap();
+        let entry = health.entry(mirror.name()).or_default();
+        entry.failures += 1;
+        entry.consecutive_failures += 1;
+        entry.last_failure = Some(Instant::now());
+        entry.last_error = Some(error);
+    }
+
+    // 給 UI 顯示用，依設定順序回傳
+    pub fn health_snapshot(&self) -> Vec<(&'static str, bool, MirrorHealth)> {
+        let health = self.health.lock().unwrap();
+        self.mirrors
+            .iter()
+            .map(|mirror| {
+                (
+                    mirror.name(),
+                    mirror.is_available(),
+                    health.get(mirror.name()).cloned().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for MirrorPool {
+    fn default() -> Self {
+        Self::from_names(&DEFAULT_MIRRORS.map(String::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn pool(names: &[&str]) -> MirrorPool {
+        MirrorPool::from_names(
+            &names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn names(
+        candidates: &[(Arc<dyn BeatmapMirror>, DownloadVariant)],
+    ) -> Vec<(&'static str, DownloadVariant)> {
+        candidates
+            .iter()
+            .map(|(mirror, variant)| (mirror.name(), *variant))
+            .collect()
+    }
+
+    fn mirror<'a>(pool: &'a MirrorPool, name: &str) -> &'a dyn BeatmapMirror {
+        pool.mirrors
+            .iter()
+            .find(|mirror| mirror.name() == name)
+            .unwrap()
+            .as_ref()
+    }
+
+    fn osu_login(expires_in: chrono::Duration) -> LoginInfo {
+        LoginInfo {
+            platform: "osu".to_string(),
+            access_token: "token".to_string(),
+            refresh_token: "refresh".to_string(),
+            expiry_time: Utc::now() + expires_in,
+            avatar_url: None,
+            user_name: None,
+        }
+    }
+
+    #[test]
+    fn from_names_skips_unknown_and_duplicate_mirrors() {
+        let pool = pool(&["Catboy", "unknown", "mino", "sayobot"]);
+
+        let order: Vec<_> = pool.mirrors.iter().map(|mirror| mirror.name()).collect();
+        assert_eq!(order, ["catboy", "sayobot"]);
+    }
+
+    #[test]
+    fn from_names_falls_back_to_default_order() {
+        let pool = pool(&["unknown"]);
+
+        let order: Vec<_> = pool.mirrors.iter().map(|mirror| mirror.name()).collect();
+        assert_eq!(order, DEFAULT_MIRRORS);
+    }
+
+    #[test]
+    fn candidates_fall_back_to_full_when_variant_unsupported() {
+        let pool = pool(&["osudirect", "catboy", "sayobot"]);
+
+        assert_eq!(
+            names(&pool.candidates(DownloadVariant::NoVideo)),
+            [
+                ("catboy", DownloadVariant::NoVideo),
+                ("sayobot", DownloadVariant::NoVideo),
+                ("osudirect", DownloadVariant::Full),
+            ]
+        );
+    }
+
+    #[test]
+    fn cooling_down_mirrors_move_to_the_end() {
+        let pool = pool(&["nerinyan", "catboy", "osudirect"]);
+
+        for _ in 0..MAX_CONSECUTIVE_FAILURES - 1 {
+            pool.record_failure(mirror(&pool, "nerinyan"), "timeout".to_string());
+        }
+        assert_eq!(
+            names(&pool.candidates(DownloadVariant::Full))[0],
+            ("nerinyan", DownloadVariant::Full)
+        );
+
+        pool.record_failure(mirror(&pool, "nerinyan"), "timeout".to_string());
+        assert_eq!(
+            names(&pool.candidates(DownloadVariant::Full)),
+            [
+                ("catboy", DownloadVariant::Full),
+                ("osudirect", DownloadVariant::Full),
+                ("nerinyan", DownloadVariant::Full),
+            ]
+        );
+
+        // 成功一次就恢復原本的順序
+        pool.record_success(mirror(&pool, "nerinyan"), Duration::from_millis(300));
+        assert_eq!(
+            names(&pool.candidates(DownloadVariant::Full))[0],
+            ("nerinyan", DownloadVariant::Full)
+        );
+    }
+
+    #[test]
+    fn cooldown_expires_after_failure_cooldown() {
+        let mut health = MirrorHealth {
+            consecutive_failures: MAX_CONSECUTIVE_FAILURES,
+            last_failure: Some(Instant::now()),
+            ..MirrorHealth::default()
+        };
+        assert!(health.is_cooling_down());
+
+        health.last_failure = Instant::now().checked_sub(FAILURE_COOLDOWN);
+        assert!(!health.is_cooling_down());
+    }
+
+    #[test]
+    fn health_snapshot_records_latency_and_errors() {
+        let pool = pool(&["catboy", "sayobot"]);
+        pool.record_success(mirror(&pool, "catboy"), Duration::from_millis(100));
+        pool.record_success(mirror(&pool, "catboy"), Duration::from_millis(300));
+        pool.record_failure(mirror(&pool, "sayobot"), "404".to_string());
+
+        let snapshot = pool.health_snapshot();
+
+        assert_eq!(snapshot[0].0, "catboy");
+        assert_eq!(snapshot[0].2.successes, 2);
+        assert_eq!(
+            snapshot[0].2.average_latency(),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(snapshot[1].2.failures, 1);
+        assert_eq!(snapshot[1].2.last_error.as_deref(), Some("404"));
+        assert_eq!(snapshot[1].2.average_latency(), None);
+    }
+
+    #[test]
+    fn official_mirror_follows_osu_login() {
+        let pool = pool(&["official", "catboy"]);
+        assert_eq!(
+            names(&pool.candidates(DownloadVariant::Full)),
+            [("catboy", DownloadVariant::Full)]
+        );
+
+        // 過期的 token 仍然列入，下載前會先刷新
+        pool.set_osu_login(Some(osu_login(chrono::Duration::minutes(-5))));
+        assert_eq!(
+            names(&pool.candidates(DownloadVariant::Full))[0],
+            ("official", DownloadVariant::Full)
+        );
+
+        pool.set_osu_login(None);
+        assert!(!pool.health_snapshot()[0].1);
+    }
+
+    #[tokio::test]
+    async fn refresh_osu_login_keeps_valid_token() {
+        let pool = pool(&["official"]);
+        let login = osu_login(chrono::Duration::hours(1));
+        pool.set_osu_login(Some(login));
+
+        let config: Config = serde_json::from_str(
+            r#"{"spotify": {"client_id": "", "client_secret": ""},
+                "osu": {"client_id": "", "client_secret": ""}}"#,
+        )
+        .unwrap();
+        // token 未過期時不會讀取登入資訊或連線
+        pool.refresh_osu_login(&Client::new(), &config).await;
+
+        let official = mirror(&pool, "official");
+        let request = official.prepare_request(Client::new().get("http://localhost/"));
+        let request = request.build().unwrap();
+        assert_eq!(
+            request.headers()["authorization"].to_str().unwrap(),
+            "Bearer token"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// 第三方庫導入
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+lazy_static! {
+    // 括號內的版本標記，例如 (TV Size)、[Cut Ver.]、-TV ver.-、（ゲームサイズ）
+    static ref VERSION_TAG_REGEX: Regex = Regex::new(
+        r"(?i)[(\[【〔（\-~～]\s*(tv\s*(size|ver\.?|version|edit)|(cut|short|game|movie|anime|op|ed)\s*(size|ver\.?|version|edit)|full\s*(ver\.?|version)|ゲームサイズ|テレビサイズ|ショートバージョン)\s*[)\]】〕）\-~～]"
+    )
+    .expect("Failed to compile version tag regex");
+    // 括號內的客演標記，例如 (feat. XXX)、[ft. XXX]
+    static ref BRACKET_FEAT_REGEX: Regex =
+        Regex::new(r"(?i)[(\[【（]\s*(feat\.?|ft\.?|featuring)\s[^)\]】）]*[)\]】）]")
+            .expect("Failed to compile bracket feat regex");
+    // 未加括號的客演標記，從 feat. 開始到字串結尾
+    static ref TRAILING_FEAT_REGEX: Regex =
+        Regex::new(r"(?i)\s(feat\.?|ft\.?|featuring)\s.*$")
+            .expect("Failed to compile trailing feat regex");
+    // 藝人欄位中的客演、合作分隔字
+    static ref ARTIST_SEPARATOR_REGEX: Regex =
+        Regex::new(r"(?i)\s(feat\.?|ft\.?|featuring|vs\.?|x|×|&|and|with)\s|[,、/]")
+            .expect("Failed to compile artist separator regex");
+}
+
+// 移除曲名中的版本與客演標記，保留原本的大小寫，用於組成搜尋字串
+pub fn strip_title_tags(title: &str) -> String {
+    let title = VERSION_TAG_REGEX.replace_all(title, " ");
+    let title = BRACKET_FEAT_REGEX.replace_all(&title, " ");
+    let title = TRAILING_FEAT_REGEX.replace(&title, "");
+    collapse_whitespace(&title)
+}
+
+// 將藝人欄位中的合作分隔字替換成空白，用於組成搜尋字串
+pub fn strip_artist_tags(artist: &str) -> String {
+    collapse_whitespace(&ARTIST_SEPARATOR_REGEX.replace_all(artist, " "))
+}
+
+// 比對用的曲名：去除標記後做全形/半形與大小寫折疊
+pub fn normalize_title(title: &str) -> String {
+    fold(&strip_title_tags(&fold_width(title)))
+}
+
+// 比對用的藝人名稱
+pub fn normalize_artist(artist: &str) -> String {
+    fold(&strip_artist_tags(&fold_width(artist)))
+}
+
+// NFKC 會把全形英數、半形片假名等相容字元轉成標準形式
+fn fold_width(s: &str) -> String {
+    s.nfkc().collect()
+}
+
+// 轉小寫並只保留文字與數字，其餘字元視為分隔
+fn fold(s: &str) -> String {
+    let folded: String = s
+        .chars()
+        .flat_map(|c| {
+            if c.is_alphanumeric() {
+                c.to_lowercase().collect::<Vec<_>>()
+            } else {
+                vec![' ']
+            }
+        })
+        .collect();
+    collapse_whitespace(&folded)
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_version_tags() {
+        assert_eq!(
+            strip_title_tags("Renai Circulation (TV Size)"),
+            "Renai Circulation"
+        );
+        assert_eq!(
+            strip_title_tags("Renai Circulation [Cut Ver.]"),
+            "Renai Circulation"
+        );
+        assert_eq!(
+            strip_title_tags("Renai Circulation -TV ver.-"),
+            "Renai Circulation"
+        );
+        assert_eq!(
+            strip_title_tags("恋愛サーキュレーション（ゲームサイズ）"),
+            "恋愛サーキュレーション"
+        );
+        assert_eq!(strip_title_tags("Song (Full Version)"), "Song");
+    }
+
+    #[test]
+    fn strips_feat_tags() {
+        assert_eq!(strip_title_tags("Song (feat. Someone)"), "Song");
+        assert_eq!(strip_title_tags("Song [ft. Someone]"), "Song");
+        assert_eq!(strip_title_tags("Song feat. Someone & Other"), "Song");
+        // 沒有標記的括號要保留
+        assert_eq!(strip_title_tags("Song (Remix)"), "Song (Remix)");
+    }
+
+    #[test]
+    fn splits_artist_separators() {
+        assert_eq!(strip_artist_tags("A feat. B"), "A B");
+        assert_eq!(strip_artist_tags("A x B & C"), "A B C");
+        assert_eq!(strip_artist_tags("A, B、C/D"), "A B C D");
+        // 名字中間的 x 不是分隔字
+        assert_eq!(strip_artist_tags("xi"), "xi");
+    }
+
+    #[test]
+    fn folds_full_width_to_half_width() {
+        assert_eq!(
+            normalize_title("ＲＥＮＡＩ　Ｃｉｒｃｕｌａｔｉｏｎ"),
+            "renai circulation"
+        );
+        assert_eq!(normalize_title("ｶﾀｶﾅ"), "カタカナ");
+        assert_eq!(normalize_artist("ＹＯＡＳＯＢＩ"), "yoasobi");
+    }
+
+    #[test]
+    fn normalize_title_drops_tags_and_punctuation() {
+        assert_eq!(
+            normalize_title("Renai Circulation (TV Size) feat. Someone"),
+            "renai circulation"
+        );
+        assert_eq!(normalize_title("Don't Say \"Lazy\"!"), "don t say lazy");
+        assert_eq!(
+            normalize_title("Renai Circulation（ＴＶ Ｓｉｚｅ）"),
+            normalize_title("renai circulation")
+        );
+    }
+}
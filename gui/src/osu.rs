@@ -1,24 +1,33 @@
 //標準庫導入
+use std::cmp::Reverse;
 use std::sync::Arc;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
-use std::io::{copy,Cursor};
+use std::io::{Cursor,Read};
+use std::time::{Duration, Instant};
 use std::fs::File;
 
 
 
 // 第三方庫導入
 use anyhow::Result;
+use chrono::Utc;
 use egui::{ColorImage, TextureHandle};
 use image::load_from_memory;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 use regex::Regex;
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
 
 use thiserror::Error;
 
-use tokio::{sync::mpsc::Sender, try_join,task};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex as TokioMutex;
+use tokio::{sync::mpsc::Sender, task};
+use url::Url;
 
 use rodio::{Decoder, Sink, OutputStreamHandle};
 
@@ -26,11 +35,38 @@ use rodio::{Decoder, Sink, OutputStreamHandle};
 
 // 本地模組導入
 
+use crate::download_settings::BandwidthLimiter;
+use crate::mirror::{BeatmapMirror, DownloadVariant, MirrorPool};
 use crate::read_config;
+use crate::spotify::{self, AuthStatus};
 use crate::DownloadStatus;
-
-
-#[derive(Debug, Deserialize, Clone)]
+use crate::{
+    open_url_default_browser, save_platform_login_info, AuthManager, AuthPlatform, LoginInfo,
+    ServiceConfig,
+};
+
+// 等待鏡像站開始回應的時間，太慢就換下一個
+const MIRROR_RESPONSE_TIMEOUT: Duration = Duration::from_secs(15);
+// 下載途中這段時間都沒收到資料就視為中斷
+const MIRROR_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+// 同一個鏡像站中斷後最多續傳幾次
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+// 回報下載進度的間隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// 下載中的暫存檔副檔名
+pub const PARTIAL_DOWNLOAD_EXTENSION: &str = ".part";
+pub const OSU_BASE_URL: &str = "https://osu.ppy.sh";
+pub const OSU_TOKEN_URL: &str = "https://osu.ppy.sh/oauth/token";
+const OSU_AUTHORIZE_URL: &str = "https://osu.ppy.sh/oauth/authorize";
+// 使用者登入時要求的權限，identify 用來讀取 /me
+pub const OSU_USER_SCOPE: &str = "identify public";
+// 等待使用者在瀏覽器完成授權的時間
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(180);
+// 使用者譜面清單一次讀取的數量（API 上限）
+const USER_LIST_LIMIT: u32 = 100;
+
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Covers {
     pub cover: Option<String>,
     pub cover_2x: Option<String>,
@@ -41,26 +77,61 @@ pub struct Covers {
     pub slimcover: Option<String>,
     pub slimcover_2x: Option<String>,
 }
-#[derive(Debug, Deserialize, Clone)] // 添加 Clone
+#[derive(Debug, Deserialize, Serialize, Clone)] // 添加 Clone
 pub struct Beatmapset {
     pub beatmaps: Vec<Beatmap>,
     pub id: i32,
     pub artist: String,
+    #[serde(default)]
+    pub artist_unicode: String,
     pub title: String,
+    #[serde(default)]
+    pub title_unicode: String,
     pub creator: String,
     pub covers: Covers,
     pub preview_url: Option<String>,
+    // 是否附有背景影片，沒有的話不必提供無影片版本
+    #[serde(default)]
+    pub video: bool,
 }
 #[derive(Deserialize)]
 pub struct TokenResponse {
     access_token: String,
 }
 
+// authorization code 換得的使用者令牌
+#[derive(Deserialize)]
+struct UserTokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+}
+
+// /me 回傳的使用者資料，只取需要的欄位
+#[derive(Debug, Deserialize, Clone)]
+pub struct OsuUser {
+    pub id: i64,
+    pub username: String,
+    pub avatar_url: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SearchResponse {
     beatmapsets: Vec<Beatmapset>,
+    // 下一頁的游標，沒有更多結果時為 null
+    cursor_string: Option<String>,
+    #[serde(default)]
+    total: u32,
 }
-#[derive(Debug, Deserialize, Clone)]
+
+// 單頁的 osu! 搜尋結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BeatmapsetPage {
+    pub beatmapsets: Vec<Beatmapset>,
+    pub cursor_string: Option<String>,
+    pub total: u32,
+}
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Beatmap {
     pub difficulty_rating: f32,
     pub id: i32,
@@ -95,6 +166,8 @@ pub enum OsuError {
     ApiError(String),
     #[error("reqwest 錯誤: {0}")]
     ReqwestError(reqwest::Error),
+    #[error("譜面檔解析錯誤: {0}")]
+    ParseError(String),
     #[error("其他錯誤: {0}")]
     Other(String),
 }
@@ -102,15 +175,335 @@ pub enum OsuError {
 
 
 
+// 譜面模式（osu! API 的 m 參數）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OsuMode {
+    Osu,
+    Taiko,
+    Catch,
+    Mania,
+}
+
+impl OsuMode {
+    pub const ALL: [OsuMode; 4] = [OsuMode::Osu, OsuMode::Taiko, OsuMode::Catch, OsuMode::Mania];
+
+    // .osu 檔 [General] 區段中 Mode 的數值
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            OsuMode::Osu => "0",
+            OsuMode::Taiko => "1",
+            OsuMode::Catch => "2",
+            OsuMode::Mania => "3",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OsuMode::Osu => "osu!",
+            OsuMode::Taiko => "osu!taiko",
+            OsuMode::Catch => "osu!catch",
+            OsuMode::Mania => "osu!mania",
+        }
+    }
+}
+
+// 譜面狀態（osu! API 的 s 參數）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RankedStatus {
+    Any,
+    Ranked,
+    Qualified,
+    Loved,
+    Pending,
+    Graveyard,
+}
+
+impl RankedStatus {
+    pub const ALL: [RankedStatus; 6] = [
+        RankedStatus::Any,
+        RankedStatus::Ranked,
+        RankedStatus::Qualified,
+        RankedStatus::Loved,
+        RankedStatus::Pending,
+        RankedStatus::Graveyard,
+    ];
+
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            RankedStatus::Any => "any",
+            RankedStatus::Ranked => "ranked",
+            RankedStatus::Qualified => "qualified",
+            RankedStatus::Loved => "loved",
+            RankedStatus::Pending => "pending",
+            RankedStatus::Graveyard => "graveyard",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RankedStatus::Any => "全部",
+            RankedStatus::Ranked => "Ranked",
+            RankedStatus::Qualified => "Qualified",
+            RankedStatus::Loved => "Loved",
+            RankedStatus::Pending => "Pending",
+            RankedStatus::Graveyard => "Graveyard",
+        }
+    }
+}
+
+// 曲風（osu! API 的 g 參數，數值對應 osu-web 的 genre id）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Genre {
+    Unspecified,
+    VideoGame,
+    Anime,
+    Rock,
+    Pop,
+    Other,
+    Novelty,
+    HipHop,
+    Electronic,
+    Metal,
+    Classical,
+    Folk,
+    Jazz,
+}
+
+impl Genre {
+    pub const ALL: [Genre; 13] = [
+        Genre::Unspecified,
+        Genre::VideoGame,
+        Genre::Anime,
+        Genre::Rock,
+        Genre::Pop,
+        Genre::Other,
+        Genre::Novelty,
+        Genre::HipHop,
+        Genre::Electronic,
+        Genre::Metal,
+        Genre::Classical,
+        Genre::Folk,
+        Genre::Jazz,
+    ];
+
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            Genre::Unspecified => "1",
+            Genre::VideoGame => "2",
+            Genre::Anime => "3",
+            Genre::Rock => "4",
+            Genre::Pop => "5",
+            Genre::Other => "6",
+            Genre::Novelty => "7",
+            Genre::HipHop => "9",
+            Genre::Electronic => "10",
+            Genre::Metal => "11",
+            Genre::Classical => "12",
+            Genre::Folk => "13",
+            Genre::Jazz => "14",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Genre::Unspecified => "未指定",
+            Genre::VideoGame => "遊戲",
+            Genre::Anime => "動畫",
+            Genre::Rock => "搖滾",
+            Genre::Pop => "流行",
+            Genre::Other => "其他",
+            Genre::Novelty => "新奇",
+            Genre::HipHop => "嘻哈",
+            Genre::Electronic => "電子",
+            Genre::Metal => "金屬",
+            Genre::Classical => "古典",
+            Genre::Folk => "民謠",
+            Genre::Jazz => "爵士",
+        }
+    }
+}
+
+// 語言（osu! API 的 l 參數，數值對應 osu-web 的 language id）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    Unspecified,
+    English,
+    Japanese,
+    Chinese,
+    Instrumental,
+    Korean,
+    French,
+    German,
+    Swedish,
+    Spanish,
+    Italian,
+    Russian,
+    Polish,
+    Other,
+}
+
+impl Language {
+    pub const ALL: [Language; 14] = [
+        Language::Unspecified,
+        Language::English,
+        Language::Japanese,
+        Language::Chinese,
+        Language::Instrumental,
+        Language::Korean,
+        Language::French,
+        Language::German,
+        Language::Swedish,
+        Language::Spanish,
+        Language::Italian,
+        Language::Russian,
+        Language::Polish,
+        Language::Other,
+    ];
+
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            Language::Unspecified => "1",
+            Language::English => "2",
+            Language::Japanese => "3",
+            Language::Chinese => "4",
+            Language::Instrumental => "5",
+            Language::Korean => "6",
+            Language::French => "7",
+            Language::German => "8",
+            Language::Swedish => "9",
+            Language::Spanish => "10",
+            Language::Italian => "11",
+            Language::Russian => "12",
+            Language::Polish => "13",
+            Language::Other => "14",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::Unspecified => "未指定",
+            Language::English => "英文",
+            Language::Japanese => "日文",
+            Language::Chinese => "中文",
+            Language::Instrumental => "純音樂",
+            Language::Korean => "韓文",
+            Language::French => "法文",
+            Language::German => "德文",
+            Language::Swedish => "瑞典文",
+            Language::Spanish => "西班牙文",
+            Language::Italian => "義大利文",
+            Language::Russian => "俄文",
+            Language::Polish => "波蘭文",
+            Language::Other => "其他",
+        }
+    }
+}
+
+// osu! 譜面搜尋條件，None 表示不限制
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BeatmapSearchFilter {
+    pub mode: Option<OsuMode>,
+    pub status: Option<RankedStatus>,
+    pub min_stars: Option<f32>,
+    pub max_stars: Option<f32>,
+    pub genre: Option<Genre>,
+    pub language: Option<Language>,
+    pub nsfw: bool,
+    pub has_video: bool,
+    pub has_storyboard: bool,
+}
+
+impl BeatmapSearchFilter {
+    // 轉換成 osu! API v2 beatmapsets/search 的查詢參數
+    pub fn to_query_params(&self, song_name: &str) -> Vec<(&'static str, String)> {
+        // 星級沒有獨立參數，需寫進查詢字串（例如 stars>=4 stars<=6）
+        let mut query = song_name.to_string();
+        if let Some(min_stars) = self.min_stars {
+            query.push_str(&format!(" stars>={:.2}", min_stars));
+        }
+        if let Some(max_stars) = self.max_stars {
+            query.push_str(&format!(" stars<={:.2}", max_stars));
+        }
+
+        let mut params = vec![("query", query.trim().to_string())];
+        if let Some(mode) = self.mode {
+            params.push(("m", mode.api_value().to_string()));
+        }
+        if let Some(status) = self.status {
+            params.push(("s", status.api_value().to_string()));
+        }
+        if let Some(genre) = self.genre {
+            params.push(("g", genre.api_value().to_string()));
+        }
+        if let Some(language) = self.language {
+            params.push(("l", language.api_value().to_string()));
+        }
+        params.push(("nsfw", self.nsfw.to_string()));
+
+        let mut extras = Vec::new();
+        if self.has_video {
+            extras.push("video");
+        }
+        if self.has_storyboard {
+            extras.push("storyboard");
+        }
+        if !extras.is_empty() {
+            params.push(("e", extras.join(".")));
+        }
+
+        params
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 pub async fn get_beatmapsets(
     client: &Client,
     access_token: &str,
     song_name: &str,
+    filter: &BeatmapSearchFilter,
     debug_mode: bool,
 ) -> Result<Vec<Beatmapset>, OsuError> {
+    get_beatmapsets_page(
+        client,
+        OSU_BASE_URL,
+        access_token,
+        song_name,
+        filter,
+        None,
+        debug_mode,
+    )
+        .await
+        .map(|page| page.beatmapsets)
+}
+
+// 取得一頁搜尋結果，cursor 為上一頁回傳的 cursor_string，第一頁傳 None
+pub async fn get_beatmapsets_page(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    song_name: &str,
+    filter: &BeatmapSearchFilter,
+    cursor: Option<&str>,
+    debug_mode: bool,
+) -> Result<BeatmapsetPage, OsuError> {
+    let mut params = filter.to_query_params(song_name);
+    if let Some(cursor) = cursor {
+        params.push(("cursor_string", cursor.to_string()));
+    }
+    if debug_mode {
+        debug!("Osu 搜尋參數: {:?}", params);
+    }
+
     let response = client
-        .get("https://osu.ppy.sh/api/v2/beatmapsets/search")
-        .query(&[("query", song_name)])
+        .get(format!("{}/api/v2/beatmapsets/search", base_url))
+        .query(&params)
         .bearer_auth(access_token)
         .send()
         .await
@@ -125,16 +518,21 @@ pub async fn get_beatmapsets(
     let search_response: SearchResponse =
         serde_json::from_str(&response_text).map_err(OsuError::JsonError)?;
 
-    Ok(search_response.beatmapsets)
+    Ok(BeatmapsetPage {
+        beatmapsets: search_response.beatmapsets,
+        cursor_string: search_response.cursor_string,
+        total: search_response.total,
+    })
 }
 
 pub async fn get_beatmapset_by_id(
     client: &Client,
+    base_url: &str,
     access_token: &str,
     beatmapset_id: &str,
     debug_mode: bool,
 ) -> Result<Beatmapset, OsuError> {
-    let url = format!("https://osu.ppy.sh/api/v2/beatmapsets/{}", beatmapset_id);
+    let url = format!("{}/api/v2/beatmapsets/{}", base_url, beatmapset_id);
 
     let response = client
         .get(&url)
@@ -155,50 +553,11 @@ pub async fn get_beatmapset_by_id(
     Ok(beatmapset)
 }
 
-
-pub async fn get_beatmapset_details(
+pub async fn get_osu_token(
     client: &Client,
-    access_token: &str,
-    beatmapset_id: &str,
+    base_url: &str,
     debug_mode: bool,
-) -> Result<(String, String), OsuError> {
-    let url = format!("https://osu.ppy.sh/api/v2/beatmapsets/{}", beatmapset_id);
-
-    let response = client
-        .get(&url)
-        .bearer_auth(access_token)
-        .send()
-        .await
-        .map_err(OsuError::RequestError)?;
-
-    let beatmapset: serde_json::Value = response.json().await.map_err(OsuError::RequestError)?;
-
-    if debug_mode {
-        println!("Beatmapset details: {:?}", beatmapset);
-    }
-
-    let (artist, title) = try_join!(
-        async {
-            Ok::<_, OsuError>(
-                beatmapset["artist"]
-                    .as_str()
-                    .unwrap_or("Unknown Artist")
-                    .to_string(),
-            )
-        },
-        async {
-            Ok::<_, OsuError>(
-                beatmapset["title"]
-                    .as_str()
-                    .unwrap_or("Unknown Title")
-                    .to_string(),
-            )
-        }
-    )?;
-
-    Ok((artist, title))
-}
-pub async fn get_osu_token(client: &Client, debug_mode: bool) -> Result<String, OsuError> {
+) -> Result<String, OsuError> {
     if debug_mode {
         debug!("開始獲取 Osu token");
     }
@@ -208,14 +567,24 @@ pub async fn get_osu_token(client: &Client, debug_mode: bool) -> Result<String,
         OsuError::ConfigError(format!("Error reading config: {}", e))
     })?;
 
-    let client_id = &config.osu.client_id;
-    let client_secret = &config.osu.client_secret;
-
     if debug_mode {
         debug!("成功讀取 Osu client_id 和 client_secret");
     }
 
-    let url = "https://osu.ppy.sh/oauth/token";
+    get_osu_token_with_credentials(client, base_url, &config.osu, debug_mode).await
+}
+
+// 以指定的 client_id 與 client_secret 取得 token，不讀取設定檔
+pub async fn get_osu_token_with_credentials(
+    client: &Client,
+    base_url: &str,
+    credentials: &ServiceConfig,
+    debug_mode: bool,
+) -> Result<String, OsuError> {
+    let client_id = &credentials.client_id;
+    let client_secret = &credentials.client_secret;
+
+    let url = format!("{}/oauth/token", base_url);
     let params = [
         ("client_id", client_id),
         ("client_secret", client_secret),
@@ -227,7 +596,7 @@ pub async fn get_osu_token(client: &Client, debug_mode: bool) -> Result<String,
         debug!("準備發送 Osu token 請求");
     }
 
-    let response = client.post(url).form(&params).send().await.map_err(|e| {
+    let response = client.post(&url).form(&params).send().await.map_err(|e| {
         error!("發送 Osu token 請求時出錯: {}", e);
         OsuError::RequestError(e)
     })?;
@@ -244,6 +613,298 @@ pub async fn get_osu_token(client: &Client, debug_mode: bool) -> Result<String,
     Ok(token_response.access_token)
 }
 
+// osu! 使用者登入（authorization code），與 Spotify 共用本機回調監聽器；
+// osu! 應用程式的 callback URL 需設為 http://localhost:8888/callback
+pub async fn authorize_osu(
+    client: &Client,
+    auth_manager: Arc<AuthManager>,
+    listener: Arc<TokioMutex<Option<TcpListener>>>,
+    debug_mode: bool,
+) -> Result<LoginInfo, OsuError> {
+    auth_manager.reset(&AuthPlatform::Osu);
+    let config = read_config(debug_mode).map_err(|e| OsuError::ConfigError(e.to_string()))?;
+    let auth_error = |e: spotify::SpotifyError| OsuError::AuthorizationError(e.to_string());
+
+    let port = {
+        let mut listener_guard = listener.lock().await;
+        if listener_guard.is_some() {
+            return Err(OsuError::AuthorizationError(
+                "其他授權正在進行中".to_string(),
+            ));
+        }
+        let (new_listener, port) = spotify::create_listener(debug_mode)
+            .await
+            .map_err(auth_error)?;
+        *listener_guard = Some(new_listener);
+        port
+    };
+    let redirect_uri = format!("http://localhost:{}/callback", port);
+    // 回調時比對 state，避免接受不是這次授權的請求
+    let state: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+
+    let mut auth_url = Url::parse(OSU_AUTHORIZE_URL)?;
+    auth_url
+        .query_pairs_mut()
+        .append_pair("client_id", &config.osu.client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("response_type", "code")
+        .append_pair("scope", OSU_USER_SCOPE)
+        .append_pair("state", &state);
+    if debug_mode {
+        debug!("osu! 授權 URL: {}", auth_url);
+    }
+
+    auth_manager.set_status(&AuthPlatform::Osu, AuthStatus::WaitingForBrowser);
+    let result: Result<LoginInfo, OsuError> = async {
+        open_url_default_browser(auth_url.as_str())
+            .map_err(|e| OsuError::IoError(e.to_string()))?;
+        let stream = spotify::accept_connection(&listener, AUTHORIZATION_TIMEOUT)
+            .await
+            .map_err(auth_error)?;
+        let callback_url = spotify::read_callback_request(stream, port)
+            .await
+            .map_err(auth_error)?;
+        auth_manager.set_status(&AuthPlatform::Osu, AuthStatus::Processing);
+
+        let code = authorization_code(&callback_url, &state)?;
+        let token = exchange_authorization_code(
+            client,
+            &config.osu.client_id,
+            &config.osu.client_secret,
+            &code,
+            &redirect_uri,
+        )
+        .await?;
+        auth_manager.set_status(&AuthPlatform::Osu, AuthStatus::TokenObtained);
+
+        let user = get_current_user(client, OSU_BASE_URL, &token.access_token).await?;
+        Ok(LoginInfo {
+            platform: "osu".to_string(),
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            expiry_time: Utc::now() + chrono::Duration::seconds(token.expires_in),
+            avatar_url: user.avatar_url,
+            user_name: Some(user.username),
+        })
+    }
+    .await;
+
+    // 無論成功與否，都關閉監聽器
+    *listener.lock().await = None;
+
+    match &result {
+        Ok(login_info) => {
+            if let Err(e) = save_platform_login_info("osu", login_info.clone()) {
+                error!("無法保存 osu! 登入信息: {:?}", e);
+            }
+            auth_manager.set_status(&AuthPlatform::Osu, AuthStatus::Completed);
+            info!("osu! 授權成功: {:?}", login_info.user_name);
+        }
+        Err(e) => auth_manager.set_status(&AuthPlatform::Osu, AuthStatus::Failed(e.to_string())),
+    }
+    result
+}
+
+fn authorization_code(callback_url: &str, expected_state: &str) -> Result<String, OsuError> {
+    let url = Url::parse(callback_url)?;
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    if let Some(error) = query("error") {
+        return Err(OsuError::AuthorizationError(format!(
+            "使用者拒絕授權: {}",
+            error
+        )));
+    }
+    if query("state").as_deref() != Some(expected_state) {
+        return Err(OsuError::AuthorizationError("state 不符".to_string()));
+    }
+    query("code")
+        .ok_or_else(|| OsuError::AuthorizationError("無法從回調 URL 中解析授權碼".to_string()))
+}
+
+async fn exchange_authorization_code(
+    client: &Client,
+    client_id: &str,
+    client_secret: &str,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<UserTokenResponse, OsuError> {
+    let params = [
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+        ("code", code),
+        ("grant_type", "authorization_code"),
+        ("redirect_uri", redirect_uri),
+    ];
+    let response = client.post(OSU_TOKEN_URL).form(&params).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(OsuError::AuthorizationError(format!(
+            "獲取訪問令牌失敗: {} - {}",
+            status, body
+        )));
+    }
+    Ok(response.json().await?)
+}
+
+// 以使用者令牌讀取目前登入的使用者
+pub async fn get_current_user(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+) -> Result<OsuUser, OsuError> {
+    let response = client
+        .get(format!("{}/api/v2/me", base_url))
+        .bearer_auth(access_token)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(OsuError::ApiError(format!(
+            "無法獲取使用者資料: {}",
+            response.status()
+        )));
+    }
+    Ok(response.json().await?)
+}
+
+// 側邊欄顯示的 osu! 使用者譜面清單
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OsuUserListKind {
+    Favourite,
+    MostPlayed,
+    Recent,
+}
+
+impl OsuUserListKind {
+    pub const ALL: [OsuUserListKind; 3] = [
+        OsuUserListKind::Favourite,
+        OsuUserListKind::MostPlayed,
+        OsuUserListKind::Recent,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OsuUserListKind::Favourite => "osu! 最愛",
+            OsuUserListKind::MostPlayed => "最常玩的譜面",
+            OsuUserListKind::Recent => "最近遊玩",
+        }
+    }
+
+    // beatmapsets/{type} 沒有最近遊玩，改用最近的成績（包含未通過）
+    fn endpoint(&self, base_url: &str, user_id: i64) -> String {
+        let path = match self {
+            OsuUserListKind::Favourite => "beatmapsets/favourite",
+            OsuUserListKind::MostPlayed => "beatmapsets/most_played",
+            OsuUserListKind::Recent => "scores/recent",
+        };
+        format!("{}/api/v2/users/{}/{}", base_url, user_id, path)
+    }
+}
+
+// 清單中的譜面集只取顯示與搜尋需要的欄位
+#[derive(Debug, Deserialize, Clone)]
+pub struct OsuUserBeatmapset {
+    pub id: i32,
+    pub artist: String,
+    pub title: String,
+    pub creator: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct OsuUserListEntry {
+    pub beatmapset: OsuUserBeatmapset,
+    // 只有最常玩的清單有遊玩次數（同一譜面集各難度合計）
+    pub play_count: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct BeatmapPlaycount {
+    count: u32,
+    beatmapset: OsuUserBeatmapset,
+}
+
+#[derive(Deserialize)]
+struct RecentScore {
+    beatmapset: OsuUserBeatmapset,
+}
+
+// 讀取使用者的最愛、最常玩或最近遊玩的譜面集，需要使用者令牌
+pub async fn get_user_beatmapset_list(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    user_id: i64,
+    kind: OsuUserListKind,
+    debug_mode: bool,
+) -> Result<Vec<OsuUserListEntry>, OsuError> {
+    let mut request = client
+        .get(kind.endpoint(base_url, user_id))
+        .bearer_auth(access_token)
+        .query(&[("limit", USER_LIST_LIMIT)]);
+    if kind == OsuUserListKind::Recent {
+        request = request.query(&[("include_fails", "1")]);
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(OsuError::ApiError(format!(
+            "無法獲取{}: {}",
+            kind.label(),
+            response.status()
+        )));
+    }
+
+    let sets: Vec<(OsuUserBeatmapset, Option<u32>)> = match kind {
+        OsuUserListKind::Favourite => response
+            .json::<Vec<OsuUserBeatmapset>>()
+            .await?
+            .into_iter()
+            .map(|beatmapset| (beatmapset, None))
+            .collect(),
+        OsuUserListKind::MostPlayed => response
+            .json::<Vec<BeatmapPlaycount>>()
+            .await?
+            .into_iter()
+            .map(|playcount| (playcount.beatmapset, Some(playcount.count)))
+            .collect(),
+        OsuUserListKind::Recent => response
+            .json::<Vec<RecentScore>>()
+            .await?
+            .into_iter()
+            .map(|score| (score.beatmapset, None))
+            .collect(),
+    };
+
+    // 同一譜面集的多個難度或多次遊玩合併成一項，保留原本的順序
+    let mut entries: Vec<OsuUserListEntry> = Vec::new();
+    for (beatmapset, play_count) in sets {
+        match entries
+            .iter_mut()
+            .find(|entry| entry.beatmapset.id == beatmapset.id)
+        {
+            Some(entry) => {
+                entry.play_count = entry.play_count.zip(play_count).map(|(a, b)| a + b);
+            }
+            None => entries.push(OsuUserListEntry {
+                beatmapset,
+                play_count,
+            }),
+        }
+    }
+
+    if debug_mode {
+        debug!("{}共 {} 個譜面集", kind.label(), entries.len());
+    }
+    Ok(entries)
+}
+
 impl Beatmapset {
     pub fn format_info(&self) -> BeatmapInfo {
         let beatmaps = self.beatmaps.iter().map(|b| b.format_info()).collect();
@@ -366,16 +1027,39 @@ pub async fn load_osu_covers(
 }
 
 pub fn is_beatmap_downloaded(download_directory: &Path, beatmapset_id: i32) -> bool {
-    if let Ok(entries) = fs::read_dir(download_directory) {
-        for entry in entries.flatten() {
-            if let Ok(file_name) = entry.file_name().into_string() {
-                if file_name.contains(&beatmapset_id.to_string()) {
-                    return true;
-                }
-            }
+    find_beatmap_entries(download_directory, beatmapset_id)
+        .map(|entries| !entries.is_empty())
+        .unwrap_or(false)
+}
+
+// 找出下載資料夾中屬於指定譜面集的 .osz 檔與資料夾，ID 必須完全相符
+pub fn find_beatmap_entries(
+    download_directory: &Path,
+    beatmapset_id: i32,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut matches = Vec::new();
+    for entry in fs::read_dir(download_directory)? {
+        let path = entry?.path();
+        if beatmapset_id_of_entry(&path) == Some(beatmapset_id) {
+            matches.push(path);
         }
     }
-    false
+    Ok(matches)
+}
+
+// 判斷 .osz 檔或譜面資料夾屬於哪個譜面集：
+// 先看檔名開頭的 ID，沒有的話才讀取 .osu 檔中的 BeatmapSetID
+pub fn beatmapset_id_of_entry(path: &Path) -> Option<i32> {
+    let is_osz = path.is_file() && path.extension().is_some_and(|ext| ext == "osz");
+    if !is_osz && !path.is_dir() {
+        return None;
+    }
+    let file_name = path.file_name()?.to_string_lossy();
+    beatmapset_id_from_file_name(&file_name).or_else(|| {
+        read_local_beatmapset(path)
+            .ok()
+            .and_then(|beatmapset| beatmapset.id)
+    })
 }
 pub fn get_downloaded_beatmaps(download_directory: &Path) -> Vec<String> {
     let mut downloaded = Vec::new();
@@ -383,20 +1067,8 @@ pub fn get_downloaded_beatmaps(download_directory: &Path) -> Vec<String> {
     if let Ok(entries) = fs::read_dir(download_directory) {
         for entry in entries.flatten() {
             if let Ok(file_name) = entry.file_name().into_string() {
-                let path = entry.path();
-                let is_valid = if path.is_file() {
-                    file_name.ends_with(".osz")
-                } else if path.is_dir() {
-                    // 檢查資料夾名稱是否包含數字（beatmapset ID）
-                    file_name.split_whitespace()
-                        .next()
-                        .map(|first_part| first_part.parse::<i32>().is_ok())
-                        .unwrap_or(false)
-                } else {
-                    false
-                };
-
-                if is_valid {
+                // 與刪除譜面相同的判斷，列出的譜面集才都能被刪除
+                if beatmapset_id_of_entry(&entry.path()).is_some() {
                     if let Ok(metadata) = entry.metadata() {
                         if let Ok(modified) = metadata.modified() {
                             downloaded.push((file_name, modified));
@@ -408,120 +1080,662 @@ pub fn get_downloaded_beatmaps(download_directory: &Path) -> Vec<String> {
     }
     
     // 按照修改時間降序排序（最新的在前）
-    downloaded.sort_by(|a, b| b.1.cmp(&a.1));
+    downloaded.sort_by_key(|(_, modified)| Reverse(*modified));
     
     // 只返回檔案名稱
     downloaded.into_iter().map(|(name, _)| name).collect()
 }
 
+// 下載的檔案與解壓後的資料夾都以譜面集 ID 開頭，例如 "123456 Artist - Title.osz"
+// 或 "123456.osz"；數字後面必須是空白、副檔名或結尾，"123456abc" 不算
+pub fn beatmapset_id_from_file_name(file_name: &str) -> Option<i32> {
+    let digits_end = file_name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(file_name.len());
+    let rest = &file_name[digits_end..];
+    if digits_end == 0 || !(rest.is_empty() || rest.starts_with([' ', '.'])) {
+        return None;
+    }
+    file_name[..digits_end].parse::<i32>().ok()
+}
+
+// 本機譜面集中的單一難度（一個 .osu 檔）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalBeatmap {
+    pub beatmap_id: Option<i32>,
+    pub version: String,
+    // 0 = osu!、1 = taiko、2 = catch、3 = mania
+    pub mode: u8,
+    pub hp_drain_rate: f32,
+    pub circle_size: f32,
+    pub overall_difficulty: f32,
+    pub approach_rate: f32,
+    // 最後一個物件結束的時間（秒）
+    pub length_secs: i32,
+    pub hit_objects: usize,
+    // .osu 檔沒有星級，只有從 API 得知時才有值
+    #[serde(default)]
+    pub difficulty_rating: Option<f32>,
+}
+
+// 由 .osu 檔或 .osz 壓縮檔讀出的本機譜面集
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalBeatmapset {
+    // 舊譜面的 .osu 檔可能沒有 BeatmapSetID，這時改用檔名開頭的數字
+    pub id: Option<i32>,
+    pub artist: String,
+    pub artist_unicode: String,
+    pub title: String,
+    pub title_unicode: String,
+    pub creator: String,
+    pub audio_filename: String,
+    pub background: Option<String>,
+    // 所有難度中最長的長度（秒）
+    pub length_secs: i32,
+    pub beatmaps: Vec<LocalBeatmap>,
+    // 下載資料夾中的檔案或資料夾名稱
+    pub file_name: String,
+    // 依檔名判斷下載的是哪個版本
+    #[serde(default)]
+    pub variant: DownloadVariant,
+}
+
+impl LocalBeatmapset {
+    // 列表顯示用的「藝人 - 曲名」
+    pub fn display_name(&self) -> String {
+        format!("{} - {}", self.artist, self.title)
+    }
+
+    // 把同一譜面集的其他難度併入
+    fn merge(&mut self, other: LocalBeatmapset) {
+        if self.id.is_none() {
+            self.id = other.id;
+        }
+        if self.background.is_none() {
+            self.background = other.background;
+        }
+        self.length_secs = self.length_secs.max(other.length_secs);
+        self.beatmaps.extend(other.beatmaps);
+    }
+}
+
+// 目前所在的 .osu 區段
+#[derive(PartialEq)]
+enum OsuSection {
+    None,
+    General,
+    Metadata,
+    Difficulty,
+    Events,
+    HitObjects,
+    Other,
+}
+
+// 解析單一 .osu 檔，回傳只含這個難度的譜面集
+pub fn parse_osu_file(content: &str) -> Result<LocalBeatmapset, OsuError> {
+    let content = content.trim_start_matches('\u{feff}');
+    if !content.trim_start().starts_with("osu file format") {
+        return Err(OsuError::ParseError("不是有效的 .osu 檔".to_string()));
+    }
+
+    let mut beatmapset = LocalBeatmapset::default();
+    let mut beatmap = LocalBeatmap::default();
+    let mut section = OsuSection::None;
+    let mut last_object_ms = 0;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = match line {
+                "[General]" => OsuSection::General,
+                "[Metadata]" => OsuSection::Metadata,
+                "[Difficulty]" => OsuSection::Difficulty,
+                "[Events]" => OsuSection::Events,
+                "[HitObjects]" => OsuSection::HitObjects,
+                _ => OsuSection::Other,
+            };
+            continue;
+        }
+
+        match section {
+            OsuSection::General | OsuSection::Metadata | OsuSection::Difficulty => {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let value = value.trim();
+                match key.trim() {
+                    "AudioFilename" => beatmapset.audio_filename = value.to_string(),
+                    "Mode" => beatmap.mode = value.parse().unwrap_or(0),
+                    "Artist" => beatmapset.artist = value.to_string(),
+                    "ArtistUnicode" => beatmapset.artist_unicode = value.to_string(),
+                    "Title" => beatmapset.title = value.to_string(),
+                    "TitleUnicode" => beatmapset.title_unicode = value.to_string(),
+                    "Creator" => beatmapset.creator = value.to_string(),
+                    "Version" => beatmap.version = value.to_string(),
+                    // 未上傳的譜面 ID 為 -1 或 0
+                    "BeatmapID" => beatmap.beatmap_id = value.parse().ok().filter(|id| *id > 0),
+                    "BeatmapSetID" => beatmapset.id = value.parse().ok().filter(|id| *id > 0),
+                    "HPDrainRate" => beatmap.hp_drain_rate = value.parse().unwrap_or(0.0),
+                    "CircleSize" => beatmap.circle_size = value.parse().unwrap_or(0.0),
+                    "OverallDifficulty" => {
+                        beatmap.overall_difficulty = value.parse().unwrap_or(0.0)
+                    }
+                    "ApproachRate" => beatmap.approach_rate = value.parse().unwrap_or(0.0),
+                    _ => {}
+                }
+            }
+            OsuSection::Events => {
+                // 背景圖片的格式為 0,0,"bg.jpg",0,0
+                if beatmapset.background.is_none() && line.starts_with("0,0,") {
+                    if let Some(name) = line.split(',').nth(2) {
+                        beatmapset.background = Some(name.trim_matches('"').to_string());
+                    }
+                }
+            }
+            OsuSection::HitObjects => {
+                beatmap.hit_objects += 1;
+                last_object_ms = last_object_ms.max(hit_object_end_time(line));
+            }
+            OsuSection::None | OsuSection::Other => {}
+        }
+    }
+
+    // 舊格式沒有 ApproachRate 時沿用 OverallDifficulty
+    if beatmap.approach_rate == 0.0 {
+        beatmap.approach_rate = beatmap.overall_difficulty;
+    }
+    beatmap.length_secs = last_object_ms / 1000;
+    beatmapset.length_secs = beatmap.length_secs;
+    beatmapset.beatmaps.push(beatmap);
+    Ok(beatmapset)
+}
+
+// 物件結束時間（毫秒）：x,y,time,type,hitSound,...
+fn hit_object_end_time(line: &str) -> i32 {
+    let fields: Vec<&str> = line.split(',').collect();
+    let start = fields
+        .get(2)
+        .and_then(|t| t.trim().parse::<f32>().ok())
+        .unwrap_or(0.0) as i32;
+    let object_type = fields
+        .get(3)
+        .and_then(|t| t.trim().parse::<u32>().ok())
+        .unwrap_or(0);
+
+    let end = if object_type & 8 != 0 {
+        // 轉盤：第 6 欄為結束時間
+        fields.get(5).and_then(|t| t.trim().parse::<i32>().ok())
+    } else if object_type & 128 != 0 {
+        // mania 長條：第 6 欄為 endTime:hitSample
+        fields
+            .get(5)
+            .and_then(|t| t.split(':').next())
+            .and_then(|t| t.trim().parse::<i32>().ok())
+    } else {
+        None
+    };
+    end.unwrap_or(start).max(start)
+}
+
+// 讀取已解壓的譜面資料夾或 .osz 壓縮檔
+pub fn read_local_beatmapset(path: &Path) -> Result<LocalBeatmapset, OsuError> {
+    let contents = if path.is_dir() {
+        read_osu_files_in_folder(path)?
+    } else {
+        read_osu_files_in_osz(path)?
+    };
+
+    let mut beatmapset: Option<LocalBeatmapset> = None;
+    for content in contents {
+        match parse_osu_file(&content) {
+            Ok(parsed) => match beatmapset.as_mut() {
+                Some(beatmapset) => beatmapset.merge(parsed),
+                None => beatmapset = Some(parsed),
+            },
+            Err(e) => error!("無法解析 {:?} 中的 .osu 檔: {:?}", path, e),
+        }
+    }
+
+    let mut beatmapset = beatmapset
+        .ok_or_else(|| OsuError::ParseError(format!("{:?} 中沒有可用的 .osu 檔", path)))?;
+    beatmapset.file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if beatmapset.id.is_none() {
+        beatmapset.id = beatmapset_id_from_file_name(&beatmapset.file_name);
+    }
+    beatmapset.variant = DownloadVariant::from_file_name(&beatmapset.file_name);
+    Ok(beatmapset)
+}
+
+fn read_osu_files_in_folder(folder: &Path) -> Result<Vec<String>, OsuError> {
+    let mut contents = Vec::new();
+    for entry in fs::read_dir(folder).map_err(|e| OsuError::IoError(e.to_string()))? {
+        let path = entry.map_err(|e| OsuError::IoError(e.to_string()))?.path();
+        if path.extension().is_some_and(|ext| ext == "osu") {
+            let bytes = fs::read(&path).map_err(|e| OsuError::IoError(e.to_string()))?;
+            contents.push(String::from_utf8_lossy(&bytes).into_owned());
+        }
+    }
+    Ok(contents)
+}
+
+fn read_osu_files_in_osz(osz_path: &Path) -> Result<Vec<String>, OsuError> {
+    let file = File::open(osz_path).map_err(|e| OsuError::IoError(e.to_string()))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| OsuError::ParseError(e.to_string()))?;
+
+    let mut contents = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| OsuError::ParseError(e.to_string()))?;
+        if !entry.name().ends_with(".osu") {
+            continue;
+        }
+        // 舊譜面的 .osu 可能是 Shift-JIS 或 Latin-1，無法解碼的字元不影響需要的欄位
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| OsuError::IoError(e.to_string()))?;
+        contents.push(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    Ok(contents)
+}
+
+// 依序嘗試各鏡像站下載，失敗或逾時就換下一個；回傳實際下載到的版本
 pub async fn download_beatmap(
     beatmapset_id: i32,
     download_directory: &Path,
+    variant: DownloadVariant,
+    mirrors: &MirrorPool,
+    limiter: &BandwidthLimiter,
     mut update_status: impl FnMut(DownloadStatus) + Send + 'static,
-) -> Result<(), OsuError> {  // 改用 OsuError
-    let url = format!("https://api.nerinyan.moe/d/{}", beatmapset_id);
-
-    update_status(DownloadStatus::Downloading);
+) -> Result<DownloadVariant, OsuError> {
+    update_status(DownloadStatus::started());
 
     let client = Client::builder()
         .redirect(reqwest::redirect::Policy::limited(10))
         .build()
-        .map_err(|e| OsuError::RequestError(e))?;
+        .map_err(OsuError::RequestError)?;
 
-    let response = client.get(&url)
-        .header("Accept", "application/x-osu-beatmap-archive")
-        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-        .header("Origin", "https://osu.ppy.sh")
-        .send()
-        .await
-        .map_err(|e| OsuError::RequestError(e))?;
-
-    if response.status().is_success() {
-        let filename = response.headers()
-            .get("content-disposition")
-            .and_then(|cd| cd.to_str().ok())
-            .and_then(|cd| cd.split("filename=\"").nth(1))
-            .and_then(|s| s.strip_suffix("\""))
-            .unwrap_or(&format!("{}.osz", beatmapset_id))
-            .to_string();
-
-        let content = response.bytes().await.map_err(|e| OsuError::RequestError(e))?;
-
-        let download_path = download_directory.join(&filename);
-        task::spawn_blocking(move || -> Result<(), OsuError> {
-            let mut dest = File::create(&download_path)
-                .map_err(|e| OsuError::IoError(e.to_string()))?;
-            copy(&mut content.as_ref(), &mut dest)
-                .map_err(|e| OsuError::IoError(e.to_string()))?;
-            Ok(())
-        })
+    let mut errors = Vec::new();
+    for (mirror, mirror_variant) in mirrors.candidates(variant) {
+        let started = Instant::now();
+        match download_from_mirror(
+            &client,
+            mirror.as_ref(),
+            beatmapset_id,
+            mirror_variant,
+            download_directory,
+            limiter,
+            &mut update_status,
+        )
         .await
-        .map_err(|e| OsuError::Other(e.to_string()))??;
+        {
+            Ok(filename) => {
+                mirrors.record_success(mirror.as_ref(), started.elapsed());
+                remove_partial_downloads(download_directory, beatmapset_id);
+                info!(
+                    "Beatmap {} downloaded successfully from {} as: {}",
+                    beatmapset_id,
+                    mirror.name(),
+                    filename
+                );
+                if mirror_variant != variant {
+                    warn!(
+                        "沒有鏡像站提供譜面 {} 的{}版本，已改為下載{}版本",
+                        beatmapset_id,
+                        variant.label(),
+                        mirror_variant.label()
+                    );
+                }
+                update_status(DownloadStatus::Completed);
+                return Ok(mirror_variant);
+            }
+            Err(e) => {
+                warn!(
+                    "鏡像站 {} 下載譜面 {} 失敗，改用下一個: {}",
+                    mirror.name(),
+                    beatmapset_id,
+                    e
+                );
+                mirrors.record_failure(mirror.as_ref(), e.to_string());
+                errors.push(format!("{}: {}", mirror.name(), e));
+            }
+        }
+    }
 
-        info!("Beatmap {} downloaded successfully as: {}", beatmapset_id, filename);
-        update_status(DownloadStatus::Completed);
-        Ok(())
-    } else {
-        let error_message = format!(
-            "下載譜面失敗 (beatmapset ID: {})\n狀態碼: {}\n請稍後再試",
-            beatmapset_id,
-            response.status()
-        );
-        error!("{}", error_message);
-        update_status(DownloadStatus::NotStarted);
-        Err(OsuError::ApiError(error_message))
+    let error_message = format!(
+        "下載譜面失敗 (beatmapset ID: {})\n{}\n請稍後再試",
+        beatmapset_id,
+        if errors.is_empty() {
+            "沒有可用的鏡像站".to_string()
+        } else {
+            errors.join("\n")
+        }
+    );
+    error!("{}", error_message);
+    update_status(DownloadStatus::Failed(error_message.clone()));
+    Err(OsuError::ApiError(error_message))
+}
+
+// 確認下載的檔案是完整的 .osz：能以 zip 開啟、至少有一個 .osu，且譜面集 ID 相符
+// 很舊的 .osu 沒有 BeatmapSetID，這種情況只能接受
+pub fn verify_osz_archive(path: &Path, beatmapset_id: i32) -> Result<(), OsuError> {
+    let contents = read_osu_files_in_osz(path)
+        .map_err(|e| OsuError::ParseError(format!("不是有效的 .osz 檔: {}", e)))?;
+    if contents.is_empty() {
+        return Err(OsuError::ParseError("壓縮檔中沒有 .osu 檔".to_string()));
+    }
+
+    let ids: Vec<i32> = contents
+        .iter()
+        .filter_map(|content| parse_osu_file(content).ok())
+        .filter_map(|beatmapset| beatmapset.id)
+        .collect();
+    if ids.is_empty() {
+        warn!("譜面 {} 的 .osu 檔沒有 BeatmapSetID，略過 ID 檢查", beatmapset_id);
+    } else if !ids.contains(&beatmapset_id) {
+        return Err(OsuError::ParseError(format!(
+            "譜面集 ID 不符：預期 {}，實際為 {}",
+            beatmapset_id, ids[0]
+        )));
     }
+    Ok(())
 }
 
-pub fn delete_beatmap(download_directory: &Path, beatmapset_id: i32) -> std::io::Result<()> {
-    let mut deleted = false;
+// 未完成的下載，依鏡像站與版本分開存放，因為壓縮檔內容不一定相同
+pub fn partial_download_path(
+    download_directory: &Path,
+    beatmapset_id: i32,
+    mirror: &dyn BeatmapMirror,
+    variant: DownloadVariant,
+) -> PathBuf {
+    let variant_tag = match variant {
+        DownloadVariant::Full => "",
+        DownloadVariant::NoVideo => ".novideo",
+    };
+    download_directory.join(format!(
+        "{}.{}{}{}",
+        beatmapset_id,
+        mirror.name(),
+        variant_tag,
+        PARTIAL_DOWNLOAD_EXTENSION
+    ))
+}
 
-    // 尋找並刪除含有 beatmapset_id 的 .osz 文件
-    let osz_pattern = format!("*{}*", beatmapset_id);
-    for entry in fs::read_dir(download_directory)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() && path.extension() == Some(std::ffi::OsStr::new("osz")) {
-            if let Some(file_name) = path.file_name() {
-                let file_name_str = file_name.to_string_lossy();
-                if file_name_str.contains(&beatmapset_id.to_string()) || 
-                   file_name_str.to_lowercase().contains(&osz_pattern.to_lowercase()) {
-                    fs::remove_file(&path)?;
-                    info!("已刪除 .osz 文件: {:?}", path);
-                    deleted = true;
-                }
+// 下載完成後刪除其他鏡像站或版本留下的 .part 檔，回傳被刪除的路徑
+pub fn remove_partial_downloads(download_directory: &Path, beatmapset_id: i32) -> Vec<PathBuf> {
+    let prefix = format!("{}.", beatmapset_id);
+    let Ok(entries) = fs::read_dir(download_directory) else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !file_name.starts_with(&prefix) || !file_name.ends_with(PARTIAL_DOWNLOAD_EXTENSION) {
+            continue;
+        }
+        match fs::remove_file(entry.path()) {
+            Ok(()) => {
+                info!("已刪除未完成的下載: {}", file_name);
+                removed.push(entry.path());
             }
+            Err(e) => warn!("無法刪除未完成的下載 {}: {}", file_name, e),
         }
     }
+    removed
+}
 
-    // 尋找並刪除包含 beatmapset_id 的資料夾
-    for entry in fs::read_dir(download_directory)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            if let Some(dir_name) = path.file_name() {
-                if dir_name.to_string_lossy().contains(&beatmapset_id.to_string()) {
-                    fs::remove_dir_all(&path)?;
-                    info!("已刪除資料夾: {:?}", path);
-                    deleted = true;
-                }
+// 從單一鏡像站串流下載到 .part 檔，連線中斷時用 Range 從中斷處續傳
+async fn download_from_mirror(
+    client: &Client,
+    mirror: &dyn BeatmapMirror,
+    beatmapset_id: i32,
+    variant: DownloadVariant,
+    download_directory: &Path,
+    limiter: &BandwidthLimiter,
+    update_status: &mut impl FnMut(DownloadStatus),
+) -> Result<String, OsuError> {
+    let part_path = partial_download_path(download_directory, beatmapset_id, mirror, variant);
+    let mut resume_attempts = 0;
+
+    loop {
+        let resume_from = tokio::fs::metadata(&part_path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let request =
+            mirror.prepare_request(client.get(mirror.download_url(beatmapset_id, variant)));
+        match stream_to_part_file(
+            request,
+            beatmapset_id,
+            variant,
+            &part_path,
+            resume_from,
+            limiter,
+            update_status,
+        )
+        .await
+        {
+            Ok(filename) => {
+                // 驗證通過才改名，下載資料夾中不會出現不完整的 .osz
+                let verify_path = part_path.clone();
+                task::spawn_blocking(move || verify_osz_archive(&verify_path, beatmapset_id))
+                    .await
+                    .map_err(|e| OsuError::Other(e.to_string()))?
+                    .inspect_err(|_| {
+                        // 內容有問題就不要再從這個檔續傳
+                        let _ = fs::remove_file(&part_path);
+                    })?;
+                tokio::fs::rename(&part_path, download_directory.join(&filename))
+                    .await
+                    .map_err(|e| OsuError::IoError(e.to_string()))?;
+                return Ok(filename);
+            }
+            // 已經收到部分內容才值得續傳，其他錯誤直接換鏡像站
+            Err(StreamError::Interrupted(e)) if resume_attempts < MAX_RESUME_ATTEMPTS => {
+                resume_attempts += 1;
+                warn!(
+                    "譜面 {} 從 {} 下載中斷，第 {} 次續傳: {}",
+                    beatmapset_id,
+                    mirror.name(),
+                    resume_attempts,
+                    e
+                );
             }
+            Err(StreamError::Interrupted(e)) | Err(StreamError::Failed(e)) => return Err(e),
         }
     }
+}
 
-    if deleted {
-        Ok(())
-    } else {
-        error!("未找到與 beatmapset_id {} 相關的文件或資料夾", beatmapset_id);
-        Err(std::io::Error::new(std::io::ErrorKind::NotFound, "未找到相關文件或資料夾"))
+enum StreamError {
+    // 連線在傳輸途中中斷，.part 檔保留可續傳
+    Interrupted(OsuError),
+    Failed(OsuError),
+}
+
+async fn stream_to_part_file(
+    mut request: RequestBuilder,
+    beatmapset_id: i32,
+    variant: DownloadVariant,
+    part_path: &Path,
+    resume_from: u64,
+    limiter: &BandwidthLimiter,
+    update_status: &mut impl FnMut(DownloadStatus),
+) -> Result<String, StreamError> {
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+    }
+    let mut response = tokio::time::timeout(MIRROR_RESPONSE_TIMEOUT, request.send())
+        .await
+        .map_err(|_| StreamError::Failed(OsuError::Other("等待鏡像站回應逾時".to_string())))?
+        .map_err(|e| StreamError::Failed(OsuError::RequestError(e)))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // .part 檔與伺服器上的檔案對不起來，刪掉下次重新下載
+        let _ = tokio::fs::remove_file(part_path).await;
+        return Err(StreamError::Interrupted(OsuError::ApiError(
+            "無法續傳，將重新下載".to_string(),
+        )));
+    }
+    if !status.is_success() {
+        return Err(StreamError::Failed(OsuError::ApiError(format!(
+            "狀態碼: {}",
+            status
+        ))));
+    }
+    // 有些鏡像站找不到譜面時仍回傳 200 和錯誤頁面
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|ct| ct.to_str().ok())
+        .unwrap_or_default();
+    if content_type.contains("text/html") || content_type.contains("json") {
+        return Err(StreamError::Failed(OsuError::ApiError(format!(
+            "回傳的不是 .osz 檔 ({})",
+            content_type
+        ))));
+    }
+
+    // 伺服器不支援 Range 時會回傳完整檔案，只能從頭寫
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut received = if resumed { resume_from } else { 0 };
+    let total = response.content_length().map(|length| length + received);
+    let filename = variant.apply_to_file_name(archive_file_name(&response, beatmapset_id));
+    if resumed {
+        info!("譜面 {} 從第 {} 位元組續傳", beatmapset_id, resume_from);
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part_path)
+        .await
+        .map_err(|e| StreamError::Failed(OsuError::IoError(e.to_string())))?;
+
+    let started = Instant::now();
+    let started_at = received;
+    let mut last_report = Instant::now();
+    loop {
+        let chunk = tokio::time::timeout(MIRROR_IDLE_TIMEOUT, response.chunk())
+            .await
+            .map_err(|_| StreamError::Interrupted(OsuError::Other("下載停滯逾時".to_string())))?
+            .map_err(|e| StreamError::Interrupted(OsuError::RequestError(e)))?;
+        let Some(chunk) = chunk else {
+            break;
+        };
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| StreamError::Failed(OsuError::IoError(e.to_string())))?;
+        received += chunk.len() as u64;
+        // 有設定頻寬上限時在這裡等待，讀得慢伺服器自然就送得慢
+        limiter.consume(chunk.len()).await;
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let elapsed = started.elapsed().as_secs_f64();
+            let bytes_per_sec = if elapsed > 0.0 {
+                ((received - started_at) as f64 / elapsed) as u64
+            } else {
+                0
+            };
+            update_status(DownloadStatus::Downloading {
+                received,
+                total,
+                bytes_per_sec,
+            });
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| StreamError::Failed(OsuError::IoError(e.to_string())))?;
+    file.sync_all()
+        .await
+        .map_err(|e| StreamError::Failed(OsuError::IoError(e.to_string())))?;
+
+    if total.is_some_and(|total| received < total) {
+        return Err(StreamError::Interrupted(OsuError::Other(format!(
+            "下載不完整 ({}/{} 位元組)",
+            received,
+            total.unwrap_or_default()
+        ))));
+    }
+    Ok(filename)
+}
+
+// 從 content-disposition 取得檔名，並確保以譜面集 ID 開頭，方便之後辨識
+fn archive_file_name(response: &reqwest::Response, beatmapset_id: i32) -> String {
+    let filename = response
+        .headers()
+        .get("content-disposition")
+        .and_then(|cd| cd.to_str().ok())
+        .and_then(|cd| cd.split("filename=").nth(1))
+        .map(|name| {
+            name.split(';')
+                .next()
+                .unwrap_or(name)
+                .trim()
+                .trim_matches('"')
+        })
+        // 避免檔名中帶有路徑
+        .and_then(|name| Path::new(name).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| name.to_lowercase().ends_with(".osz"));
+
+    match filename {
+        Some(name) if beatmapset_id_from_file_name(&name) == Some(beatmapset_id) => name,
+        Some(name) => format!("{} {}", beatmapset_id, name),
+        None => format!("{}.osz", beatmapset_id),
+    }
+}
+
+// 刪除指定譜面集的 .osz 檔與資料夾，回傳被刪除的路徑
+// dry_run 為 true 時只列出會被刪除的項目，不實際刪除
+pub fn delete_beatmap(
+    download_directory: &Path,
+    beatmapset_id: i32,
+    dry_run: bool,
+) -> std::io::Result<Vec<PathBuf>> {
+    let entries = find_beatmap_entries(download_directory, beatmapset_id)?;
+    if entries.is_empty() {
+        error!(
+            "未找到與 beatmapset_id {} 相關的文件或資料夾",
+            beatmapset_id
+        );
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "未找到相關文件或資料夾",
+        ));
     }
+
+    for path in &entries {
+        if dry_run {
+            info!("將會刪除: {:?}", path);
+        } else if path.is_dir() {
+            fs::remove_dir_all(path)?;
+            info!("已刪除資料夾: {:?}", path);
+        } else {
+            fs::remove_file(path)?;
+            info!("已刪除 .osz 文件: {:?}", path);
+        }
+    }
+    Ok(entries)
 }
 pub async fn preview_beatmap(beatmapset_id: i32, stream_handle: &OutputStreamHandle, volume: f32) -> Result<Sink, Box<dyn std::error::Error + Send + Sync>> {
     // 首先建立 reqwest Client
     let client = Client::new();
     
     // 獲取 osu! API 的訪問令牌
-    let access_token = get_osu_token(&client, false).await?;
+    let access_token = get_osu_token(&client, OSU_BASE_URL, false).await?;
 
     let url = format!("https://osu.ppy.sh/api/v2/beatmapsets/{}", beatmapset_id);
     
@@ -585,3 +1799,446 @@ pub async fn preview_beatmap(beatmapset_id: i32, stream_handle: &OutputStreamHan
     
     Ok(sink)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::OsuLogin;
+    use std::io::Write;
+    use tempfile::{tempdir, TempDir};
+
+    #[test]
+    fn default_filter_only_sends_query_and_nsfw() {
+        let params = BeatmapSearchFilter::default().to_query_params("  freedom dive ");
+
+        assert_eq!(
+            params,
+            [
+                ("query", "freedom dive".to_string()),
+                ("nsfw", "false".to_string()),
+            ]
+        );
+        assert!(BeatmapSearchFilter::default().is_default());
+    }
+
+    #[test]
+    fn full_filter_maps_to_api_params() {
+        let filter = BeatmapSearchFilter {
+            mode: Some(OsuMode::Mania),
+            status: Some(RankedStatus::Loved),
+            min_stars: Some(4.0),
+            max_stars: Some(6.5),
+            genre: Some(Genre::Electronic),
+            language: Some(Language::Japanese),
+            nsfw: true,
+            has_video: true,
+            has_storyboard: true,
+        };
+
+        assert_eq!(
+            filter.to_query_params("freedom dive"),
+            [
+                ("query", "freedom dive stars>=4.00 stars<=6.50".to_string()),
+                ("m", "3".to_string()),
+                ("s", "loved".to_string()),
+                ("g", "10".to_string()),
+                ("l", "3".to_string()),
+                ("nsfw", "true".to_string()),
+                ("e", "video.storyboard".to_string()),
+            ]
+        );
+        assert!(!filter.is_default());
+    }
+
+    #[test]
+    fn star_filter_without_song_name_is_trimmed() {
+        let filter = BeatmapSearchFilter {
+            max_stars: Some(3.0),
+            has_storyboard: true,
+            ..BeatmapSearchFilter::default()
+        };
+
+        assert_eq!(
+            filter.to_query_params(""),
+            [
+                ("query", "stars<=3.00".to_string()),
+                ("nsfw", "false".to_string()),
+                ("e", "storyboard".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_current_user_reads_me_with_user_token() {
+        let mut server = mockito::Server::new_async().await;
+        let me = server
+            .mock("GET", "/api/v2/me")
+            .match_header("authorization", "Bearer user-token")
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id": 7, "username": "peppy", "avatar_url": null, "country_code": "AU"}"#,
+            )
+            .create_async()
+            .await;
+
+        let user = get_current_user(&Client::new(), &server.url(), "user-token")
+            .await
+            .unwrap();
+
+        me.assert_async().await;
+        assert_eq!(user.id, 7);
+        assert_eq!(user.username, "peppy");
+        assert!(user.avatar_url.is_none());
+    }
+
+    #[tokio::test]
+    async fn get_current_user_reports_rejected_token() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v2/me")
+            .with_status(401)
+            .create_async()
+            .await;
+
+        let result = get_current_user(&Client::new(), &server.url(), "expired").await;
+
+        assert!(matches!(result, Err(OsuError::ApiError(_))));
+    }
+
+    fn beatmapset_json(id: i32) -> String {
+        format!(
+            r#"{{"id": {}, "artist": "Artist {}", "title": "Title {}", "creator": "Mapper"}}"#,
+            id, id, id
+        )
+    }
+
+    async fn user_list_from_mock(
+        kind: OsuUserListKind,
+        path: &str,
+        extra_query: Vec<mockito::Matcher>,
+        body: String,
+    ) -> Vec<OsuUserListEntry> {
+        let mut server = mockito::Server::new_async().await;
+        let mut query = vec![mockito::Matcher::UrlEncoded(
+            "limit".to_string(),
+            USER_LIST_LIMIT.to_string(),
+        )];
+        query.extend(extra_query);
+        let list = server
+            .mock("GET", path)
+            .match_header("authorization", "Bearer user-token")
+            .match_query(mockito::Matcher::AllOf(query))
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let entries =
+            get_user_beatmapset_list(&Client::new(), &server.url(), "user-token", 7, kind, false)
+                .await
+                .unwrap();
+        list.assert_async().await;
+        entries
+    }
+
+    fn entry_summary(entries: &[OsuUserListEntry]) -> Vec<(i32, Option<u32>)> {
+        entries
+            .iter()
+            .map(|entry| (entry.beatmapset.id, entry.play_count))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recent_list_merges_scores_and_includes_fails() {
+        let body = format!(
+            r#"[{{"beatmapset": {}}}, {{"beatmapset": {}}}, {{"beatmapset": {}}}]"#,
+            beatmapset_json(2),
+            beatmapset_json(1),
+            beatmapset_json(2)
+        );
+
+        let entries = user_list_from_mock(
+            OsuUserListKind::Recent,
+            "/api/v2/users/7/scores/recent",
+            vec![mockito::Matcher::UrlEncoded(
+                "include_fails".to_string(),
+                "1".to_string(),
+            )],
+            body,
+        )
+        .await;
+
+        assert_eq!(entry_summary(&entries), [(2, None), (1, None)]);
+        assert_eq!(entries[0].beatmapset.title, "Title 2");
+    }
+
+    #[tokio::test]
+    async fn most_played_list_sums_play_counts_per_beatmapset() {
+        let body = format!(
+            r#"[{{"count": 10, "beatmapset": {}}}, {{"count": 4, "beatmapset": {}}}, {{"count": 3, "beatmapset": {}}}]"#,
+            beatmapset_json(5),
+            beatmapset_json(6),
+            beatmapset_json(5)
+        );
+
+        let entries = user_list_from_mock(
+            OsuUserListKind::MostPlayed,
+            "/api/v2/users/7/beatmapsets/most_played",
+            Vec::new(),
+            body,
+        )
+        .await;
+
+        assert_eq!(entry_summary(&entries), [(5, Some(13)), (6, Some(4))]);
+    }
+
+    #[tokio::test]
+    async fn favourite_list_reads_beatmapsets() {
+        let body = format!("[{}, {}]", beatmapset_json(8), beatmapset_json(9));
+
+        let entries = user_list_from_mock(
+            OsuUserListKind::Favourite,
+            "/api/v2/users/7/beatmapsets/favourite",
+            Vec::new(),
+            body,
+        )
+        .await;
+
+        assert_eq!(entry_summary(&entries), [(8, None), (9, None)]);
+        assert_eq!(entries[1].beatmapset.artist, "Artist 9");
+    }
+
+    // 下載資料夾中有 ID 相近的譜面集，處理 123 時不能動到 1234
+    fn download_directory_with_similar_ids() -> TempDir {
+        let dir = tempdir().unwrap();
+        for name in ["123 A.osz", "1234 B.osz", "123.osz"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        fs::create_dir(dir.path().join("123 Foo")).unwrap();
+        dir
+    }
+
+    fn sorted_file_names(paths: &[PathBuf]) -> Vec<String> {
+        let mut names: Vec<String> = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn remaining_file_names(dir: &Path) -> Vec<String> {
+        let paths: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        sorted_file_names(&paths)
+    }
+
+    #[test]
+    fn beatmapset_id_from_file_name_requires_separator_after_id() {
+        assert_eq!(beatmapset_id_from_file_name("123 A.osz"), Some(123));
+        assert_eq!(beatmapset_id_from_file_name("123.osz"), Some(123));
+        assert_eq!(beatmapset_id_from_file_name("123"), Some(123));
+        assert_eq!(beatmapset_id_from_file_name("1234 B.osz"), Some(1234));
+        assert_eq!(beatmapset_id_from_file_name("123abc.osz"), None);
+        assert_eq!(beatmapset_id_from_file_name("A 123.osz"), None);
+    }
+
+    #[test]
+    fn find_beatmap_entries_matches_exact_id_only() {
+        let dir = download_directory_with_similar_ids();
+
+        let entries = find_beatmap_entries(dir.path(), 123).unwrap();
+
+        assert_eq!(
+            sorted_file_names(&entries),
+            ["123 A.osz", "123 Foo", "123.osz"]
+        );
+        assert_eq!(
+            sorted_file_names(&find_beatmap_entries(dir.path(), 1234).unwrap()),
+            ["1234 B.osz"]
+        );
+        assert!(find_beatmap_entries(dir.path(), 12).unwrap().is_empty());
+    }
+
+    #[test]
+    fn delete_beatmap_dry_run_lists_entries_without_deleting() {
+        let dir = download_directory_with_similar_ids();
+
+        let deleted = delete_beatmap(dir.path(), 123, true).unwrap();
+
+        assert_eq!(
+            sorted_file_names(&deleted),
+            ["123 A.osz", "123 Foo", "123.osz"]
+        );
+        assert_eq!(
+            remaining_file_names(dir.path()),
+            ["123 A.osz", "123 Foo", "123.osz", "1234 B.osz"]
+        );
+    }
+
+    #[test]
+    fn delete_beatmap_keeps_beatmapsets_with_longer_ids() {
+        let dir = download_directory_with_similar_ids();
+
+        let deleted = delete_beatmap(dir.path(), 123, false).unwrap();
+
+        assert_eq!(deleted.len(), 3);
+        assert_eq!(remaining_file_names(dir.path()), ["1234 B.osz"]);
+    }
+
+    #[test]
+    fn delete_beatmap_reports_missing_beatmapset() {
+        let dir = download_directory_with_similar_ids();
+
+        let error = delete_beatmap(dir.path(), 12, false).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(remaining_file_names(dir.path()).len(), 4);
+    }
+
+    // 指向模擬伺服器的鏡像站
+    struct MockMirror {
+        name: &'static str,
+        base_url: String,
+    }
+
+    impl BeatmapMirror for MockMirror {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn download_url(&self, beatmapset_id: i32, _variant: DownloadVariant) -> String {
+            format!("{}/d/{}", self.base_url, beatmapset_id)
+        }
+    }
+
+    fn osz_with_osu_file(content: &[u8]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("Artist - Title [Normal].osu", Default::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn osz_with_beatmapset_id(beatmapset_id: i32) -> Vec<u8> {
+        osz_with_osu_file(
+            format!(
+                "osu file format v14\n\n[Metadata]\nVersion:Normal\nBeatmapSetID:{}\n",
+                beatmapset_id
+            )
+            .as_bytes(),
+        )
+    }
+
+    // Title 為 Latin-1 的 "Café"、Artist 為 Shift-JIS 的 "ア"，都不是有效的 UTF-8
+    const NON_UTF8_OSU: &[u8] = b"osu file format v7\n\n[Metadata]\nTitle:Caf\xe9\nArtist:\x83\x41\nVersion:Hard\nBeatmapSetID:321\n";
+
+    #[test]
+    fn verify_osz_archive_accepts_non_utf8_osu_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("321.osz");
+        fs::write(&path, osz_with_osu_file(NON_UTF8_OSU)).unwrap();
+
+        verify_osz_archive(&path, 321).unwrap();
+        let beatmapset = read_local_beatmapset(&path).unwrap();
+        assert_eq!(beatmapset.id, Some(321));
+        assert_eq!(beatmapset.beatmaps[0].version, "Hard");
+    }
+
+    #[test]
+    fn read_local_beatmapset_reads_non_utf8_folder() {
+        let dir = tempdir().unwrap();
+        let folder = dir.path().join("Old Song");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("Old Song [Hard].osu"), NON_UTF8_OSU).unwrap();
+
+        let beatmapset = read_local_beatmapset(&folder).unwrap();
+
+        assert_eq!(beatmapset.id, Some(321));
+        assert!(beatmapset.title.starts_with("Caf"));
+        assert_eq!(beatmapset_id_of_entry(&folder), Some(321));
+    }
+
+    #[test]
+    fn remove_partial_downloads_only_touches_the_beatmapset() {
+        let dir = tempdir().unwrap();
+        for name in [
+            "123.catboy.part",
+            "123.nerinyan.novideo.part",
+            "1234.catboy.part",
+            "123 A.osz",
+        ] {
+            fs::write(dir.path().join(name), b"partial").unwrap();
+        }
+
+        let removed = remove_partial_downloads(dir.path(), 123);
+
+        assert_eq!(
+            sorted_file_names(&removed),
+            ["123.catboy.part", "123.nerinyan.novideo.part"]
+        );
+        assert_eq!(
+            remaining_file_names(dir.path()),
+            ["123 A.osz", "1234.catboy.part"]
+        );
+    }
+
+    #[tokio::test]
+    async fn successful_download_removes_parts_left_by_failed_mirrors() {
+        let mut server = mockito::Server::new_async().await;
+        let broken = server
+            .mock("GET", "/broken/d/123")
+            .with_status(500)
+            .create_async()
+            .await;
+        let working = server
+            .mock("GET", "/working/d/123")
+            .with_header("content-type", "application/octet-stream")
+            .with_header(
+                "content-disposition",
+                "attachment; filename=\"123 Artist - Title.osz\"",
+            )
+            .with_body(osz_with_beatmapset_id(123))
+            .create_async()
+            .await;
+
+        let dir = tempdir().unwrap();
+        let broken_mirror = MockMirror {
+            name: "broken",
+            base_url: format!("{}/broken", server.url()),
+        };
+        // 上次從 broken 下載到一半留下的檔案
+        let broken_part =
+            partial_download_path(dir.path(), 123, &broken_mirror, DownloadVariant::Full);
+        fs::write(&broken_part, b"partial").unwrap();
+        let mirrors = MirrorPool::new(
+            vec![
+                Arc::new(broken_mirror),
+                Arc::new(MockMirror {
+                    name: "working",
+                    base_url: format!("{}/working", server.url()),
+                }),
+            ],
+            OsuLogin::default(),
+        );
+
+        let variant = download_beatmap(
+            123,
+            dir.path(),
+            DownloadVariant::Full,
+            &mirrors,
+            &BandwidthLimiter::new(0),
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        broken.assert_async().await;
+        working.assert_async().await;
+        assert_eq!(variant, DownloadVariant::Full);
+        assert_eq!(remaining_file_names(dir.path()), ["123 Artist - Title.osz"]);
+    }
+}
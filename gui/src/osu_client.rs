@@ -0,0 +1,237 @@
+// 標準庫導入
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// 第三方庫導入
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use thiserror::Error;
+
+// 本地模組導入
+use crate::get_app_data_path;
+use crate::lazer;
+use crate::osu::find_beatmap_entries;
+use crate::osu_db::OSU_DB_FILE_NAME;
+
+// 設定檔存放在應用數據目錄下
+const SETTINGS_FILE_NAME: &str = "osu_client.json";
+
+#[derive(Error, Debug)]
+pub enum OsuClientError {
+    #[error("找不到 osu! 執行檔: {0}")]
+    ExecutableNotFound(PathBuf),
+    #[error("無法啟動 osu!: {0}")]
+    LaunchError(#[from] std::io::Error),
+    #[error("下載資料夾中沒有譜面集 {0} 的 .osz 檔")]
+    ArchiveNotFound(i32),
+    #[error("無法讀取下載資料夾: {0}")]
+    DirectoryError(std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OsuClientKind {
+    #[default]
+    Stable,
+    Lazer,
+}
+
+impl OsuClientKind {
+    pub const ALL: [OsuClientKind; 2] = [OsuClientKind::Stable, OsuClientKind::Lazer];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OsuClientKind::Stable => "osu!stable",
+            OsuClientKind::Lazer => "osu!lazer",
+        }
+    }
+
+    // 預設安裝位置，只有 Windows 有固定路徑
+    pub fn default_executable(&self) -> Option<PathBuf> {
+        if !cfg!(windows) {
+            return None;
+        }
+        let local_data = dirs::data_local_dir()?;
+        let path = match self {
+            OsuClientKind::Stable => local_data.join("osu!").join("osu!.exe"),
+            OsuClientKind::Lazer => local_data.join("osulazer").join("current").join("osu!.exe"),
+        };
+        path.is_file().then_some(path)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OsuClientSettings {
+    pub client: OsuClientKind,
+    // 沒有設定時用系統的檔案關聯開啟 .osz
+    pub executable_path: Option<PathBuf>,
+    // 下載完成後自動交給 osu! 匯入
+    pub auto_import: bool,
+    // lazer 的資料目錄，沒有設定時使用預設位置
+    pub lazer_data_directory: Option<PathBuf>,
+    // 讀不到 lazer 檔案庫時，可以改用匯出的譜面集清單
+    pub lazer_exported_list: Option<PathBuf>,
+    // osu!stable 的安裝資料夾（osu!.db 與 collection.db 所在處）
+    pub stable_directory: Option<PathBuf>,
+}
+
+impl OsuClientSettings {
+    fn settings_path() -> PathBuf {
+        get_app_data_path().join(SETTINGS_FILE_NAME)
+    }
+
+    pub fn load() -> Self {
+        let Ok(content) = fs::read_to_string(Self::settings_path()) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            error!("無法解析 osu! 客戶端設定，改用預設值: {:?}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        fs::create_dir_all(get_app_data_path())?;
+        fs::write(Self::settings_path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // 使用者指定的執行檔優先，否則找預設安裝位置
+    pub fn executable(&self) -> Option<PathBuf> {
+        self.executable_path
+            .clone()
+            .or_else(|| self.client.default_executable())
+    }
+
+    // 沒有設定時依序找預設安裝位置、下載資料夾（通常是 Songs）的上一層
+    pub fn stable_directory(&self, download_directory: &Path) -> Option<PathBuf> {
+        if let Some(directory) = &self.stable_directory {
+            return Some(directory.clone());
+        }
+        OsuClientKind::Stable
+            .default_executable()
+            .and_then(|executable| executable.parent().map(Path::to_path_buf))
+            .into_iter()
+            .chain(download_directory.parent().map(Path::to_path_buf))
+            .find(|directory| directory.join(OSU_DB_FILE_NAME).is_file())
+    }
+
+    pub fn lazer_data_directory(&self) -> Option<PathBuf> {
+        self.lazer_data_directory
+            .clone()
+            .or_else(lazer::default_data_directory)
+    }
+}
+
+// 把 .osz 交給 osu! 匯入；osu! 已在執行時會轉交給執行中的程式，匯入後 .osz 會被移走
+pub fn import_beatmap(settings: &OsuClientSettings, osz_path: &Path) -> Result<(), OsuClientError> {
+    match settings.executable() {
+        Some(executable) => {
+            if !executable.is_file() {
+                return Err(OsuClientError::ExecutableNotFound(executable));
+            }
+            Command::new(&executable).arg(osz_path).spawn()?;
+        }
+        None => open::that(osz_path)?,
+    }
+    info!("已將 {:?} 交給 {} 匯入", osz_path, settings.client.label());
+    Ok(())
+}
+
+// 找出下載資料夾中該譜面集的 .osz 並匯入，已解壓的資料夾 osu! 無法匯入
+pub fn import_downloaded_beatmapset(
+    settings: &OsuClientSettings,
+    download_directory: &Path,
+    beatmapset_id: i32,
+) -> Result<(), OsuClientError> {
+    let archive = find_beatmap_entries(download_directory, beatmapset_id)
+        .map_err(OsuClientError::DirectoryError)?
+        .into_iter()
+        .find(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("osz"))
+        })
+        .ok_or(OsuClientError::ArchiveNotFound(beatmapset_id))?;
+    import_beatmap(settings, &archive)
+}
+
+// 用 osu:// 連結在遊戲內開啟譜面集，沒有註冊協定時回傳 false 讓呼叫端改開網頁
+pub fn open_beatmapset(beatmapset_id: i32) -> bool {
+    if !is_osu_protocol_registered() {
+        return false;
+    }
+    match open::that(format!("osu://s/{}", beatmapset_id)) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("無法開啟 osu:// 連結: {:?}", e);
+            false
+        }
+    }
+}
+
+// 找出正在執行的 osu!，lazer 的執行檔同名，只能從安裝路徑區分
+pub fn running_client() -> Option<OsuClientKind> {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        ProcessRefreshKind::new().with_exe(UpdateKind::OnlyIfNotSet),
+    );
+    system.processes().values().find_map(|process| {
+        let name = process.name().to_string_lossy().to_lowercase();
+        if !name.starts_with("osu!") && !name.starts_with("osu.") {
+            return None;
+        }
+        let is_lazer = process
+            .exe()
+            .map(|exe| exe.to_string_lossy().to_lowercase())
+            .is_some_and(|exe| exe.contains("osulazer") || exe.contains(".appimage"));
+        Some(if is_lazer {
+            OsuClientKind::Lazer
+        } else {
+            OsuClientKind::Stable
+        })
+    })
+}
+
+#[cfg(windows)]
+fn is_osu_protocol_registered() -> bool {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+
+    use winapi::shared::minwindef::HKEY;
+    use winapi::um::winreg::{RegCloseKey, RegOpenKeyExW, HKEY_CLASSES_ROOT};
+
+    let sub_key: Vec<u16> = OsStr::new("osu")
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut hkey: HKEY = ptr::null_mut();
+    let result = unsafe {
+        RegOpenKeyExW(
+            HKEY_CLASSES_ROOT,
+            sub_key.as_ptr(),
+            0,
+            winapi::um::winnt::KEY_READ,
+            &mut hkey,
+        )
+    };
+    if result == 0 {
+        unsafe {
+            RegCloseKey(hkey);
+        }
+        true
+    } else {
+        false
+    }
+}
+
+// 其他平台無法簡單判斷，交給系統處理
+#[cfg(not(windows))]
+fn is_osu_protocol_registered() -> bool {
+    true
+}
@@ -0,0 +1,766 @@
+// 標準庫導入
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// 第三方庫導入
+use log::info;
+use thiserror::Error;
+
+pub const OSU_DB_FILE_NAME: &str = "osu!.db";
+pub const COLLECTION_DB_FILE_NAME: &str = "collection.db";
+// 從這個版本開始難度數值改用 Single，並記錄各模式的星級
+const VERSION_FLOAT_DIFFICULTY: i32 = 20140609;
+// 這個版本之前每個譜面前面有一個 Int 表示長度
+const VERSION_NO_ENTRY_SIZE: i32 = 20191106;
+// 沒有既有的 collection.db 時寫入的版本
+const DEFAULT_COLLECTION_DB_VERSION: i32 = 20150203;
+
+#[derive(Error, Debug)]
+pub enum OsuDbError {
+    #[error("IO 錯誤: {0}")]
+    IoError(#[from] io::Error),
+    #[error("檔案格式錯誤: {0}")]
+    FormatError(String),
+}
+
+// osu! 資料庫使用的小端序二進位格式
+struct DbReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> DbReader<R> {
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], OsuDbError> {
+        let mut buffer = [0u8; N];
+        self.inner.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn skip(&mut self, count: u64) -> Result<(), OsuDbError> {
+        let skipped = io::copy(&mut (&mut self.inner).take(count), &mut io::sink())?;
+        if skipped != count {
+            return Err(OsuDbError::FormatError("檔案提前結束".to_string()));
+        }
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, OsuDbError> {
+        Ok(self.read_bytes::<1>()?[0])
+    }
+
+    fn read_bool(&mut self) -> Result<bool, OsuDbError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    fn read_i16(&mut self) -> Result<i16, OsuDbError> {
+        Ok(i16::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, OsuDbError> {
+        Ok(i32::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_i64(&mut self) -> Result<i64, OsuDbError> {
+        Ok(i64::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, OsuDbError> {
+        Ok(f32::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, OsuDbError> {
+        Ok(f64::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_uleb128(&mut self) -> Result<u64, OsuDbError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+            if shift >= 64 {
+                return Err(OsuDbError::FormatError("字串長度過長".to_string()));
+            }
+        }
+    }
+
+    // 0x00 表示沒有字串，0x0b 後面接 ULEB128 長度與 UTF-8 內容
+    fn read_string(&mut self) -> Result<String, OsuDbError> {
+        match self.read_u8()? {
+            0x00 => Ok(String::new()),
+            0x0b => {
+                let length = self.read_uleb128()?;
+                let mut buffer = Vec::new();
+                (&mut self.inner).take(length).read_to_end(&mut buffer)?;
+                if buffer.len() as u64 != length {
+                    return Err(OsuDbError::FormatError("檔案提前結束".to_string()));
+                }
+                String::from_utf8(buffer)
+                    .map_err(|e| OsuDbError::FormatError(format!("字串不是 UTF-8: {}", e)))
+            }
+            flag => Err(OsuDbError::FormatError(format!(
+                "未知的字串標記 0x{:02x}",
+                flag
+            ))),
+        }
+    }
+}
+
+struct DbWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> DbWriter<W> {
+    fn write_i32(&mut self, value: i32) -> Result<(), OsuDbError> {
+        self.inner.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<(), OsuDbError> {
+        if value.is_empty() {
+            self.inner.write_all(&[0x00])?;
+            return Ok(());
+        }
+        self.inner.write_all(&[0x0b])?;
+        let mut length = value.len() as u64;
+        loop {
+            let mut byte = (length & 0x7f) as u8;
+            length >>= 7;
+            if length != 0 {
+                byte |= 0x80;
+            }
+            self.inner.write_all(&[byte])?;
+            if length == 0 {
+                break;
+            }
+        }
+        self.inner.write_all(value.as_bytes())?;
+        Ok(())
+    }
+}
+
+// osu!.db 中的單一難度，只保留對照收藏需要的欄位
+#[derive(Debug, Clone)]
+pub struct OsuDbBeatmap {
+    pub md5: String,
+    pub artist: String,
+    pub title: String,
+    pub difficulty: String,
+    pub beatmap_id: i32,
+    pub beatmapset_id: i32,
+    pub folder_name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OsuDb {
+    pub version: i32,
+    pub beatmaps: Vec<OsuDbBeatmap>,
+}
+
+impl OsuDb {
+    pub fn read(path: &Path) -> Result<Self, OsuDbError> {
+        let mut reader = DbReader {
+            inner: BufReader::new(File::open(path)?),
+        };
+        let version = reader.read_i32()?;
+        reader.read_i32()?; // 資料夾數量
+        reader.read_bool()?; // 帳號是否解鎖
+        reader.read_i64()?; // 解鎖時間
+        reader.read_string()?; // 玩家名稱
+        let count = reader.read_i32()?;
+
+        let mut beatmaps = Vec::with_capacity(count.max(0) as usize);
+        for _ in 0..count {
+            beatmaps.push(read_beatmap(&mut reader, version)?);
+        }
+        info!("已讀取 osu!.db，共 {} 個難度", beatmaps.len());
+        Ok(Self { version, beatmaps })
+    }
+
+    pub fn find_by_md5(&self, md5: &str) -> Option<&OsuDbBeatmap> {
+        self.beatmaps.iter().find(|beatmap| beatmap.md5 == md5)
+    }
+
+    // 譜面集 ID 對應各難度的 MD5，未上傳的譜面 ID 為 0 或 -1 不列入
+    pub fn hashes_by_beatmapset(&self) -> HashMap<i32, Vec<String>> {
+        let mut hashes: HashMap<i32, Vec<String>> = HashMap::new();
+        for beatmap in &self.beatmaps {
+            if beatmap.beatmapset_id > 0 {
+                hashes
+                    .entry(beatmap.beatmapset_id)
+                    .or_default()
+                    .push(beatmap.md5.clone());
+            }
+        }
+        hashes
+    }
+}
+
+fn read_beatmap<R: Read>(
+    reader: &mut DbReader<R>,
+    version: i32,
+) -> Result<OsuDbBeatmap, OsuDbError> {
+    if version < VERSION_NO_ENTRY_SIZE {
+        reader.read_i32()?;
+    }
+    let artist = reader.read_string()?;
+    reader.read_string()?; // 藝人（Unicode）
+    let title = reader.read_string()?;
+    reader.read_string()?; // 曲名（Unicode）
+    reader.read_string()?; // 作者
+    let difficulty = reader.read_string()?;
+    reader.read_string()?; // 音訊檔名
+    let md5 = reader.read_string()?;
+    reader.read_string()?; // .osu 檔名
+    reader.read_u8()?; // 上架狀態
+    reader.skip(2 * 3)?; // 圓圈、滑條、轉盤數量
+    reader.read_i64()?; // 修改時間
+
+    // AR、CS、HP、OD，舊版各一個 Byte，新版各一個 Single
+    let difficulty_size = if version < VERSION_FLOAT_DIFFICULTY {
+        4
+    } else {
+        16
+    };
+    reader.skip(difficulty_size)?;
+    reader.read_f64()?; // 滑條速度
+    if version >= VERSION_FLOAT_DIFFICULTY {
+        // 四個模式的 mods 與星級
+        for _ in 0..4 {
+            let count = reader.read_i32()?;
+            for _ in 0..count {
+                skip_star_rating_pair(reader)?;
+            }
+        }
+    }
+    reader.skip(4 * 3)?; // 遊玩時間、總長度、預覽時間
+    let timing_points = reader.read_i32()?;
+    reader.skip(17 * timing_points.max(0) as u64)?;
+    let beatmap_id = reader.read_i32()?;
+    let beatmapset_id = reader.read_i32()?;
+    reader.read_i32()?; // 討論串 ID
+    reader.skip(4)?; // 各模式的評級
+    reader.read_i16()?; // 本機偏移
+    reader.read_f32()?; // stack leniency
+    reader.read_u8()?; // 模式
+    reader.read_string()?; // 來源
+    reader.read_string()?; // 標籤
+    reader.read_i16()?; // 線上偏移
+    reader.read_string()?; // 標題字型
+    reader.read_bool()?; // 未遊玩
+    reader.read_i64()?; // 最後遊玩時間
+    reader.read_bool()?; // osz2
+    let folder_name = reader.read_string()?;
+    reader.read_i64()?; // 最後與伺服器比對的時間
+    reader.skip(5)?; // 忽略音效、忽略外觀、停用故事板、停用影片、視覺覆寫
+    if version < VERSION_FLOAT_DIFFICULTY {
+        reader.read_i16()?;
+    }
+    reader.read_i32()?; // 修改時間
+    reader.read_u8()?; // mania 捲動速度
+
+    Ok(OsuDbBeatmap {
+        md5,
+        artist,
+        title,
+        difficulty,
+        beatmap_id,
+        beatmapset_id,
+        folder_name,
+    })
+}
+
+// 0x08 + Int mods，再接 0x0d + Double（舊版）或 0x0c + Single（新版）
+fn skip_star_rating_pair<R: Read>(reader: &mut DbReader<R>) -> Result<(), OsuDbError> {
+    reader.read_u8()?;
+    reader.read_i32()?;
+    match reader.read_u8()? {
+        0x0d => reader.skip(8),
+        0x0c => reader.skip(4),
+        marker => Err(OsuDbError::FormatError(format!(
+            "未知的星級資料標記 0x{:02x}",
+            marker
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Collection {
+    pub name: String,
+    // 收藏以各難度 .osu 檔的 MD5 記錄
+    pub beatmap_hashes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionDb {
+    pub version: i32,
+    pub collections: Vec<Collection>,
+}
+
+impl Default for CollectionDb {
+    fn default() -> Self {
+        Self {
+            version: DEFAULT_COLLECTION_DB_VERSION,
+            collections: Vec::new(),
+        }
+    }
+}
+
+impl CollectionDb {
+    // 檔案不存在時回傳空的收藏
+    pub fn read(path: &Path) -> Result<Self, OsuDbError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut reader = DbReader {
+            inner: BufReader::new(file),
+        };
+        let version = reader.read_i32()?;
+        let count = reader.read_i32()?;
+        let mut collections = Vec::with_capacity(count.max(0) as usize);
+        for _ in 0..count {
+            let name = reader.read_string()?;
+            let beatmap_count = reader.read_i32()?;
+            let beatmap_hashes = (0..beatmap_count)
+                .map(|_| reader.read_string())
+                .collect::<Result<Vec<_>, _>>()?;
+            collections.push(Collection {
+                name,
+                beatmap_hashes,
+            });
+        }
+        Ok(Self {
+            version,
+            collections,
+        })
+    }
+
+    // 先寫到暫存檔再取代，原本的檔案留一份 .bak
+    pub fn write(&self, path: &Path) -> Result<(), OsuDbError> {
+        let temp_path = path.with_extension("db.tmp");
+        {
+            let mut writer = DbWriter {
+                inner: BufWriter::new(File::create(&temp_path)?),
+            };
+            writer.write_i32(self.version)?;
+            writer.write_i32(self.collections.len() as i32)?;
+            for collection in &self.collections {
+                writer.write_string(&collection.name)?;
+                writer.write_i32(collection.beatmap_hashes.len() as i32)?;
+                for hash in &collection.beatmap_hashes {
+                    writer.write_string(hash)?;
+                }
+            }
+            writer.inner.flush()?;
+        }
+        if path.exists() {
+            fs::copy(path, path.with_extension("db.bak"))?;
+        }
+        fs::rename(&temp_path, path)?;
+        info!("已寫入 {:?}，共 {} 個收藏", path, self.collections.len());
+        Ok(())
+    }
+
+    // 加入指定名稱的收藏，不存在就建立；回傳實際新增的數量
+    pub fn add_to_collection(&mut self, name: &str, hashes: &[String]) -> usize {
+        let index = match self.collections.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => {
+                self.collections.push(Collection {
+                    name: name.to_string(),
+                    beatmap_hashes: Vec::new(),
+                });
+                self.collections.len() - 1
+            }
+        };
+        let collection = &mut self.collections[index];
+        let mut existing: HashSet<String> = collection.beatmap_hashes.iter().cloned().collect();
+        let before = collection.beatmap_hashes.len();
+        for hash in hashes {
+            if existing.insert(hash.clone()) {
+                collection.beatmap_hashes.push(hash.clone());
+            }
+        }
+        collection.beatmap_hashes.len() - before
+    }
+}
+
+// 譜面集 ID 對應所屬的收藏名稱，透過 osu!.db 把 MD5 換成譜面集
+pub fn collections_by_beatmapset(
+    collection_db: &CollectionDb,
+    osu_db: &OsuDb,
+) -> HashMap<i32, Vec<String>> {
+    let beatmapset_by_md5: HashMap<&str, i32> = osu_db
+        .beatmaps
+        .iter()
+        .filter(|beatmap| beatmap.beatmapset_id > 0)
+        .map(|beatmap| (beatmap.md5.as_str(), beatmap.beatmapset_id))
+        .collect();
+
+    let mut result: HashMap<i32, Vec<String>> = HashMap::new();
+    for collection in &collection_db.collections {
+        let ids: HashSet<i32> = collection
+            .beatmap_hashes
+            .iter()
+            .filter_map(|hash| beatmapset_by_md5.get(hash.as_str()).copied())
+            .collect();
+        for id in ids {
+            result.entry(id).or_default().push(collection.name.clone());
+        }
+    }
+    result
+}
+
+// 讀取 osu!stable 資料夾中的 osu!.db 與 collection.db，建立譜面集對應收藏的索引
+pub fn read_collection_index(
+    stable_directory: &Path,
+) -> Result<HashMap<i32, Vec<String>>, OsuDbError> {
+    let osu_db = OsuDb::read(&stable_directory.join(OSU_DB_FILE_NAME))?;
+    let collection_db = CollectionDb::read(&stable_directory.join(COLLECTION_DB_FILE_NAME))?;
+    Ok(collections_by_beatmapset(&collection_db, &osu_db))
+}
+
+// 建立收藏的結果
+#[derive(Debug, Clone, Default)]
+pub struct CollectionUpdate {
+    pub added: usize,
+    // 還沒匯入 osu!（不在 osu!.db 中）的譜面集，無法取得 MD5
+    pub missing: Vec<i32>,
+}
+
+// 把譜面集的所有難度加入 osu!stable 的收藏
+pub fn add_beatmapsets_to_collection(
+    stable_directory: &Path,
+    name: &str,
+    beatmapset_ids: &[i32],
+) -> Result<CollectionUpdate, OsuDbError> {
+    let osu_db = OsuDb::read(&stable_directory.join(OSU_DB_FILE_NAME))?;
+    let hashes_by_beatmapset = osu_db.hashes_by_beatmapset();
+
+    let mut update = CollectionUpdate::default();
+    let mut hashes = Vec::new();
+    for id in beatmapset_ids {
+        match hashes_by_beatmapset.get(id) {
+            Some(set_hashes) => hashes.extend(set_hashes.iter().cloned()),
+            None => update.missing.push(*id),
+        }
+    }
+
+    if hashes.is_empty() {
+        return Ok(update);
+    }
+
+    let collection_path = stable_directory.join(COLLECTION_DB_FILE_NAME);
+    let mut collection_db = CollectionDb::read(&collection_path)?;
+    update.added = collection_db.add_to_collection(name, &hashes);
+    collection_db.write(&collection_path)?;
+    Ok(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // 星級資料的寫法：舊版 0x0d + Double，新版 0x0c + Single
+    #[derive(Clone, Copy)]
+    enum StarRating {
+        Double,
+        Single,
+    }
+
+    // 依 osu!.db 的格式手動組出位元組，不經過 DbWriter
+    #[derive(Default)]
+    struct Bytes(Vec<u8>);
+
+    impl Bytes {
+        fn u8(&mut self, value: u8) -> &mut Self {
+            self.0.push(value);
+            self
+        }
+
+        fn i16(&mut self, value: i16) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn i32(&mut self, value: i32) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn i64(&mut self, value: i64) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn f32(&mut self, value: f32) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn f64(&mut self, value: f64) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        // 測試用的字串都短於 128 位元組，長度只佔一個位元組
+        fn string(&mut self, value: &str) -> &mut Self {
+            if value.is_empty() {
+                return self.u8(0x00);
+            }
+            self.u8(0x0b).u8(value.len() as u8);
+            self.0.extend_from_slice(value.as_bytes());
+            self
+        }
+    }
+
+    fn beatmap_entry(
+        version: i32,
+        md5: &str,
+        beatmap_id: i32,
+        beatmapset_id: i32,
+        star_rating: StarRating,
+    ) -> Vec<u8> {
+        let mut entry = Bytes::default();
+        entry
+            .string("Kana Hanazawa")
+            .string("花澤香菜")
+            .string("Renai Circulation")
+            .string("恋愛サーキュレーション")
+            .string("mapper")
+            .string("Insane")
+            .string("audio.mp3")
+            .string(md5)
+            .string("map.osu")
+            .u8(4)
+            .i16(100)
+            .i16(20)
+            .i16(1)
+            .i64(637_000_000_000_000_000);
+        if version < VERSION_FLOAT_DIFFICULTY {
+            entry.u8(9).u8(4).u8(6).u8(8);
+        } else {
+            entry.f32(9.0).f32(4.0).f32(6.0).f32(8.0);
+        }
+        entry.f64(1.4);
+        if version >= VERSION_FLOAT_DIFFICULTY {
+            for _ in 0..4 {
+                entry.i32(2);
+                for mods in [0, 64] {
+                    entry.u8(0x08).i32(mods);
+                    match star_rating {
+                        StarRating::Double => entry.u8(0x0d).f64(5.25),
+                        StarRating::Single => entry.u8(0x0c).f32(5.25),
+                    };
+                }
+            }
+        }
+        entry.i32(250).i32(255_000).i32(60_000);
+        entry.i32(2);
+        for offset in [0.0, 1000.0] {
+            entry.f64(333.33).f64(offset).u8(1);
+        }
+        entry
+            .i32(beatmap_id)
+            .i32(beatmapset_id)
+            .i32(0)
+            .u8(9)
+            .u8(9)
+            .u8(9)
+            .u8(9)
+            .i16(0)
+            .f32(0.7)
+            .u8(0)
+            .string("")
+            .string("anime")
+            .i16(0)
+            .string("")
+            .u8(1)
+            .i64(0)
+            .u8(0)
+            .string(&format!(
+                "{} Kana Hanazawa - Renai Circulation",
+                beatmapset_id
+            ))
+            .i64(0)
+            .u8(0)
+            .u8(0)
+            .u8(0)
+            .u8(0)
+            .u8(0);
+        if version < VERSION_FLOAT_DIFFICULTY {
+            entry.i16(0);
+        }
+        entry.i32(0).u8(0);
+
+        // 舊版每個譜面前面有長度
+        if version < VERSION_NO_ENTRY_SIZE {
+            let mut sized = Bytes::default();
+            sized.i32(entry.0.len() as i32);
+            sized.0.extend(entry.0);
+            sized.0
+        } else {
+            entry.0
+        }
+    }
+
+    fn osu_db_bytes(version: i32, star_rating: StarRating) -> Vec<u8> {
+        let mut db = Bytes::default();
+        db.i32(version).i32(2).u8(1).i64(0).string("player").i32(3);
+        let entries = [
+            beatmap_entry(version, "aaaa", 101, 100, star_rating),
+            beatmap_entry(version, "bbbb", 102, 100, star_rating),
+            // 未上傳的譜面，譜面集 ID 為 -1
+            beatmap_entry(version, "cccc", 0, -1, star_rating),
+        ];
+        for entry in entries {
+            db.0.extend(entry);
+        }
+        db.i32(0); // 使用者權限
+        db.0
+    }
+
+    fn read_osu_db(bytes: &[u8]) -> Result<OsuDb, OsuDbError> {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join(OSU_DB_FILE_NAME);
+        fs::write(&path, bytes).unwrap();
+        OsuDb::read(&path)
+    }
+
+    fn assert_fixture(version: i32, star_rating: StarRating) {
+        let osu_db = read_osu_db(&osu_db_bytes(version, star_rating)).unwrap();
+        assert_eq!(osu_db.version, version);
+        assert_eq!(osu_db.beatmaps.len(), 3);
+
+        // 欄位位置錯一個位元組，後面的譜面就會讀錯
+        let last = &osu_db.beatmaps[2];
+        assert_eq!(last.md5, "cccc");
+        assert_eq!(last.beatmapset_id, -1);
+
+        let first = osu_db.find_by_md5("aaaa").unwrap();
+        assert_eq!(first.artist, "Kana Hanazawa");
+        assert_eq!(first.title, "Renai Circulation");
+        assert_eq!(first.difficulty, "Insane");
+        assert_eq!(first.beatmap_id, 101);
+        assert_eq!(first.beatmapset_id, 100);
+        assert_eq!(first.folder_name, "100 Kana Hanazawa - Renai Circulation");
+
+        let hashes = osu_db.hashes_by_beatmapset();
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes[&100], vec!["aaaa".to_string(), "bbbb".to_string()]);
+    }
+
+    #[test]
+    fn reads_byte_difficulty_with_entry_size() {
+        assert_fixture(VERSION_FLOAT_DIFFICULTY - 1, StarRating::Double);
+    }
+
+    #[test]
+    fn reads_float_difficulty_with_entry_size() {
+        assert_fixture(VERSION_FLOAT_DIFFICULTY, StarRating::Double);
+        assert_fixture(VERSION_NO_ENTRY_SIZE - 1, StarRating::Double);
+    }
+
+    #[test]
+    fn reads_entries_without_entry_size() {
+        assert_fixture(VERSION_NO_ENTRY_SIZE, StarRating::Double);
+    }
+
+    #[test]
+    fn reads_single_precision_star_ratings() {
+        assert_fixture(20250108, StarRating::Single);
+    }
+
+    #[test]
+    fn rejects_unknown_star_rating_marker() {
+        let mut bytes = osu_db_bytes(VERSION_NO_ENTRY_SIZE, StarRating::Single);
+        // 第一個星級標記在 mods 之後
+        let position = bytes
+            .windows(6)
+            .position(|window| window == [0x08, 0, 0, 0, 0, 0x0c])
+            .unwrap();
+        bytes[position + 5] = 0x0e;
+        assert!(matches!(
+            read_osu_db(&bytes),
+            Err(OsuDbError::FormatError(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let bytes = osu_db_bytes(VERSION_NO_ENTRY_SIZE, StarRating::Double);
+        assert!(read_osu_db(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn collection_db_round_trip() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join(COLLECTION_DB_FILE_NAME);
+        let collection_db = CollectionDb {
+            version: 20240101,
+            collections: vec![
+                Collection {
+                    name: "Spotify 喜歡的歌曲".to_string(),
+                    beatmap_hashes: vec!["aaaa".to_string(), "bbbb".to_string()],
+                },
+                Collection {
+                    name: "空的收藏".to_string(),
+                    beatmap_hashes: Vec::new(),
+                },
+                Collection {
+                    // 超過 127 位元組，長度需要兩個位元組的 ULEB128
+                    name: "長".repeat(50),
+                    beatmap_hashes: vec!["cccc".to_string()],
+                },
+            ],
+        };
+
+        collection_db.write(&path).unwrap();
+        assert_eq!(CollectionDb::read(&path).unwrap(), collection_db);
+        assert!(!path.with_extension("db.bak").exists());
+
+        // 第二次寫入時保留原本的檔案
+        let mut updated = collection_db.clone();
+        assert_eq!(
+            updated.add_to_collection(
+                "Spotify 喜歡的歌曲",
+                &["bbbb".to_string(), "dddd".to_string()]
+            ),
+            1
+        );
+        updated.write(&path).unwrap();
+        assert_eq!(CollectionDb::read(&path).unwrap(), updated);
+        assert_eq!(
+            CollectionDb::read(&path.with_extension("db.bak")).unwrap(),
+            collection_db
+        );
+    }
+
+    #[test]
+    fn missing_collection_db_is_empty() {
+        let directory = tempdir().unwrap();
+        let collection_db =
+            CollectionDb::read(&directory.path().join(COLLECTION_DB_FILE_NAME)).unwrap();
+        assert_eq!(collection_db, CollectionDb::default());
+    }
+
+    #[test]
+    fn maps_collections_to_beatmapsets() {
+        let osu_db = read_osu_db(&osu_db_bytes(VERSION_NO_ENTRY_SIZE, StarRating::Single)).unwrap();
+        let mut collection_db = CollectionDb::default();
+        collection_db.add_to_collection("最愛", &["aaaa".to_string(), "bbbb".to_string()]);
+        collection_db.add_to_collection("未上傳", &["cccc".to_string()]);
+
+        let index = collections_by_beatmapset(&collection_db, &osu_db);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[&100], vec!["最愛".to_string()]);
+    }
+}
@@ -0,0 +1,1047 @@
+// 標準庫導入
+use std::fmt;
+
+// 第三方庫導入
+use log::{debug, error, info};
+use reqwest::Client;
+use serde::Serialize;
+use thiserror::Error;
+
+// 本地模組導入
+use crate::matching::{best_track_for_song, rank_beatmapsets, BestMatch, MatchScore, SongFields};
+use crate::normalize::{strip_artist_tags, strip_title_tags};
+use crate::osu::{
+    get_beatmapset_by_id, get_beatmapsets_page, get_osu_token, get_osu_token_with_credentials,
+    parse_osu_url, BeatmapSearchFilter, Beatmapset, BeatmapsetPage, OSU_BASE_URL,
+};
+use crate::spotify::{
+    get_access_token, get_access_token_with_credentials, get_album_tracks, get_artist_top_tracks,
+    get_public_playlist_tracks, get_track_info, is_valid_spotify_url, parse_spotify_url,
+    search_spotify_resource, search_track, SpotifyError, SpotifyResource, SpotifySearchType,
+    SpotifyUrlStatus, Track, SPOTIFY_ACCOUNTS_BASE_URL, SPOTIFY_API_BASE_URL,
+};
+use crate::ServiceConfig;
+
+// 從 osu! 譜面反查 Spotify 時只取前幾筆，結果越後面越不相關
+const REVERSE_SEARCH_LIMIT: u32 = 10;
+// Spotify 搜尋 API 最多只能翻到第 1000 筆
+pub const SPOTIFY_MAX_OFFSET: u32 = 1000;
+// 專輯、藝人、播放清單展開後最多交叉搜尋的曲目數，避免大型播放清單打爆 osu! API
+const COLLECTION_TRACK_LIMIT: usize = 50;
+// 展開搜尋時每首曲目保留的 osu! 譜面數
+const MAPS_PER_TRACK: usize = 3;
+
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("搜尋內容不可為空")]
+    EmptyQuery,
+    #[error("Spotify URL 不完整，請輸入完整的 URL")]
+    IncompleteSpotifyUrl,
+    #[error("無效的 Spotify URL")]
+    InvalidSpotifyUrl,
+    #[error("Spotify 錯誤：{0}")]
+    Spotify(String),
+    #[error("Osu 錯誤：{0}")]
+    Osu(String),
+}
+
+// 查詢字串被解讀的方式
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueryKind {
+    // osu! 譜面集網址，反查 Spotify
+    OsuBeatmapset { beatmapset_id: String },
+    // Spotify 曲目網址，查詢 osu! 譜面
+    SpotifyTrack { track_id: String },
+    // Spotify 專輯、藝人、播放清單網址，展開成曲目後逐首查詢 osu! 譜面
+    SpotifyAlbum { album_id: String },
+    SpotifyArtist { artist_id: String },
+    SpotifyPlaylist { playlist_id: String },
+    // 一般關鍵字，兩邊同時搜尋
+    Keyword,
+}
+
+impl fmt::Display for QueryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryKind::OsuBeatmapset { beatmapset_id } => {
+                write!(f, "osu! 譜面集 {}", beatmapset_id)
+            }
+            QueryKind::SpotifyTrack { track_id } => write!(f, "Spotify 曲目 {}", track_id),
+            QueryKind::SpotifyAlbum { album_id } => write!(f, "Spotify 專輯 {}", album_id),
+            QueryKind::SpotifyArtist { artist_id } => write!(f, "Spotify 藝人 {}", artist_id),
+            QueryKind::SpotifyPlaylist { playlist_id } => {
+                write!(f, "Spotify 播放清單 {}", playlist_id)
+            }
+            QueryKind::Keyword => write!(f, "關鍵字"),
+        }
+    }
+}
+
+// 單頁的 Spotify 搜尋結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrackPage {
+    pub tracks: Vec<Track>,
+    pub total: u32,
+}
+
+// 展開搜尋時，單首曲目在 osu! 上的最佳匹配
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackMatch {
+    pub track_index: usize,
+    pub best_match: Option<BestMatch>,
+}
+
+// 一次交叉搜尋的結果
+#[derive(Serialize)]
+pub struct CrossSearchResult {
+    pub query: String,
+    pub kind: QueryKind,
+    pub spotify_tracks: Vec<Track>,
+    pub osu_beatmapsets: Vec<Beatmapset>,
+    // Spotify 實際送出的查詢字串與符合條件的曲目總數，供分頁使用
+    pub spotify_query: Option<String>,
+    pub spotify_total: u32,
+    // 以 Spotify 曲目查詢時，osu! 結果依匹配分數排序後的第一名
+    pub best_match: Option<BestMatch>,
+    // 專輯、藝人、播放清單展開後每首曲目的匹配結果
+    pub track_matches: Vec<TrackMatch>,
+    // osu! 實際送出的查詢字串與下一頁游標，供載入更多結果使用
+    pub osu_query: Option<String>,
+    pub osu_cursor: Option<String>,
+    pub osu_total: u32,
+}
+
+// 與介面無關的交叉搜尋引擎，GUI 與命令列共用
+#[derive(Clone)]
+pub struct CrossSearchEngine {
+    client: Client,
+    debug_mode: bool,
+    // API 位址，預設為官方伺服器，測試時可指向本機模擬伺服器
+    spotify_api_url: String,
+    spotify_accounts_url: String,
+    osu_url: String,
+    // 取得 token 用的憑證，None 時從設定檔讀取
+    spotify_credentials: Option<ServiceConfig>,
+    osu_credentials: Option<ServiceConfig>,
+    spotify_limit: u32,
+    spotify_search_type: SpotifySearchType,
+    osu_filter: BeatmapSearchFilter,
+}
+
+impl CrossSearchEngine {
+    pub fn new(client: Client, debug_mode: bool) -> Self {
+        Self {
+            client,
+            debug_mode,
+            spotify_api_url: SPOTIFY_API_BASE_URL.to_string(),
+            spotify_accounts_url: SPOTIFY_ACCOUNTS_BASE_URL.to_string(),
+            osu_url: OSU_BASE_URL.to_string(),
+            spotify_credentials: None,
+            osu_credentials: None,
+            spotify_limit: 50,
+            spotify_search_type: SpotifySearchType::default(),
+            osu_filter: BeatmapSearchFilter::default(),
+        }
+    }
+
+    // 設定 Spotify API、Spotify 帳號服務與 osu! 的位址（不含路徑）及對應的憑證，
+    // 之後取得 token 時不再讀取設定檔
+    pub fn with_endpoints(
+        mut self,
+        spotify_api: &str,
+        spotify_accounts: &str,
+        osu_api: &str,
+        spotify_credentials: ServiceConfig,
+        osu_credentials: ServiceConfig,
+    ) -> Self {
+        self.spotify_api_url = spotify_api.trim_end_matches('/').to_string();
+        self.spotify_accounts_url = spotify_accounts.trim_end_matches('/').to_string();
+        self.osu_url = osu_api.trim_end_matches('/').to_string();
+        self.spotify_credentials = Some(spotify_credentials);
+        self.osu_credentials = Some(osu_credentials);
+        self
+    }
+
+    // 設定關鍵字搜尋時 Spotify 回傳的曲目數量
+    pub fn with_spotify_limit(mut self, limit: u32) -> Self {
+        self.spotify_limit = limit;
+        self
+    }
+
+    // 設定關鍵字搜尋的類型（曲目、專輯、藝人、播放清單）
+    pub fn with_spotify_search_type(mut self, search_type: SpotifySearchType) -> Self {
+        self.spotify_search_type = search_type;
+        self
+    }
+
+    // 設定 osu! 譜面搜尋條件（模式、狀態、星級等）
+    pub fn with_osu_filter(mut self, filter: BeatmapSearchFilter) -> Self {
+        self.osu_filter = filter;
+        self
+    }
+
+    // 判斷查詢字串是 osu! 網址、Spotify 網址還是一般關鍵字
+    pub fn classify_query(query: &str) -> Result<QueryKind, SearchError> {
+        let query = query.trim();
+
+        if let Some((beatmapset_id, _)) = parse_osu_url(query) {
+            return Ok(QueryKind::OsuBeatmapset { beatmapset_id });
+        }
+
+        let status = is_valid_spotify_url(query).map_err(|e| {
+            error!("驗證 Spotify URL 時發生錯誤: {:?}", e);
+            SearchError::Spotify("URL 驗證錯誤".to_string())
+        })?;
+
+        match status {
+            SpotifyUrlStatus::Valid => match parse_spotify_url(query) {
+                Some(SpotifyResource::Track(track_id)) => Ok(QueryKind::SpotifyTrack { track_id }),
+                Some(SpotifyResource::Album(album_id)) => Ok(QueryKind::SpotifyAlbum { album_id }),
+                Some(SpotifyResource::Artist(artist_id)) => {
+                    Ok(QueryKind::SpotifyArtist { artist_id })
+                }
+                Some(SpotifyResource::Playlist(playlist_id)) => {
+                    Ok(QueryKind::SpotifyPlaylist { playlist_id })
+                }
+                None => Err(SearchError::InvalidSpotifyUrl),
+            },
+            SpotifyUrlStatus::Incomplete => Err(SearchError::IncompleteSpotifyUrl),
+            SpotifyUrlStatus::Invalid => Err(SearchError::InvalidSpotifyUrl),
+            SpotifyUrlStatus::NotSpotify => {
+                if query.is_empty() {
+                    Err(SearchError::EmptyQuery)
+                } else {
+                    Ok(QueryKind::Keyword)
+                }
+            }
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<CrossSearchResult, SearchError> {
+        let query = query.trim();
+        let kind = Self::classify_query(query)?;
+
+        if self.debug_mode {
+            debug!("查詢類型: {}", kind);
+        }
+
+        let mut best_match = None;
+        let mut track_matches = Vec::new();
+        let mut osu_query = None;
+        let mut spotify_query = None;
+        let (spotify_page, osu_page) = match &kind {
+            QueryKind::OsuBeatmapset { beatmapset_id } => {
+                info!("Osu 搜尋: {}", query);
+                let (tracks, sent_query, page) = self.search_from_osu(beatmapset_id).await?;
+                spotify_query = Some(sent_query);
+                (tracks, page)
+            }
+            QueryKind::SpotifyTrack { track_id } => {
+                info!("Spotify 查詢 (URL): {}", query);
+                let (tracks, sent_query, mut page) = self.search_from_spotify(track_id).await?;
+                // 只排序第一頁，之後載入的頁面依 API 順序接在後面
+                let (ranked, best) = rank_beatmapsets(&tracks[0], page.beatmapsets);
+                if let Some(best) = &best {
+                    info!(
+                        "最佳匹配: beatmapset {} (信心度 {:.2})",
+                        best.beatmapset_id, best.score.confidence
+                    );
+                }
+                page.beatmapsets = ranked;
+                best_match = best;
+                osu_query = Some(sent_query);
+                (TrackPage { tracks, total: 1 }, page)
+            }
+            QueryKind::SpotifyAlbum { album_id } => {
+                info!("Spotify 查詢 (專輯): {}", query);
+                let resource = SpotifyResource::Album(album_id.clone());
+                let spotify_token = self.spotify_token().await?;
+                let (page, osu_page, matches) =
+                    self.search_collection(&spotify_token, &resource).await?;
+                track_matches = matches;
+                (page, osu_page)
+            }
+            QueryKind::SpotifyArtist { artist_id } => {
+                info!("Spotify 查詢 (藝人): {}", query);
+                let resource = SpotifyResource::Artist(artist_id.clone());
+                let spotify_token = self.spotify_token().await?;
+                let (page, osu_page, matches) =
+                    self.search_collection(&spotify_token, &resource).await?;
+                track_matches = matches;
+                (page, osu_page)
+            }
+            QueryKind::SpotifyPlaylist { playlist_id } => {
+                info!("Spotify 查詢 (播放清單): {}", query);
+                let resource = SpotifyResource::Playlist(playlist_id.clone());
+                let spotify_token = self.spotify_token().await?;
+                let (page, osu_page, matches) =
+                    self.search_collection(&spotify_token, &resource).await?;
+                track_matches = matches;
+                (page, osu_page)
+            }
+            QueryKind::Keyword if self.spotify_search_type != SpotifySearchType::Track => {
+                let search_type = self.spotify_search_type;
+                info!("Spotify 查詢 ({}): {}", search_type.label(), query);
+                let spotify_token = self.spotify_token().await?;
+                let (resource, name) = search_spotify_resource(
+                    &self.client,
+                    &self.spotify_api_url,
+                    query,
+                    search_type,
+                    &spotify_token,
+                )
+                .await
+                .map_err(|e| {
+                    error!("Spotify 搜索錯誤: {:?}", e);
+                    SearchError::Spotify("搜索失敗".to_string())
+                })?
+                .ok_or_else(|| {
+                    SearchError::Spotify(format!("找不到符合的{}", search_type.label()))
+                })?;
+                info!("展開 Spotify {}: {}", search_type.label(), name);
+                let (page, osu_page, matches) =
+                    self.search_collection(&spotify_token, &resource).await?;
+                track_matches = matches;
+                (page, osu_page)
+            }
+            QueryKind::Keyword => {
+                info!("Spotify 查詢 (關鍵字): {}", query);
+                osu_query = Some(query.to_string());
+                spotify_query = Some(query.to_string());
+                self.search_keyword(query).await?
+            }
+        };
+        let spotify_tracks = spotify_page.tracks;
+        let osu_beatmapsets = osu_page.beatmapsets;
+
+        info!(
+            "搜尋完成: {} 首 Spotify 曲目, {} 個 osu beatmapsets",
+            spotify_tracks.len(),
+            osu_beatmapsets.len()
+        );
+
+        Ok(CrossSearchResult {
+            query: query.to_string(),
+            kind,
+            spotify_tracks,
+            osu_beatmapsets,
+            spotify_query,
+            spotify_total: spotify_page.total,
+            best_match,
+            track_matches,
+            osu_query,
+            osu_cursor: osu_page.cursor_string,
+            osu_total: osu_page.total,
+        })
+    }
+
+    // 以上一次搜尋的 spotify_query 從 offset 開始取得下一頁曲目
+    pub async fn next_spotify_page(
+        &self,
+        spotify_query: &str,
+        offset: u32,
+    ) -> Result<TrackPage, SearchError> {
+        let spotify_token = self.spotify_token().await?;
+        info!("Spotify 載入下一頁: {} (offset {})", spotify_query, offset);
+        self.search_spotify(&spotify_token, spotify_query, self.spotify_limit, offset)
+            .await
+    }
+
+    // 以上一次搜尋的 osu_query 與 osu_cursor 取得下一頁譜面
+    pub async fn next_osu_page(
+        &self,
+        osu_query: &str,
+        cursor: &str,
+    ) -> Result<BeatmapsetPage, SearchError> {
+        let osu_token = self.osu_token().await?;
+        info!("Osu 載入下一頁: {}", osu_query);
+        self.search_osu(&osu_token, osu_query, Some(cursor)).await
+    }
+
+    // 替單首曲目搜尋 osu! 並回傳最佳匹配的譜面集，供批次比對使用
+    pub(crate) async fn best_beatmapset_for_track(
+        &self,
+        osu_token: &str,
+        track: &Track,
+    ) -> Result<Option<(Beatmapset, BestMatch)>, SearchError> {
+        let osu_query = osu_query_for_track(track);
+        let page = self.search_osu(osu_token, &osu_query, None).await?;
+        let (ranked, best) = rank_beatmapsets(track, page.beatmapsets);
+        Ok(best.and_then(|best| {
+            ranked
+                .into_iter()
+                .find(|b| b.id == best.beatmapset_id)
+                .map(|beatmapset| (beatmapset, best))
+        }))
+    }
+
+    // 以本機譜面的歌曲資訊搜尋 Spotify，回傳最相符的曲目，供反向批次比對使用
+    pub(crate) async fn best_track_for_song(
+        &self,
+        spotify_token: &str,
+        song: &SongFields<'_>,
+    ) -> Result<Option<(Track, MatchScore)>, SearchError> {
+        let spotify_query = spotify_query_for_song(song);
+        let page = self
+            .search_spotify(spotify_token, &spotify_query, REVERSE_SEARCH_LIMIT, 0)
+            .await?;
+        Ok(best_track_for_song(&page.tracks, song)
+            .map(|(index, score)| (page.tracks[index].clone(), score)))
+    }
+
+    // 以譜面集 ID 取得譜面資訊，本機譜面讀不到 .osu 檔時使用
+    pub(crate) async fn beatmapset_by_id(
+        &self,
+        osu_token: &str,
+        beatmapset_id: i32,
+    ) -> Result<Beatmapset, SearchError> {
+        get_beatmapset_by_id(
+            &self.client,
+            &self.osu_url,
+            osu_token,
+            &beatmapset_id.to_string(),
+            self.debug_mode,
+        )
+        .await
+        .map_err(|e| {
+            error!("獲取 Osu 譜面錯誤: {:?}", e);
+            SearchError::Osu("獲取譜面失敗".to_string())
+        })
+    }
+
+    async fn search_from_osu(
+        &self,
+        beatmapset_id: &str,
+    ) -> Result<(TrackPage, String, BeatmapsetPage), SearchError> {
+        let osu_token = self.osu_token().await?;
+        let beatmapset = get_beatmapset_by_id(
+            &self.client,
+            &self.osu_url,
+            &osu_token,
+            beatmapset_id,
+            self.debug_mode,
+        )
+        .await
+        .map_err(|e| {
+            error!("獲取 Osu 譜面錯誤: {:?}", e);
+            SearchError::Osu("獲取譜面失敗".to_string())
+        })?;
+
+        let spotify_query = spotify_query_for_song(&SongFields::from(&beatmapset));
+        info!("Spotify 查詢 (從 osu): {}", spotify_query);
+
+        let spotify_token = self.spotify_token().await?;
+        let spotify_page = self
+            .search_spotify(
+                &spotify_token,
+                &spotify_query,
+                self.spotify_limit.min(REVERSE_SEARCH_LIMIT),
+                0,
+            )
+            .await
+            .map_err(|_| SearchError::Spotify("反搜索失敗".to_string()))?;
+
+        Ok((
+            spotify_page,
+            spotify_query,
+            BeatmapsetPage {
+                beatmapsets: vec![beatmapset],
+                cursor_string: None,
+                total: 1,
+            },
+        ))
+    }
+
+    async fn search_from_spotify(
+        &self,
+        track_id: &str,
+    ) -> Result<(Vec<Track>, String, BeatmapsetPage), SearchError> {
+        let spotify_token = self.spotify_token().await?;
+        let mut track = get_track_info(
+            &self.client,
+            &self.spotify_api_url,
+            track_id,
+            &spotify_token,
+        )
+        .await
+        .map_err(|e| SearchError::Spotify(format!("獲取曲目資訊錯誤: {}", e)))?;
+        track.index = 0;
+
+        let osu_query = osu_query_for_track(&track);
+        info!("Osu 查詢 (從 Spotify): {}", osu_query);
+
+        let osu_token = self.osu_token().await?;
+        let page = self.search_osu(&osu_token, &osu_query, None).await?;
+        Ok((vec![track], osu_query, page))
+    }
+
+    // 將專輯、藝人或播放清單展開成曲目，逐首在 osu! 上搜尋並保留分數最高的幾個譜面
+    async fn search_collection(
+        &self,
+        spotify_token: &str,
+        resource: &SpotifyResource,
+    ) -> Result<(TrackPage, BeatmapsetPage, Vec<TrackMatch>), SearchError> {
+        let mut tracks = self.expand_collection(spotify_token, resource).await?;
+        let total = tracks.len() as u32;
+        if tracks.len() > COLLECTION_TRACK_LIMIT {
+            info!(
+                "曲目共 {} 首，只交叉搜尋前 {} 首",
+                tracks.len(),
+                COLLECTION_TRACK_LIMIT
+            );
+            tracks.truncate(COLLECTION_TRACK_LIMIT);
+        }
+
+        let mut beatmapsets: Vec<Beatmapset> = Vec::new();
+        let mut track_matches = Vec::new();
+        // 展開後沒有曲目時不必再向 osu! 取得 token
+        let osu_token = if tracks.is_empty() {
+            String::new()
+        } else {
+            self.osu_token().await?
+        };
+        for (track_index, track) in tracks.iter().enumerate() {
+            let osu_query = osu_query_for_track(track);
+            // 單首失敗不影響整張專輯，記錄後繼續
+            let page = match self.search_osu(&osu_token, &osu_query, None).await {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("Osu 搜索 \"{}\" 失敗: {:?}", osu_query, e);
+                    track_matches.push(TrackMatch {
+                        track_index,
+                        best_match: None,
+                    });
+                    continue;
+                }
+            };
+
+            let (ranked, best) = rank_beatmapsets(track, page.beatmapsets);
+            for beatmapset in ranked.into_iter().take(MAPS_PER_TRACK) {
+                if !beatmapsets.iter().any(|b| b.id == beatmapset.id) {
+                    beatmapsets.push(beatmapset);
+                }
+            }
+            track_matches.push(TrackMatch {
+                track_index,
+                best_match: best,
+            });
+        }
+
+        let osu_total = beatmapsets.len() as u32;
+        Ok((
+            TrackPage { tracks, total },
+            BeatmapsetPage {
+                beatmapsets,
+                cursor_string: None,
+                total: osu_total,
+            },
+            track_matches,
+        ))
+    }
+
+    async fn expand_collection(
+        &self,
+        spotify_token: &str,
+        resource: &SpotifyResource,
+    ) -> Result<Vec<Track>, SearchError> {
+        let result = match resource {
+            SpotifyResource::Track(track_id) => {
+                get_track_info(&self.client, &self.spotify_api_url, track_id, spotify_token)
+                    .await
+                    .map(|track| vec![track])
+                    .map_err(|e| SearchError::Spotify(format!("獲取曲目資訊錯誤: {}", e)))
+            }
+            SpotifyResource::Album(album_id) => {
+                get_album_tracks(&self.client, &self.spotify_api_url, album_id, spotify_token)
+                    .await
+                    .map_err(|e| SearchError::Spotify(format!("獲取專輯曲目錯誤: {}", e)))
+            }
+            SpotifyResource::Artist(artist_id) => get_artist_top_tracks(
+                &self.client,
+                &self.spotify_api_url,
+                artist_id,
+                spotify_token,
+            )
+            .await
+            .map_err(|e| SearchError::Spotify(format!("獲取藝人熱門曲目錯誤: {}", e))),
+            SpotifyResource::Playlist(playlist_id) => get_public_playlist_tracks(
+                &self.client,
+                &self.spotify_api_url,
+                playlist_id,
+                spotify_token,
+                COLLECTION_TRACK_LIMIT,
+            )
+            .await
+            .map_err(|e| SearchError::Spotify(format!("獲取播放清單曲目錯誤: {}", e))),
+        };
+
+        let tracks = result?;
+        info!("展開後共 {} 首曲目", tracks.len());
+        Ok(tracks)
+    }
+
+    async fn search_keyword(
+        &self,
+        query: &str,
+    ) -> Result<(TrackPage, BeatmapsetPage), SearchError> {
+        let spotify_token = self.spotify_token().await?;
+        let spotify_page = self
+            .search_spotify(&spotify_token, query, self.spotify_limit, 0)
+            .await?;
+
+        info!("Osu 查詢 (關鍵字): {}", query);
+        let osu_token = self.osu_token().await?;
+        let osu_page = self.search_osu(&osu_token, query, None).await?;
+
+        Ok((spotify_page, osu_page))
+    }
+
+    async fn search_spotify(
+        &self,
+        spotify_token: &str,
+        spotify_query: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<TrackPage, SearchError> {
+        // Spotify 搜尋的 offset + limit 不能超過 1000
+        let limit = limit.min(SPOTIFY_MAX_OFFSET.saturating_sub(offset));
+        if limit == 0 {
+            return Ok(TrackPage::default());
+        }
+
+        let (tracks_with_cover, total) = search_track(
+            &self.client,
+            &self.spotify_api_url,
+            spotify_query,
+            spotify_token,
+            limit,
+            offset,
+            self.debug_mode,
+        )
+        .await
+        .map_err(|e| {
+            error!("Spotify 搜索錯誤: {:?}", e);
+            SearchError::Spotify("搜索失敗".to_string())
+        })?;
+        info!(
+            "Spotify 搜索結果: {} 首曲目（共 {} 首）",
+            tracks_with_cover.len(),
+            total
+        );
+
+        Ok(TrackPage {
+            tracks: tracks_with_cover.into_iter().map(Track::from).collect(),
+            total,
+        })
+    }
+
+    async fn search_osu(
+        &self,
+        osu_token: &str,
+        osu_query: &str,
+        cursor: Option<&str>,
+    ) -> Result<BeatmapsetPage, SearchError> {
+        let page = get_beatmapsets_page(
+            &self.client,
+            &self.osu_url,
+            osu_token,
+            osu_query,
+            &self.osu_filter,
+            cursor,
+            self.debug_mode,
+        )
+        .await
+        .map_err(|e| {
+            error!("Osu 搜索錯誤: {:?}", e);
+            SearchError::Osu("搜索失敗".to_string())
+        })?;
+
+        info!(
+            "Osu 搜索結果: {} 個 beatmapsets（共 {} 個）",
+            page.beatmapsets.len(),
+            page.total
+        );
+        if self.debug_mode {
+            debug!("Osu 搜索結果詳情: {:?}", page.beatmapsets);
+        }
+        Ok(page)
+    }
+
+    pub(crate) async fn spotify_token(&self) -> Result<String, SearchError> {
+        let token = match &self.spotify_credentials {
+            Some(credentials) => {
+                get_access_token_with_credentials(
+                    &self.client,
+                    &self.spotify_accounts_url,
+                    credentials,
+                    self.debug_mode,
+                )
+                .await
+            }
+            None => {
+                get_access_token(&self.client, &self.spotify_accounts_url, self.debug_mode).await
+            }
+        };
+        token.map_err(|e| match e {
+            SpotifyError::AccessTokenError(msg) => {
+                SearchError::Spotify(format!("無法獲取 token: {}", msg))
+            }
+            SpotifyError::RequestError(e) => SearchError::Spotify(format!("請求錯誤：{}", e)),
+            _ => SearchError::Spotify(e.to_string()),
+        })
+    }
+
+    pub(crate) async fn osu_token(&self) -> Result<String, SearchError> {
+        let token = match &self.osu_credentials {
+            Some(credentials) => {
+                get_osu_token_with_credentials(
+                    &self.client,
+                    &self.osu_url,
+                    credentials,
+                    self.debug_mode,
+                )
+                .await
+            }
+            None => get_osu_token(&self.client, &self.osu_url, self.debug_mode).await,
+        };
+        token.map_err(|e| {
+            error!("獲取 Osu token 錯誤: {:?}", e);
+            SearchError::Osu("無法獲取 token".to_string())
+        })
+    }
+}
+
+// 由譜面的歌曲資訊組成 Spotify 搜尋字串
+fn spotify_query_for_song(song: &SongFields) -> String {
+    // 動畫歌曲在 Spotify 上多半以原文登錄，有原文欄位時優先使用
+    let artist = if song.artist_unicode.is_empty() {
+        song.artist
+    } else {
+        song.artist_unicode
+    };
+    let title = if song.title_unicode.is_empty() {
+        song.title
+    } else {
+        song.title_unicode
+    };
+    format!("{} {}", strip_artist_tags(artist), strip_title_tags(title))
+}
+
+// 由 Spotify 曲目組成 osu! 搜尋字串，去掉 feat.、TV Size 等標記
+fn osu_query_for_track(track: &Track) -> String {
+    format!(
+        "{} {}",
+        strip_artist_tags(
+            &track
+                .artists
+                .iter()
+                .map(|a| a.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        strip_title_tags(&track.name)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Mock, Server, ServerGuard};
+    use serde_json::{json, Value};
+
+    const SPOTIFY_TOKEN: &str = "spotify-test-token";
+    const OSU_TOKEN: &str = "osu-test-token";
+    const TRACK_ID: &str = "4uLU6hMCjMI75M1A2tKUQC";
+
+    // 三個位址都指向同一台模擬伺服器，路徑不重疊；憑證直接傳入，不讀取 config.json
+    fn engine_for(server: &ServerGuard) -> CrossSearchEngine {
+        CrossSearchEngine::new(Client::new(), false).with_endpoints(
+            &server.url(),
+            &server.url(),
+            &server.url(),
+            credentials("spotify"),
+            credentials("osu"),
+        )
+    }
+
+    fn credentials(platform: &str) -> ServiceConfig {
+        ServiceConfig {
+            client_id: format!("{}-id", platform),
+            client_secret: format!("{}-secret", platform),
+        }
+    }
+
+    fn track_json(name: &str, artist: &str, duration_ms: u32) -> Value {
+        json!({
+            "name": name,
+            "artists": [{ "name": artist }],
+            "external_urls": { "spotify": format!("https://open.spotify.com/track/{}", TRACK_ID) },
+            "album": {
+                "album_type": "single",
+                "total_tracks": 1,
+                "external_urls": {},
+                "id": "album",
+                "images": [],
+                "name": name,
+                "release_date": "2010-01-01",
+                "artists": [{ "name": artist }]
+            },
+            "duration_ms": duration_ms
+        })
+    }
+
+    fn beatmapset_json(id: i32, artist: &str, title: &str, total_length: i32) -> Value {
+        json!({
+            "beatmaps": [{
+                "difficulty_rating": 4.5,
+                "id": id * 10,
+                "mode": "osu",
+                "status": "ranked",
+                "total_length": total_length,
+                "user_id": 1,
+                "version": "Insane"
+            }],
+            "id": id,
+            "artist": artist,
+            "title": title,
+            "creator": "mapper",
+            "covers": {},
+            "preview_url": null
+        })
+    }
+
+    async fn mock_spotify_token(server: &mut ServerGuard) -> Mock {
+        server
+            .mock("POST", "/api/token")
+            .match_header(
+                "authorization",
+                format!("Basic {}", base64::encode("spotify-id:spotify-secret")).as_str(),
+            )
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "access_token": SPOTIFY_TOKEN }).to_string())
+            .create_async()
+            .await
+    }
+
+    async fn mock_osu_token(server: &mut ServerGuard) -> Mock {
+        server
+            .mock("POST", "/oauth/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("client_id".to_string(), "osu-id".to_string()),
+                Matcher::UrlEncoded("client_secret".to_string(), "osu-secret".to_string()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "access_token": OSU_TOKEN }).to_string())
+            .create_async()
+            .await
+    }
+
+    async fn mock_osu_search(server: &mut ServerGuard, query: &str, beatmapsets: Value) -> Mock {
+        server
+            .mock("GET", "/api/v2/beatmapsets/search")
+            .match_header("authorization", format!("Bearer {}", OSU_TOKEN).as_str())
+            .match_query(Matcher::UrlEncoded("query".into(), query.into()))
+            .with_body(
+                json!({ "beatmapsets": beatmapsets, "cursor_string": null, "total": 2 })
+                    .to_string(),
+            )
+            .create_async()
+            .await
+    }
+
+    async fn mock_spotify_search(server: &mut ServerGuard, query: &str, tracks: Value) -> Mock {
+        server
+            .mock("GET", "/v1/search")
+            .match_header(
+                "authorization",
+                format!("Bearer {}", SPOTIFY_TOKEN).as_str(),
+            )
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("q".into(), query.into()),
+                Matcher::UrlEncoded("type".into(), "track".into()),
+            ]))
+            .with_body(json!({ "tracks": { "items": tracks, "total": 1 } }).to_string())
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn spotify_track_url_ranks_osu_results() {
+        let mut server = Server::new_async().await;
+        let spotify_token = mock_spotify_token(&mut server).await;
+        let track = server
+            .mock("GET", format!("/v1/tracks/{}", TRACK_ID).as_str())
+            .match_header(
+                "authorization",
+                format!("Bearer {}", SPOTIFY_TOKEN).as_str(),
+            )
+            .with_body(track_json("Renai Circulation", "Kana Hanazawa", 255_000).to_string())
+            .create_async()
+            .await;
+        let osu_token = mock_osu_token(&mut server).await;
+        // API 把不相關的譜面排在前面，引擎應依匹配分數重新排序
+        let osu_search = mock_osu_search(
+            &mut server,
+            "Kana Hanazawa Renai Circulation",
+            json!([
+                beatmapset_json(1, "Someone Else", "Another Song", 120),
+                beatmapset_json(2, "Kana Hanazawa", "Renai Circulation", 255),
+            ]),
+        )
+        .await;
+
+        let result = engine_for(&server)
+            .search(&format!("https://open.spotify.com/track/{}", TRACK_ID))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.kind,
+            QueryKind::SpotifyTrack {
+                track_id: TRACK_ID.to_string()
+            }
+        );
+        assert_eq!(result.spotify_tracks.len(), 1);
+        assert_eq!(
+            result.osu_query.as_deref(),
+            Some("Kana Hanazawa Renai Circulation")
+        );
+        assert_eq!(result.osu_beatmapsets[0].id, 2);
+        assert_eq!(result.best_match.map(|best| best.beatmapset_id), Some(2));
+
+        spotify_token.assert_async().await;
+        track.assert_async().await;
+        osu_token.assert_async().await;
+        osu_search.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn osu_beatmapset_url_reverse_searches_spotify() {
+        let mut server = Server::new_async().await;
+        let osu_token = mock_osu_token(&mut server).await;
+        let mut beatmapset = beatmapset_json(123, "Kana Hanazawa", "Renai Circulation", 255);
+        beatmapset["artist_unicode"] = json!("花澤香菜");
+        beatmapset["title_unicode"] = json!("恋愛サーキュレーション");
+        let beatmapset_mock = server
+            .mock("GET", "/api/v2/beatmapsets/123")
+            .match_header("authorization", format!("Bearer {}", OSU_TOKEN).as_str())
+            .with_body(beatmapset.to_string())
+            .create_async()
+            .await;
+        let spotify_token = mock_spotify_token(&mut server).await;
+        // 有原文欄位時以原文反查 Spotify
+        let spotify_search = mock_spotify_search(
+            &mut server,
+            "花澤香菜 恋愛サーキュレーション",
+            json!([track_json("恋愛サーキュレーション", "花澤香菜", 255_000)]),
+        )
+        .await;
+
+        let result = engine_for(&server)
+            .search("https://osu.ppy.sh/beatmapsets/123")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.kind,
+            QueryKind::OsuBeatmapset {
+                beatmapset_id: "123".to_string()
+            }
+        );
+        assert_eq!(
+            result.spotify_query.as_deref(),
+            Some("花澤香菜 恋愛サーキュレーション")
+        );
+        assert_eq!(result.spotify_tracks.len(), 1);
+        assert_eq!(result.spotify_tracks[0].name, "恋愛サーキュレーション");
+        assert_eq!(result.osu_beatmapsets.len(), 1);
+        assert_eq!(result.osu_beatmapsets[0].id, 123);
+
+        osu_token.assert_async().await;
+        beatmapset_mock.assert_async().await;
+        spotify_token.assert_async().await;
+        spotify_search.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn keyword_searches_both_platforms() {
+        let mut server = Server::new_async().await;
+        let spotify_token = mock_spotify_token(&mut server).await;
+        let spotify_search = mock_spotify_search(
+            &mut server,
+            "renai circulation",
+            json!([track_json("Renai Circulation", "Kana Hanazawa", 255_000)]),
+        )
+        .await;
+        let osu_token = mock_osu_token(&mut server).await;
+        let osu_search = mock_osu_search(
+            &mut server,
+            "renai circulation",
+            json!([
+                beatmapset_json(1, "Someone Else", "Another Song", 120),
+                beatmapset_json(2, "Kana Hanazawa", "Renai Circulation", 255),
+            ]),
+        )
+        .await;
+
+        let result = engine_for(&server)
+            .search("  renai circulation ")
+            .await
+            .unwrap();
+
+        assert_eq!(result.kind, QueryKind::Keyword);
+        assert_eq!(result.spotify_query.as_deref(), Some("renai circulation"));
+        assert_eq!(result.osu_query.as_deref(), Some("renai circulation"));
+        assert_eq!(result.spotify_tracks.len(), 1);
+        assert_eq!(result.spotify_total, 1);
+        // 關鍵字搜尋不重新排序，保留 API 的順序
+        let ids: Vec<i32> = result.osu_beatmapsets.iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(result.osu_total, 2);
+        assert!(result.best_match.is_none());
+
+        spotify_token.assert_async().await;
+        spotify_search.assert_async().await;
+        osu_token.assert_async().await;
+        osu_search.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn failed_spotify_lookup_skips_osu_token() {
+        let mut server = Server::new_async().await;
+        let spotify_token = mock_spotify_token(&mut server).await;
+        let track = server
+            .mock("GET", format!("/v1/tracks/{}", TRACK_ID).as_str())
+            .with_status(404)
+            .with_body(json!({ "error": { "status": 404 } }).to_string())
+            .create_async()
+            .await;
+        let osu_token = server
+            .mock("POST", "/oauth/token")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let result = engine_for(&server)
+            .search(&format!("https://open.spotify.com/track/{}", TRACK_ID))
+            .await;
+
+        assert!(matches!(result, Err(SearchError::Spotify(_))));
+        spotify_token.assert_async().await;
+        track.assert_async().await;
+        osu_token.assert_async().await;
+    }
+
+    #[test]
+    fn classify_rejects_empty_and_incomplete_queries() {
+        assert!(matches!(
+            CrossSearchEngine::classify_query("   "),
+            Err(SearchError::EmptyQuery)
+        ));
+        assert!(matches!(
+            CrossSearchEngine::classify_query("https://open.spotify.com/track/"),
+            Err(SearchError::IncompleteSpotifyUrl)
+        ));
+    }
+}
@@ -1,12 +1,15 @@
 // 標準庫導入
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(windows)]
 use std::ffi::OsString;
 use std::fs::{self, OpenOptions};
 use std::future::Future;
 use std::io::{self, Write};
 use std::net::SocketAddr;
+#[cfg(windows)]
 use std::os::windows::ffi::OsStrExt;
 use std::pin::Pin;
+#[cfg(windows)]
 use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -22,7 +25,7 @@ use log::{debug, error, info};
 use regex::Regex;
 use reqwest::Client;
 use rspotify::{
-    clients::{OAuthClient,BaseClient}, model::{PlayableItem,TrackId,FullTrack,PlaylistId}, scopes, AuthCodeSpotify, ClientError, Credentials,
+    clients::{OAuthClient,BaseClient}, model::{PlayableId,PlayableItem,TrackId,FullTrack,PlaylistId}, prelude::Id, scopes, AuthCodeSpotify, ClientError, Credentials,
     OAuth, Token,model::SimplifiedPlaylist,
 };
 use serde::{Deserialize, Serialize};
@@ -33,6 +36,7 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Mutex as TokioMutex;
 use tokio::time::timeout;
 use url::Url;
+#[cfg(windows)]
 use winapi::{
     shared::{minwindef::HKEY, ntdef::LPCWSTR},
     um::{
@@ -45,12 +49,12 @@ use winapi::{
 
 
 // 本地模組導入
-use crate::{read_config, AuthManager, AuthPlatform};
-use lib::{LoginInfo, save_login_info, open_url_default_browser};
+use crate::{read_config, AuthManager, AuthPlatform, ServiceConfig};
+use crate::{LoginInfo, save_platform_login_info, open_url_default_browser};
 
 // 常量定義
-const SPOTIFY_API_BASE_URL: &str = "https://api.spotify.com/v1";
-const SPOTIFY_AUTH_URL: &str = "https://accounts.spotify.com/api/token";
+pub const SPOTIFY_API_BASE_URL: &str = "https://api.spotify.com";
+pub const SPOTIFY_ACCOUNTS_BASE_URL: &str = "https://accounts.spotify.com";
 
 // 靜態變量
 lazy_static! {
@@ -157,26 +161,114 @@ pub struct Tracks {
     pub total: u32,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Track {
     pub name: String,
     pub artists: Vec<Artist>,
     pub external_urls: HashMap<String, String>,
     pub album: Album,
+    #[serde(default)]
+    pub duration_ms: u32,
     pub is_liked: Option<bool>,
     #[serde(skip)]
     pub index: usize,
     
 }
+#[derive(Serialize)]
 pub struct TrackWithCover {
     pub name: String,
     pub artists: Vec<Artist>,
     pub external_urls: HashMap<String, String>,
     pub album_name: String,
     pub cover_url: Option<String>,
+    pub duration_ms: u32,
     pub index: usize,
 }
 
+// 搜尋結果只帶有封面網址，轉換成完整的 Track 供介面顯示
+impl From<TrackWithCover> for Track {
+    fn from(twc: TrackWithCover) -> Self {
+        Track {
+            name: twc.name,
+            artists: twc.artists,
+            album: Album {
+                name: twc.album_name,
+                album_type: String::new(),
+                artists: Vec::new(),
+                external_urls: HashMap::new(),
+                images: twc
+                    .cover_url
+                    .map(|url| {
+                        vec![Image {
+                            url,
+                            width: 0,
+                            height: 0,
+                        }]
+                    })
+                    .unwrap_or_default(),
+                id: String::new(),
+                release_date: String::new(),
+                total_tracks: 0,
+            },
+            external_urls: twc.external_urls,
+            duration_ms: twc.duration_ms,
+            index: twc.index,
+            is_liked: None,
+        }
+    }
+}
+
+// 側邊欄的播放清單與 Liked Songs 是 rspotify 的 FullTrack，轉換後才能送進比對流程
+impl From<&FullTrack> for Track {
+    fn from(full: &FullTrack) -> Self {
+        Track {
+            name: full.name.clone(),
+            artists: full
+                .artists
+                .iter()
+                .map(|a| Artist {
+                    name: a.name.clone(),
+                })
+                .collect(),
+            album: Album {
+                name: full.album.name.clone(),
+                album_type: full.album.album_type.clone().unwrap_or_default(),
+                artists: full
+                    .album
+                    .artists
+                    .iter()
+                    .map(|a| Artist {
+                        name: a.name.clone(),
+                    })
+                    .collect(),
+                external_urls: full.album.external_urls.clone(),
+                images: full
+                    .album
+                    .images
+                    .iter()
+                    .map(|image| Image {
+                        url: image.url.clone(),
+                        width: image.width.unwrap_or(0),
+                        height: image.height.unwrap_or(0),
+                    })
+                    .collect(),
+                id: full
+                    .album
+                    .id
+                    .as_ref()
+                    .map(|id| id.id().to_string())
+                    .unwrap_or_default(),
+                release_date: full.album.release_date.clone().unwrap_or_default(),
+                total_tracks: 0,
+            },
+            external_urls: full.external_urls.clone(),
+            duration_ms: full.duration.num_milliseconds().max(0) as u32,
+            index: 0,
+            is_liked: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TrackInfo {
     pub name: String,
@@ -206,7 +298,7 @@ pub struct CurrentlyPlaying {
 pub fn is_valid_spotify_url(url: &str) -> Result<SpotifyUrlStatus, SpotifyError> {
     lazy_static! {
         static ref SPOTIFY_URL_REGEX: Regex = Regex::new(
-            r"^https?://open\.spotify\.com/(track|album|playlist)/[a-zA-Z0-9]+(?:\?.*)?$"
+            r"^https?://open\.spotify\.com/(?:intl-[a-z]+/)?(track|album|artist|playlist)/[a-zA-Z0-9]+(?:\?.*)?$"
         )
         .unwrap();
     }
@@ -221,7 +313,10 @@ pub fn is_valid_spotify_url(url: &str) -> Result<SpotifyUrlStatus, SpotifyError>
                 }
             }
             Some(_) => {
-                if url.contains("/track/") || url.contains("/album/") || url.contains("/playlist/")
+                if url.contains("/track/")
+                    || url.contains("/album/")
+                    || url.contains("/artist/")
+                    || url.contains("/playlist/")
                 {
                     Ok(SpotifyUrlStatus::Invalid)
                 } else {
@@ -234,104 +329,277 @@ pub fn is_valid_spotify_url(url: &str) -> Result<SpotifyUrlStatus, SpotifyError>
         Ok(SpotifyUrlStatus::NotSpotify)
     }
 }
-/*
-pub async fn search_album_by_url(
-    client: &reqwest::Client,
-    url: &str,
-    access_token: &str,
-) -> Result<Album, Box<dyn std::error::Error>> {
-    let re = Regex::new(r"https?://open\.spotify\.com/album/([a-zA-Z0-9]+)").unwrap();
-
-    let album_id_result = match re.captures(url) {
-        Some(caps) => match caps.get(1) {
-            Some(m) => Ok(m.as_str().to_string()),
-            None => {
-                let mut err_msg = ERR_MSG.lock().unwrap();
-                *err_msg = "URL疑似錯誤，請重新輸入".to_string();
-                Err("URL疑似錯誤，請重新輸入".into())
-            }
-        },
-        None => {
-            let mut err_msg = ERR_MSG.lock().unwrap();
-            *err_msg = "URL疑似錯誤，請重新輸入".to_string();
-            Err("URL疑似錯誤，請重新輸入".into())
-        }
-    };
+// 以 client credentials 存取的 Spotify 查詢預設市場（熱門曲目 API 需要）
+const SPOTIFY_DEFAULT_MARKET: &str = "US";
+
+// 網址或搜尋結果所指向的 Spotify 資源
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpotifyResource {
+    Track(String),
+    Album(String),
+    Artist(String),
+    Playlist(String),
+}
 
-    match album_id_result {
-        Ok(album_id) => {
-            let api_url = format!("https://api.spotify.com/v1/albums/{}", album_id);
-            let response = client
-                .get(&api_url)
-                .header(AUTHORIZATION, format!("Bearer {}", access_token))
-                .header(CONTENT_TYPE, "application/json")
-                .send()
-                .await?
-                .json::<Album>()
-                .await?;
+// 關鍵字搜尋的類型，非曲目類型會展開成其中的曲目
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SpotifySearchType {
+    #[default]
+    Track,
+    Album,
+    Artist,
+    Playlist,
+}
+
+impl SpotifySearchType {
+    pub const ALL: [SpotifySearchType; 4] = [
+        SpotifySearchType::Track,
+        SpotifySearchType::Album,
+        SpotifySearchType::Artist,
+        SpotifySearchType::Playlist,
+    ];
 
-            Ok(response)
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            SpotifySearchType::Track => "track",
+            SpotifySearchType::Album => "album",
+            SpotifySearchType::Artist => "artist",
+            SpotifySearchType::Playlist => "playlist",
         }
-        Err(e) => {
-            println!("ERROR: {}", e);
+    }
 
-            Err(e)
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpotifySearchType::Track => "曲目",
+            SpotifySearchType::Album => "專輯",
+            SpotifySearchType::Artist => "藝人",
+            SpotifySearchType::Playlist => "播放清單",
         }
     }
 }
 
-pub async fn search_album_by_name(
-    client: &reqwest::Client,
-    album_name: &str,
+#[derive(Deserialize)]
+struct AlbumTracksResponse {
+    items: Vec<AlbumTrack>,
+    next: Option<String>,
+}
+
+// 專輯內的曲目不含 album 欄位，需由外層專輯補上
+#[derive(Deserialize)]
+struct AlbumTrack {
+    name: String,
+    artists: Vec<Artist>,
+    external_urls: HashMap<String, String>,
+    #[serde(default)]
+    duration_ms: u32,
+}
+
+#[derive(Deserialize)]
+struct ArtistTopTracksResponse {
+    tracks: Vec<Track>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistTracksResponse {
+    items: Vec<PlaylistTrackItem>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistTrackItem {
+    // 已下架的曲目或 podcast 集數會是 null
+    track: Option<Track>,
+}
+
+#[derive(Deserialize)]
+struct SearchIdItem {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct SearchIdItems {
+    // 播放清單搜尋結果偶爾會夾雜 null
+    items: Vec<Option<SearchIdItem>>,
+}
+
+pub fn parse_spotify_url(url: &str) -> Option<SpotifyResource> {
+    lazy_static! {
+        static ref RESOURCE_REGEX: Regex = Regex::new(
+            r"open\.spotify\.com/(?:intl-[a-z]+/)?(track|album|artist|playlist)/([a-zA-Z0-9]+)"
+        )
+        .expect("Failed to compile Spotify resource regex");
+    }
+
+    let caps = RESOURCE_REGEX.captures(url)?;
+    let id = caps.get(2)?.as_str().to_string();
+    match caps.get(1)?.as_str() {
+        "track" => Some(SpotifyResource::Track(id)),
+        "album" => Some(SpotifyResource::Album(id)),
+        "artist" => Some(SpotifyResource::Artist(id)),
+        "playlist" => Some(SpotifyResource::Playlist(id)),
+        _ => None,
+    }
+}
+
+async fn spotify_get<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    url: &str,
     access_token: &str,
-    page: u32,
-    limit: u32,
-) -> Result<(Vec<Album>, u32), Box<dyn std::error::Error>> {
-    let offset = (page - 1) * limit;
-    let search_url = format!(
-        "https://api.spotify.com/v1/search?q={}&type=album&limit={}&offset={}",
-        album_name, limit, offset
-    );
+) -> Result<T, SpotifyError> {
     let response = client
-        .get(&search_url)
-        .header("Authorization", format!("Bearer {}", access_token))
+        .get(url)
+        .bearer_auth(access_token)
         .send()
-        .await?;
+        .await
+        .map_err(SpotifyError::RequestError)?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(SpotifyError::ApiError(format!(
+            "請求失敗 ({}): {}",
+            status, error_text
+        )));
+    }
 
-    let search_result: SearchResult = response.json().await?;
-    let total_pages =
-        (search_result.albums.clone().unwrap().items.len() as u32 + limit - 1) / limit;
-    let albums = search_result.albums.unwrap().items;
-    Ok((albums, total_pages))
+    let body = response.text().await.map_err(SpotifyError::RequestError)?;
+    serde_json::from_str(&body).map_err(SpotifyError::JsonError)
 }
 
+// 取得專輯的所有曲目
+pub async fn get_album_tracks(
+    client: &Client,
+    api_base_url: &str,
+    album_id: &str,
+    access_token: &str,
+) -> Result<Vec<Track>, SpotifyError> {
+    let album: Album = spotify_get(
+        client,
+        &format!("{}/v1/albums/{}", api_base_url, album_id),
+        access_token,
+    )
+    .await?;
+
+    let mut tracks = Vec::new();
+    let mut next_url = Some(format!(
+        "{}/v1/albums/{}/tracks?limit=50",
+        api_base_url, album_id
+    ));
+    while let Some(url) = next_url {
+        let page: AlbumTracksResponse = spotify_get(client, &url, access_token).await?;
+        for item in page.items {
+            tracks.push(Track {
+                name: item.name,
+                artists: item.artists,
+                external_urls: item.external_urls,
+                album: album.clone(),
+                duration_ms: item.duration_ms,
+                is_liked: None,
+                index: tracks.len(),
+            });
+        }
+        next_url = page.next;
+    }
 
-pub fn print_track_infos(track_infos: Vec<Track>) {
-    println!(" ");
-    println!("------------------------");
-    for track_info in track_infos {
-        let artist_names: Vec<String> = track_info
-            .artists
-            .into_iter()
-            .map(|artist| artist.name)
-            .collect();
-        println!("Track: {}", track_info.name);
-        println!("Artists: {}", artist_names.join(", "));
-        println!("Album: {}", track_info.album.name);
-        if let Some(spotify_url) = track_info.external_urls.get("spotify") {
-            println!("Spotify URL: {}", spotify_url);
+    info!("專輯 {} 共 {} 首曲目", album.name, tracks.len());
+    Ok(tracks)
+}
+
+// 取得藝人的熱門曲目
+pub async fn get_artist_top_tracks(
+    client: &Client,
+    api_base_url: &str,
+    artist_id: &str,
+    access_token: &str,
+) -> Result<Vec<Track>, SpotifyError> {
+    let response: ArtistTopTracksResponse = spotify_get(
+        client,
+        &format!(
+            "{}/v1/artists/{}/top-tracks?market={}",
+            api_base_url, artist_id, SPOTIFY_DEFAULT_MARKET
+        ),
+        access_token,
+    )
+    .await?;
+
+    Ok(response
+        .tracks
+        .into_iter()
+        .enumerate()
+        .map(|(index, track)| Track { index, ..track })
+        .collect())
+}
+
+// 取得公開播放清單的曲目（不需要使用者登入）
+pub async fn get_public_playlist_tracks(
+    client: &Client,
+    api_base_url: &str,
+    playlist_id: &str,
+    access_token: &str,
+    limit: usize,
+) -> Result<Vec<Track>, SpotifyError> {
+    let mut tracks = Vec::new();
+    let mut next_url = Some(format!(
+        "{}/v1/playlists/{}/tracks?limit=100",
+        api_base_url, playlist_id
+    ));
+    while let Some(url) = next_url {
+        let page: PlaylistTracksResponse = spotify_get(client, &url, access_token).await?;
+        for track in page.items.into_iter().filter_map(|item| item.track) {
+            let index = tracks.len();
+            tracks.push(Track { index, ..track });
+        }
+        if tracks.len() >= limit {
+            tracks.truncate(limit);
+            break;
         }
-        println!("------------------------");
+        next_url = page.next;
     }
+
+    Ok(tracks)
+}
+
+// 以關鍵字搜尋專輯、藝人或播放清單，回傳最相關一筆的 ID 與名稱
+pub async fn search_spotify_resource(
+    client: &Client,
+    api_base_url: &str,
+    query: &str,
+    search_type: SpotifySearchType,
+    access_token: &str,
+) -> Result<Option<(SpotifyResource, String)>, SpotifyError> {
+    let url = format!(
+        "{}/v1/search?q={}&type={}&limit=5",
+        api_base_url,
+        urlencoding::encode(query),
+        search_type.api_value()
+    );
+    let response: Value = spotify_get(client, &url, access_token).await?;
+
+    let key = format!("{}s", search_type.api_value());
+    let items: SearchIdItems = match response.get(&key) {
+        Some(items) => serde_json::from_value(items.clone()).map_err(SpotifyError::JsonError)?,
+        None => return Ok(None),
+    };
+
+    Ok(items.items.into_iter().flatten().next().map(|item| {
+        let resource = match search_type {
+            SpotifySearchType::Track => SpotifyResource::Track(item.id),
+            SpotifySearchType::Album => SpotifyResource::Album(item.id),
+            SpotifySearchType::Artist => SpotifyResource::Artist(item.id),
+            SpotifySearchType::Playlist => SpotifyResource::Playlist(item.id),
+        };
+        (resource, item.name)
+    }))
 }
- */
 
 pub async fn get_track_info(
     client: &reqwest::Client,
+    api_base_url: &str,
     track_id: &str,
     access_token: &str,
 ) -> Result<Track> {
-    let url = format!("{}/tracks/{}", SPOTIFY_API_BASE_URL, track_id);
+    let url = format!("{}/v1/tracks/{}", api_base_url, track_id);
     let response = client
         .get(&url)
         .header("Authorization", format!("Bearer {}", access_token))
@@ -345,8 +613,10 @@ pub async fn get_track_info(
     Ok(track)
 }
 
+// 回傳本頁曲目與符合條件的曲目總數，index 已加上 offset，可直接用來排序
 pub async fn search_track(
     client: &Client,
+    api_base_url: &str,
     query: &str,
     token: &str,
     limit: u32,
@@ -354,8 +624,8 @@ pub async fn search_track(
     debug_mode: bool,
 ) -> Result<(Vec<TrackWithCover>, u32), SpotifyError> {
     let url = format!(
-        "{}/search?q={}&type=track&limit={}&offset={}",
-        SPOTIFY_API_BASE_URL, query, limit, offset
+        "{}/v1/search?q={}&type=track&limit={}&offset={}",
+        api_base_url, query, limit, offset
     );
 
     let response = client
@@ -426,6 +696,7 @@ pub async fn search_track(
                         external_urls: track.external_urls,
                         album_name: track.album.name,
                         cover_url,
+                        duration_ms: track.duration_ms,
                         index: index + (offset as usize),
                     }
                 })
@@ -435,7 +706,7 @@ pub async fn search_track(
                 info!("成功處理 {} 首曲目", track_infos.len());
             }
 
-            Ok((track_infos, total_pages))
+            Ok((track_infos, total_tracks))
         }
         None => Err(SpotifyError::ApiError("搜索結果中沒有找到曲目".to_string())),
     }
@@ -444,17 +715,28 @@ pub async fn search_track(
 
 pub async fn get_access_token(
     client: &reqwest::Client,
+    accounts_base_url: &str,
     debug_mode: bool,
 ) -> Result<String, SpotifyError> {
     let config = read_config(debug_mode).map_err(|e| SpotifyError::IoError(e.to_string()))?;
-    let client_id = &config.spotify.client_id;
-    let client_secret = &config.spotify.client_secret;
+    get_access_token_with_credentials(client, accounts_base_url, &config.spotify, debug_mode).await
+}
+
+// 以指定的 client_id 與 client_secret 取得 token，不讀取設定檔
+pub async fn get_access_token_with_credentials(
+    client: &reqwest::Client,
+    accounts_base_url: &str,
+    credentials: &ServiceConfig,
+    debug_mode: bool,
+) -> Result<String, SpotifyError> {
+    let client_id = &credentials.client_id;
+    let client_secret = &credentials.client_secret;
 
     if debug_mode {
         debug!("正在獲取 Spotify access token");
     }
 
-    let auth_url = SPOTIFY_AUTH_URL;
+    let auth_url = format!("{}/api/token", accounts_base_url);
     let body = "grant_type=client_credentials";
     let auth_header = base64::encode(format!("{}:{}", client_id, client_secret));
     let request = client
@@ -505,9 +787,11 @@ pub fn open_spotify_url(url: &str) -> io::Result<()> {
         ));
     }
 
+    #[cfg(windows)]
     let spotify_uri = format!("spotify:track:{}", track_id);
     let web_url = format!("https://open.spotify.com/track/{}", track_id);
 
+    #[cfg(windows)]
     if is_spotify_protocol_associated()? {
         let result = unsafe {
             ShellExecuteA(
@@ -559,6 +843,7 @@ pub fn open_spotify_url(url: &str) -> io::Result<()> {
     }
 }
 
+#[cfg(windows)]
 fn is_spotify_protocol_associated() -> io::Result<bool> {
     let sub_key_os_string = OsString::from("spotify");
     let sub_key_vec: Vec<u16> = sub_key_os_string
@@ -700,7 +985,7 @@ pub fn authorize_spotify(
         let client_id = config["spotify"]["client_id"]
             .as_str()
             .ok_or_else(|| SpotifyError::ConfigError("Missing Spotify client ID".to_string()))?;
-        let scope = "user-read-currently-playing user-read-private user-read-email user-library-read user-library-modify";
+        let scope = "user-read-currently-playing user-read-private user-read-email user-library-read user-library-modify playlist-read-private playlist-modify-private";
 
         // 檢查是否已有監聽器，如果沒有則創建新的
         let bound_port = {
@@ -745,10 +1030,8 @@ pub fn authorize_spotify(
                 )
                 .await?;
 
-                // 保存登入信息
-                let mut login_info_map = HashMap::new();
-                login_info_map.insert("spotify".to_string(), login_info);
-                match save_login_info(&login_info_map) {
+                // 保存登入信息，保留 osu! 的登入
+                match save_platform_login_info("spotify", login_info) {
                     Ok(()) => info!("成功保存 Spotify 登入信息"),
                     Err(e) => error!("無法保存 Spotify 登入信息: {:?}", e),
                 }
@@ -775,8 +1058,8 @@ pub fn authorize_spotify(
     })
 }
 
-// 輔助函數來創建監聽器
-async fn create_listener(debug_mode: bool) -> Result<(TcpListener, u16), SpotifyError> {
+// 輔助函數來創建監聽器，osu! 授權也共用
+pub async fn create_listener(debug_mode: bool) -> Result<(TcpListener, u16), SpotifyError> {
     let ports = vec![8888, 8889, 8890, 8891, 8892];
     for port in ports {
         let addr = SocketAddr::from(([127, 0, 0, 1], port));
@@ -817,6 +1100,28 @@ async fn process_successful_connection(
     debug_mode: bool,
     spotify_authorized: Arc<AtomicBool>,
 ) -> Result<(LoginInfo, Option<String>, Option<String>), SpotifyError> {
+    let url = read_callback_request(stream, port).await?;
+
+    if debug_mode {
+        info!("Received callback URL: {}", url);
+    }
+
+    auth_manager.update_status(&AuthPlatform::Spotify, AuthStatus::Processing);
+
+    // 處理授權回調
+    process_authorization_callback(
+        url,
+        spotify_client,
+        auth_manager,
+        config,
+        redirect_uri,
+        spotify_authorized,
+    )
+    .await
+}
+
+// 讀取瀏覽器導回的請求並回應，回傳完整的回調 URL
+pub async fn read_callback_request(stream: TcpStream, port: u16) -> Result<String, SpotifyError> {
     let mut reader = BufReader::new(stream);
     let mut request_line = String::new();
     reader
@@ -838,22 +1143,7 @@ async fn process_successful_connection(
         .await
         .map_err(|e| SpotifyError::IoError(format!("無法發送響應: {}", e)))?;
 
-    if debug_mode {
-        info!("Received callback URL: {}", url);
-    }
-
-    auth_manager.update_status(&AuthPlatform::Spotify, AuthStatus::Processing);
-
-    // 處理授權回調
-    process_authorization_callback(
-        url,
-        spotify_client,
-        auth_manager,
-        config,
-        redirect_uri,
-        spotify_authorized,
-    )
-    .await
+    Ok(url)
 }
 
 async fn process_authorization_callback(
@@ -919,7 +1209,9 @@ async fn process_authorization_callback(
                         scopes: scopes!(
                             "user-read-currently-playing",
                             "user-read-private",
-                            "user-read-email"
+                            "user-read-email",
+                            "playlist-read-private",
+                            "playlist-modify-private"
                         ),
                         ..Default::default()
                     };
@@ -1006,7 +1298,7 @@ async fn process_authorization_callback(
     }
 }
 
-async fn accept_connection(
+pub async fn accept_connection(
     listener: &Arc<TokioMutex<Option<TcpListener>>>,
     timeout_duration: Duration,
 ) -> Result<TcpStream, SpotifyError> {
@@ -1082,6 +1374,115 @@ pub async fn remove_track_from_liked(
     
     Ok(())
 }
+// 從 Spotify 曲目網址取得曲目 ID
+pub fn track_id_from_track(track: &Track) -> Option<String> {
+    match parse_spotify_url(track.external_urls.get("spotify")?)? {
+        SpotifyResource::Track(id) => Some(id),
+        _ => None,
+    }
+}
+
+// 找出使用者名下指定名稱的播放清單，沒有的話建立一個私人播放清單，
+// 再把清單中還沒有的曲目加進去，回傳實際新增的曲目數
+pub async fn add_tracks_to_named_playlist(
+    spotify: &AuthCodeSpotify,
+    name: &str,
+    track_ids: &[String],
+) -> Result<usize, SpotifyError> {
+    let user = spotify.current_user().await?;
+
+    let mut existing_playlist = None;
+    let mut offset = 0;
+    loop {
+        let page = spotify
+            .current_user_playlists_manual(Some(50), Some(offset))
+            .await?;
+        if let Some(playlist) = page
+            .items
+            .iter()
+            .find(|p| p.name == name && p.owner.id == user.id)
+        {
+            existing_playlist = Some(playlist.id.clone());
+            break;
+        }
+        if page.next.is_none() {
+            break;
+        }
+        offset += 50;
+    }
+
+    let mut existing_tracks = HashSet::new();
+    let playlist_id = match existing_playlist {
+        Some(playlist_id) => {
+            let mut offset = 0;
+            loop {
+                let page = spotify
+                    .playlist_items_manual(
+                        playlist_id.as_ref(),
+                        None,
+                        None,
+                        Some(100),
+                        Some(offset),
+                    )
+                    .await?;
+                for item in &page.items {
+                    if let Some(PlayableItem::Track(track)) = &item.track {
+                        if let Some(id) = &track.id {
+                            existing_tracks.insert(id.id().to_string());
+                        }
+                    }
+                }
+                if page.next.is_none() {
+                    break;
+                }
+                offset += 100;
+            }
+            info!(
+                "更新播放清單 \"{}\"，已有 {} 首曲目",
+                name,
+                existing_tracks.len()
+            );
+            playlist_id
+        }
+        None => {
+            info!("建立播放清單 \"{}\"", name);
+            spotify
+                .user_playlist_create(
+                    user.id.as_ref(),
+                    name,
+                    Some(false),
+                    Some(false),
+                    Some("由本機 osu! 譜面自動建立"),
+                )
+                .await?
+                .id
+        }
+    };
+
+    let mut new_tracks = Vec::new();
+    for track_id in track_ids {
+        if existing_tracks.insert(track_id.clone()) {
+            new_tracks.push(
+                TrackId::from_id(track_id.clone())
+                    .map_err(|e| SpotifyError::ApiError(format!("無效的曲目 ID: {}", e)))?,
+            );
+        }
+    }
+
+    // 每次請求最多加入 100 首
+    for chunk in new_tracks.chunks(100) {
+        spotify
+            .playlist_add_items(
+                playlist_id.as_ref(),
+                chunk.iter().map(|id| PlayableId::Track(id.as_ref())),
+                None,
+            )
+            .await?;
+    }
+
+    Ok(new_tracks.len())
+}
+
 pub async fn get_user_playlists(spotify_client: Arc<Mutex<Option<AuthCodeSpotify>>>) -> Result<Vec<SimplifiedPlaylist>> {
     // 鎖定 Mutex，取得 Spotify 客戶端的克隆，然後立即釋放 MutexGuard
     let spotify_ref = {
@@ -0,0 +1,211 @@
+// 標準庫導入
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// 第三方庫導入
+use log::{debug, error, info};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use thiserror::Error;
+
+// 本地模組導入
+use crate::osu::{beatmapset_id_from_file_name, PARTIAL_DOWNLOAD_EXTENSION};
+
+// 同一批檔案操作（例如解壓縮、寫入 .osz）在這段時間內沒有新事件才送出
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Error, Debug)]
+pub enum WatcherError {
+    #[error("無法監看資料夾: {0}")]
+    NotifyError(#[from] notify::Error),
+    #[error("IO 錯誤: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+// 下載資料夾最上層項目的變化，檔名皆相對於下載資料夾
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryEvent {
+    Added(String),
+    Removed(String),
+    Renamed { from: String, to: String },
+}
+
+impl DirectoryEvent {
+    // 事件涉及的譜面集 ID（依檔名判斷）
+    pub fn beatmapset_ids(&self) -> Vec<i32> {
+        match self {
+            DirectoryEvent::Added(name) | DirectoryEvent::Removed(name) => {
+                beatmapset_id_from_file_name(name).into_iter().collect()
+            }
+            DirectoryEvent::Renamed { from, to } => beatmapset_id_from_file_name(from)
+                .into_iter()
+                .chain(beatmapset_id_from_file_name(to))
+                .collect(),
+        }
+    }
+}
+
+// 在背景監看下載資料夾，drop 時停止監看
+pub struct DownloadDirWatcher {
+    directory: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+impl DownloadDirWatcher {
+    // 開始監看，on_events 在背景執行緒上以批次呼叫
+    pub fn start(
+        directory: &Path,
+        debug_mode: bool,
+        on_events: impl FnMut(Vec<DirectoryEvent>) + Send + 'static,
+    ) -> Result<Self, WatcherError> {
+        // 事件路徑以監看時給的路徑為準，統一成絕對路徑才能比對上層資料夾
+        let directory = directory.canonicalize()?;
+        let (sender, receiver) = mpsc::channel::<Event>();
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+                Ok(event) => {
+                    let _ = sender.send(event);
+                }
+                Err(e) => error!("下載資料夾監看錯誤: {:?}", e),
+            })?;
+        // 只關心最上層的 .osz 與譜面資料夾
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+        let worker_directory = directory.clone();
+        thread::Builder::new()
+            .name("download-dir-watcher".to_string())
+            .spawn(move || debounce_events(worker_directory, receiver, debug_mode, on_events))?;
+
+        info!("開始監看下載資料夾: {:?}", directory);
+        Ok(Self {
+            directory,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+// 收集事件直到安靜一段時間，再依檔案目前是否存在整理成新增、刪除或改名
+fn debounce_events(
+    directory: PathBuf,
+    receiver: mpsc::Receiver<Event>,
+    debug_mode: bool,
+    mut on_events: impl FnMut(Vec<DirectoryEvent>),
+) {
+    let mut touched: Vec<String> = Vec::new();
+    let mut renames: Vec<(String, String)> = Vec::new();
+
+    loop {
+        let event = if touched.is_empty() && renames.is_empty() {
+            match receiver.recv() {
+                Ok(event) => event,
+                Err(_) => break,
+            }
+        } else {
+            match receiver.recv_timeout(DEBOUNCE_INTERVAL) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    let events = resolve_events(
+                        &directory,
+                        std::mem::take(&mut touched),
+                        std::mem::take(&mut renames),
+                    );
+                    if !events.is_empty() {
+                        if debug_mode {
+                            debug!("下載資料夾變動: {:?}", events);
+                        }
+                        on_events(events);
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        };
+
+        if debug_mode {
+            debug!("收到檔案事件: {:?}", event);
+        }
+        if let EventKind::Modify(ModifyKind::Name(RenameMode::Both)) = event.kind {
+            if let [from, to] = event.paths.as_slice() {
+                if let (Some(from), Some(to)) = (
+                    top_level_name(&directory, from),
+                    top_level_name(&directory, to),
+                ) {
+                    renames.push((from, to));
+                    continue;
+                }
+            }
+        }
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        for path in &event.paths {
+            if let Some(name) = top_level_name(&directory, path) {
+                if !touched.contains(&name) {
+                    touched.push(name);
+                }
+            }
+        }
+    }
+    info!("停止監看下載資料夾: {:?}", directory);
+}
+
+fn resolve_events(
+    directory: &Path,
+    touched: Vec<String>,
+    renames: Vec<(String, String)>,
+) -> Vec<DirectoryEvent> {
+    let mut events = Vec::new();
+    let mut handled = HashSet::new();
+
+    for (from, to) in renames {
+        if !directory.join(&from).exists() && directory.join(&to).exists() {
+            handled.insert(from.clone());
+            handled.insert(to.clone());
+            events.push(DirectoryEvent::Renamed { from, to });
+        } else {
+            for name in [from, to] {
+                if handled.insert(name.clone()) {
+                    events.push(existence_event(directory, name));
+                }
+            }
+        }
+    }
+    for name in touched {
+        if handled.insert(name.clone()) {
+            events.push(existence_event(directory, name));
+        }
+    }
+    events
+}
+
+fn existence_event(directory: &Path, name: String) -> DirectoryEvent {
+    if directory.join(&name).exists() {
+        DirectoryEvent::Added(name)
+    } else {
+        DirectoryEvent::Removed(name)
+    }
+}
+
+// 只保留下載資料夾最上層、看起來像譜面的項目
+fn top_level_name(directory: &Path, path: &Path) -> Option<String> {
+    if path.parent() != Some(directory) {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?.to_string();
+    // 下載中的暫存檔完成後會改名，只處理改名後的結果
+    if name.ends_with(PARTIAL_DOWNLOAD_EXTENSION) {
+        return None;
+    }
+    if name.to_lowercase().ends_with(".osz") || beatmapset_id_from_file_name(&name).is_some() {
+        Some(name)
+    } else {
+        None
+    }
+}